@@ -93,6 +93,9 @@ fn main() -> Result<()> {
         device: ID3D12DeviceVersion::Device(device.clone()),
         debug_settings: Default::default(),
         allocation_sizes: Default::default(),
+        sub_allocator: Default::default(),
+        allocation_strategy: Default::default(),
+        empty_block_retention: Default::default(),
     })
     .unwrap();
 
@@ -167,6 +170,8 @@ fn main() -> Result<()> {
                 size: alloc_info.SizeInBytes,
                 alignment: alloc_info.Alignment,
                 resource_category: ResourceCategory::Buffer,
+                allocation_strategy: None,
+                pool: None,
             })
             .unwrap();
 
@@ -215,6 +220,8 @@ fn main() -> Result<()> {
                 size: alloc_info.SizeInBytes,
                 alignment: alloc_info.Alignment,
                 resource_category: ResourceCategory::Buffer,
+                allocation_strategy: None,
+                pool: None,
             })
             .unwrap();
 
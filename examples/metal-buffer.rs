@@ -21,6 +21,9 @@ fn main() {
         device: device.clone(),
         debug_settings: Default::default(),
         allocation_sizes: Default::default(),
+        sub_allocator: Default::default(),
+        allocation_strategy: Default::default(),
+        empty_block_retention: Default::default(),
         create_residency_set: false,
     })
     .unwrap();
@@ -89,7 +89,15 @@ fn main() {
         physical_device: pdevice,
         debug_settings: Default::default(),
         buffer_device_address: false,
+        memory_budget: false,
+        get_memory_requirements2: false,
+        memory_priority: false,
+        pageable_device_local_memory: false,
+        heap_size_limits: vec![],
         allocation_sizes: Default::default(),
+        sub_allocator: Default::default(),
+        allocation_strategy: Default::default(),
+        empty_block_retention: Default::default(),
     })
     .unwrap();
 
@@ -109,6 +117,10 @@ fn main() {
                 location,
                 linear: true,
                 allocation_scheme: AllocationScheme::GpuAllocatorManaged,
+                allocation_strategy: None,
+                pool: None,
+                priority: None,
+                memory_type_preferences: Default::default(),
                 name: "Test allocation (Gpu Only)",
             })
             .unwrap();
@@ -142,6 +154,10 @@ fn main() {
                 location,
                 linear: true,
                 allocation_scheme: AllocationScheme::GpuAllocatorManaged,
+                allocation_strategy: None,
+                pool: None,
+                priority: None,
+                memory_type_preferences: Default::default(),
                 name: "Test allocation (Cpu to Gpu)",
             })
             .unwrap();
@@ -175,6 +191,10 @@ fn main() {
                 location,
                 linear: true,
                 allocation_scheme: AllocationScheme::GpuAllocatorManaged,
+                allocation_strategy: None,
+                pool: None,
+                priority: None,
+                memory_type_preferences: Default::default(),
                 name: "Test allocation (Gpu to Cpu)",
             })
             .unwrap();
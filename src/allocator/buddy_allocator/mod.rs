@@ -0,0 +1,389 @@
+#![deny(unsafe_code, clippy::unwrap_used)]
+//! Binary buddy sub-allocator.
+//!
+//! Every allocation is rounded up to a power-of-two sized block, placed at an offset that is a
+//! multiple of that size. Larger free blocks are split in halves ("buddies") until a block of the
+//! right size is available, and a freed block is merged back with its buddy whenever that buddy is
+//! free as well. Internal fragmentation is bounded to less than half of each block, and
+//! coalescing only ever has to look at a single buddy per size class.
+use alloc::{borrow::ToOwned, collections::BTreeSet, string::ToString, vec::Vec};
+#[cfg(all(feature = "std", not(feature = "hashbrown")))]
+use std::collections::HashMap;
+
+#[cfg(feature = "hashbrown")]
+use hashbrown::HashMap;
+use log::{log, Level};
+
+#[cfg(feature = "visualizer")]
+pub(crate) mod visualizer;
+
+use super::{
+    free_list_allocator::MemoryChunk, AllocationContext, AllocationReport, AllocationStrategy,
+    AllocationType, SubAllocator,
+};
+use crate::{AllocationError, Result};
+
+/// Smallest block that is handed out, smaller allocations are rounded up to this size.
+const MIN_BLOCK_SIZE: u64 = 256;
+
+#[derive(Debug)]
+pub(crate) struct BuddyAllocator {
+    size: u64,
+    allocated: u64,
+    pub(crate) chunk_id_counter: u64,
+    /// Live allocations. The size of each chunk is the size of the block it occupies.
+    pub(crate) chunks: HashMap<core::num::NonZeroU64, MemoryChunk>,
+    /// Offsets of the free blocks of size `1 << order`, indexed by `order`.
+    free_lists: Vec<BTreeSet<u64>>,
+}
+
+impl BuddyAllocator {
+    pub(crate) fn new(size: u64) -> Self {
+        let mut free_lists = vec![BTreeSet::new(); u64::BITS as usize];
+
+        // Cover the block with the largest possible naturally aligned power-of-two blocks, so that
+        // block sizes which are not a power of two are fully usable. Their missing buddies are
+        // simply never free, which stops merging at the end of the block.
+        let mut offset = 0;
+        while size - offset >= MIN_BLOCK_SIZE {
+            let order = (size - offset).ilog2().min(offset.trailing_zeros());
+            free_lists[order as usize].insert(offset);
+            offset += 1 << order;
+        }
+
+        Self {
+            size,
+            allocated: 0,
+            // 0 is not allowed as a chunk ID.
+            chunk_id_counter: 1,
+            chunks: HashMap::default(),
+            free_lists,
+        }
+    }
+
+    /// Generates a new unique chunk ID
+    fn get_new_chunk_id(&mut self) -> Result<core::num::NonZeroU64> {
+        if self.chunk_id_counter == u64::MAX {
+            // End of chunk id counter reached, no more allocations are possible.
+            return Err(AllocationError::OutOfMemory);
+        }
+
+        let id = self.chunk_id_counter;
+        self.chunk_id_counter += 1;
+        core::num::NonZeroU64::new(id).ok_or_else(|| {
+            AllocationError::Internal("New chunk id was 0, which is not allowed.".into())
+        })
+    }
+
+    /// Iterates over all free blocks as `(offset, size)` pairs.
+    #[cfg(feature = "visualizer")]
+    pub(crate) fn free_blocks(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.free_lists
+            .iter()
+            .enumerate()
+            .flat_map(|(order, offsets)| offsets.iter().map(move |&offset| (offset, 1 << order)))
+    }
+}
+
+impl SubAllocator for BuddyAllocator {
+    fn allocate(
+        &mut self,
+        size: u64,
+        alignment: u64,
+        allocation_type: AllocationType,
+        granularity: u64,
+        _strategy: AllocationStrategy,
+        name: &str,
+        context: AllocationContext,
+    ) -> Result<(u64, core::num::NonZeroU64)> {
+        // Blocks are aligned to their own size. Making them at least as large as the granularity
+        // means that no two allocations ever share a page, so there can't be any granularity
+        // conflicts between them.
+        let block_size = size
+            .max(alignment)
+            .max(granularity)
+            .max(MIN_BLOCK_SIZE)
+            .checked_next_power_of_two()
+            .ok_or(AllocationError::OutOfMemory)?;
+        if block_size > self.size - self.allocated {
+            return Err(AllocationError::OutOfMemory);
+        }
+
+        let order = block_size.trailing_zeros() as usize;
+        let (mut free_order, offset) = (order..self.free_lists.len())
+            .find_map(|order| {
+                self.free_lists[order]
+                    .first()
+                    .map(|&offset| (order, offset))
+            })
+            .ok_or(AllocationError::OutOfMemory)?;
+
+        let chunk_id = self.get_new_chunk_id()?;
+
+        self.free_lists[free_order].remove(&offset);
+        // Split the free block in halves until it has the requested size, returning the upper
+        // halves to the free lists.
+        while free_order > order {
+            free_order -= 1;
+            self.free_lists[free_order].insert(offset + (1 << free_order));
+        }
+
+        self.chunks.insert(
+            chunk_id,
+            MemoryChunk {
+                chunk_id,
+                size: block_size,
+                offset,
+                allocation_type,
+                alignment: 1,
+                padding: 0,
+                name: Some(name.to_string()),
+                context,
+                prev: None,
+                next: None,
+            },
+        );
+        self.allocated += block_size;
+
+        Ok((offset, chunk_id))
+    }
+
+    fn free(&mut self, chunk_id: Option<core::num::NonZeroU64>) -> Result<()> {
+        let chunk_id = chunk_id
+            .ok_or_else(|| AllocationError::Internal("Chunk ID must be a valid value.".into()))?;
+
+        let chunk = self.chunks.remove(&chunk_id).ok_or_else(|| {
+            AllocationError::Internal("Attempting to free chunk that is not in chunk list.".into())
+        })?;
+        self.allocated -= chunk.size;
+
+        // Merge with the buddy for as long as it is free as well.
+        let mut offset = chunk.offset;
+        let mut order = chunk.size.trailing_zeros() as usize;
+        while order + 1 < self.free_lists.len()
+            && self.free_lists[order].remove(&(offset ^ (1 << order)))
+        {
+            offset &= !(1 << order);
+            order += 1;
+        }
+        self.free_lists[order].insert(offset);
+
+        Ok(())
+    }
+
+    fn rename_allocation(
+        &mut self,
+        chunk_id: Option<core::num::NonZeroU64>,
+        name: &str,
+    ) -> Result<()> {
+        let chunk_id = chunk_id
+            .ok_or_else(|| AllocationError::Internal("Chunk ID must be a valid value.".into()))?;
+
+        let chunk = self.chunks.get_mut(&chunk_id).ok_or_else(|| {
+            AllocationError::Internal(
+                "Attempting to rename chunk that is not in chunk list.".into(),
+            )
+        })?;
+
+        chunk.name = Some(name.into());
+
+        Ok(())
+    }
+
+    fn report_memory_leaks(
+        &self,
+        log_level: Level,
+        memory_type_index: usize,
+        memory_block_index: usize,
+    ) {
+        for (chunk_id, chunk) in self.chunks.iter() {
+            let empty = "".to_string();
+            let name = chunk.name.as_ref().unwrap_or(&empty);
+            let backtrace_info;
+            #[cfg(feature = "std")]
+            {
+                // TODO: Allocation could be avoided here if https://github.com/rust-lang/rust/pull/139135 is merged and stabilized.
+                backtrace_info = format!(
+                    ",
+        backtrace: {}",
+                    chunk.context.backtrace
+                )
+            }
+            #[cfg(not(feature = "std"))]
+            {
+                backtrace_info = ""
+            }
+            log!(
+                log_level,
+                r#"leak detected: {{
+    memory type: {}
+    memory block: {}
+    chunk: {{
+        chunk_id: {},
+        size: 0x{:x},
+        offset: 0x{:x},
+        allocation_type: {:?},
+        name: {}{backtrace_info}
+    }}
+}}"#,
+                memory_type_index,
+                memory_block_index,
+                chunk_id,
+                chunk.size,
+                chunk.offset,
+                chunk.allocation_type,
+                name,
+            );
+        }
+    }
+
+    fn report_allocations(&self) -> Vec<AllocationReport> {
+        self.chunks
+            .values()
+            .map(|chunk| AllocationReport {
+                name: chunk
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| "<Unnamed buddy allocation>".to_owned()),
+                offset: chunk.offset,
+                size: chunk.size,
+                #[cfg(feature = "visualizer")]
+                backtrace: chunk.context.backtrace.clone(),
+            })
+            .collect::<Vec<_>>()
+    }
+
+    fn allocated(&self) -> u64 {
+        self.allocated
+    }
+
+    fn supports_general_allocations(&self) -> bool {
+        true
+    }
+
+    #[cfg(feature = "visualizer")]
+    fn visualizer(&self) -> Option<&dyn crate::visualizer::SubAllocatorVisualizer> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allocate(
+        allocator: &mut BuddyAllocator,
+        size: u64,
+        granularity: u64,
+    ) -> Result<(u64, core::num::NonZeroU64)> {
+        allocator.allocate(
+            size,
+            1,
+            AllocationType::Linear,
+            granularity,
+            AllocationStrategy::default(),
+            "test",
+            AllocationContext::default(),
+        )
+    }
+
+    /// Returns all free blocks as `(offset, size)` pairs, sorted by offset.
+    fn free_blocks(allocator: &BuddyAllocator) -> Vec<(u64, u64)> {
+        let mut blocks = allocator
+            .free_lists
+            .iter()
+            .enumerate()
+            .flat_map(|(order, offsets)| offsets.iter().map(move |&offset| (offset, 1 << order)))
+            .collect::<Vec<_>>();
+        blocks.sort_unstable();
+        blocks
+    }
+
+    #[test]
+    fn split_and_coalesce_round_trip() -> Result<()> {
+        let mut allocator = BuddyAllocator::new(4096);
+        assert_eq!(free_blocks(&allocator), [(0, 4096)]);
+
+        let (offset_a, a) = allocate(&mut allocator, 100, 1)?;
+        assert_eq!(offset_a, 0);
+        assert_eq!(allocator.allocated(), MIN_BLOCK_SIZE);
+        assert_eq!(
+            free_blocks(&allocator),
+            [(256, 256), (512, 512), (1024, 1024), (2048, 2048)]
+        );
+
+        let (offset_b, b) = allocate(&mut allocator, 1000, 1)?;
+        assert_eq!(offset_b, 1024);
+        let (offset_c, c) = allocate(&mut allocator, 256, 1)?;
+        assert_eq!(offset_c, 256);
+
+        allocator.free(Some(b))?;
+        allocator.free(Some(a))?;
+        assert_eq!(
+            free_blocks(&allocator),
+            [(0, 256), (512, 512), (1024, 1024), (2048, 2048)]
+        );
+        allocator.free(Some(c))?;
+
+        assert_eq!(allocator.allocated(), 0);
+        assert_eq!(free_blocks(&allocator), [(0, 4096)]);
+        assert!(matches!(
+            allocator.free(Some(c)),
+            Err(AllocationError::Internal(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn covers_blocks_that_are_not_a_power_of_two() -> Result<()> {
+        let mut allocator = BuddyAllocator::new(3072);
+        assert_eq!(free_blocks(&allocator), [(0, 2048), (2048, 1024)]);
+
+        let (_, a) = allocate(&mut allocator, 2048, 1)?;
+        let (offset, b) = allocate(&mut allocator, 1024, 1)?;
+        assert_eq!(offset, 2048);
+
+        allocator.free(Some(b))?;
+        allocator.free(Some(a))?;
+        assert_eq!(free_blocks(&allocator), [(0, 2048), (2048, 1024)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn granularity_rounds_up_blocks() -> Result<()> {
+        let mut allocator = BuddyAllocator::new(4096);
+
+        let (offset_a, _) = allocate(&mut allocator, 16, 1024)?;
+        let (offset_b, _) = allocate(&mut allocator, 16, 1024)?;
+        assert_eq!((offset_a, offset_b), (0, 1024));
+        assert_eq!(allocator.allocated(), 2048);
+
+        Ok(())
+    }
+
+    #[test]
+    fn out_of_memory_when_exhausted() -> Result<()> {
+        let mut allocator = BuddyAllocator::new(4096);
+
+        assert!(matches!(
+            allocate(&mut allocator, 8192, 1),
+            Err(AllocationError::OutOfMemory)
+        ));
+        assert!(matches!(
+            allocate(&mut allocator, u64::MAX, 1),
+            Err(AllocationError::OutOfMemory)
+        ));
+
+        for _ in 0..4 {
+            allocate(&mut allocator, 1024, 1)?;
+        }
+        assert!(matches!(
+            allocate(&mut allocator, 1, 1),
+            Err(AllocationError::OutOfMemory)
+        ));
+
+        Ok(())
+    }
+}
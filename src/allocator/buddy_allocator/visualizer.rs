@@ -0,0 +1,53 @@
+use super::BuddyAllocator;
+use crate::{
+    allocator::{free_list_allocator::MemoryChunk, AllocationContext, AllocationType},
+    visualizer::{
+        render_sparse_memory_chunks_ui, ColorScheme, MemoryChunksVisualizationSettings,
+        SubAllocatorVisualizer,
+    },
+};
+
+impl SubAllocatorVisualizer for BuddyAllocator {
+    fn supports_visualization(&self) -> bool {
+        true
+    }
+
+    fn draw_base_info(&self, ui: &mut egui::Ui) {
+        ui.label("buddy sub-allocator");
+        ui.label(format!("chunk count: {}", self.chunks.len()));
+        ui.label(format!("free block count: {}", self.free_blocks().count()));
+        ui.label(format!("chunk id counter: {}", self.chunk_id_counter));
+    }
+
+    fn draw_visualization(
+        &self,
+        color_scheme: &ColorScheme,
+        ui: &mut egui::Ui,
+        settings: &MemoryChunksVisualizationSettings,
+    ) {
+        // Draw every free block separately, to show how the memory block is currently split.
+        let free_blocks = self
+            .free_blocks()
+            .map(|(offset, size)| MemoryChunk {
+                chunk_id: core::num::NonZeroU64::MAX,
+                size,
+                offset,
+                allocation_type: AllocationType::Free,
+                alignment: 1,
+                padding: 0,
+                name: None,
+                context: AllocationContext::default(),
+                prev: None,
+                next: None,
+            })
+            .collect::<Vec<_>>();
+
+        render_sparse_memory_chunks_ui(
+            ui,
+            color_scheme,
+            settings,
+            self.size,
+            self.chunks.values().chain(&free_blocks),
+        );
+    }
+}
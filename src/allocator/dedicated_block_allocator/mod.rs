@@ -1,20 +1,18 @@
 #![deny(unsafe_code, clippy::unwrap_used)]
-#[cfg(feature = "std")]
-use alloc::sync::Arc;
 use alloc::{
     borrow::ToOwned,
     string::{String, ToString},
     vec::Vec,
 };
-#[cfg(feature = "std")]
-use std::backtrace::Backtrace;
 
 use log::{log, Level};
 
 #[cfg(feature = "visualizer")]
 pub(crate) mod visualizer;
 
-use super::{AllocationReport, AllocationType, SubAllocator, SubAllocatorBase};
+use super::{
+    AllocationContext, AllocationReport, AllocationStrategy, AllocationType, SubAllocator,
+};
 use crate::{AllocationError, Result};
 
 #[derive(Debug)]
@@ -23,8 +21,7 @@ pub(crate) struct DedicatedBlockAllocator {
     allocated: u64,
     /// Only used if [`crate::AllocatorDebugSettings::store_stack_traces`] is [`true`]
     name: Option<String>,
-    #[cfg(feature = "std")]
-    backtrace: Arc<Backtrace>,
+    context: AllocationContext,
 }
 
 impl DedicatedBlockAllocator {
@@ -33,13 +30,11 @@ impl DedicatedBlockAllocator {
             size,
             allocated: 0,
             name: None,
-            #[cfg(feature = "std")]
-            backtrace: Arc::new(Backtrace::disabled()),
+            context: AllocationContext::default(),
         }
     }
 }
 
-impl SubAllocatorBase for DedicatedBlockAllocator {}
 impl SubAllocator for DedicatedBlockAllocator {
     fn allocate(
         &mut self,
@@ -47,8 +42,9 @@ impl SubAllocator for DedicatedBlockAllocator {
         _alignment: u64,
         _allocation_type: AllocationType,
         _granularity: u64,
+        _strategy: AllocationStrategy,
         name: &str,
-        #[cfg(feature = "std")] backtrace: Arc<Backtrace>,
+        context: AllocationContext,
     ) -> Result<(u64, core::num::NonZeroU64)> {
         if self.allocated != 0 {
             return Err(AllocationError::OutOfMemory);
@@ -62,10 +58,7 @@ impl SubAllocator for DedicatedBlockAllocator {
 
         self.allocated = size;
         self.name = Some(name.to_string());
-        #[cfg(feature = "std")]
-        {
-            self.backtrace = backtrace;
-        }
+        self.context = context;
 
         #[allow(clippy::unwrap_used)]
         let dummy_id = core::num::NonZeroU64::new(1).unwrap();
@@ -109,7 +102,7 @@ impl SubAllocator for DedicatedBlockAllocator {
             backtrace_info = format!(
                 ",
         backtrace: {}",
-                self.backtrace
+                self.context.backtrace
             )
         }
         #[cfg(not(feature = "std"))]
@@ -143,7 +136,7 @@ impl SubAllocator for DedicatedBlockAllocator {
             offset: 0,
             size: self.size,
             #[cfg(feature = "visualizer")]
-            backtrace: self.backtrace.clone(),
+            backtrace: self.context.backtrace.clone(),
         }]
     }
 
@@ -154,4 +147,9 @@ impl SubAllocator for DedicatedBlockAllocator {
     fn supports_general_allocations(&self) -> bool {
         false
     }
+
+    #[cfg(feature = "visualizer")]
+    fn visualizer(&self) -> Option<&dyn crate::visualizer::SubAllocatorVisualizer> {
+        Some(self)
+    }
 }
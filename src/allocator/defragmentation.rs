@@ -0,0 +1,342 @@
+//! Planning moves of live allocations out of sparsely used memory blocks, so that whole memory
+//! blocks are emptied and released.
+//!
+//! Planning reserves room for every moved allocation in another memory block of the same memory
+//! type. The caller then copies the data and recreates its resources on the reserved memory,
+//! after which committing the plan frees the original allocations and with that the emptied
+//! memory blocks.
+
+use alloc::{string::String, vec, vec::Vec};
+use core::num::NonZeroU64;
+
+use super::{generic_allocator::MemoryType, GenericAllocator, MemoryBackend, SubAllocation};
+use crate::Result;
+
+/// Identifies a live allocation: its memory type index, memory block index and chunk ID.
+pub(crate) type AllocationKey = (usize, usize, NonZeroU64);
+
+impl SubAllocation {
+    fn key(&self) -> AllocationKey {
+        (
+            self.memory_type_index,
+            self.memory_block_index,
+            self.chunk_id,
+        )
+    }
+}
+
+/// A move planned by [`GenericAllocator::plan_defragmentation()`].
+#[derive(Debug)]
+pub(crate) struct AllocationMove {
+    pub(crate) source: SubAllocation,
+    /// The room reserved for the allocation in another memory block.
+    pub(crate) destination: SubAllocation,
+    pub(crate) name: String,
+}
+
+impl AllocationMove {
+    /// Wraps this move for the caller, with `destination` being the backend `Allocation` handle
+    /// for the reserved room.
+    pub(crate) fn with_destination<A>(self, destination: A) -> DefragmentationMove<A> {
+        DefragmentationMove {
+            source: self.source,
+            reservation: self.destination,
+            destination,
+        }
+    }
+}
+
+/// A planned move of an allocation into another memory block of the same memory type.
+///
+/// `A` is the `Allocation` type of the backend the plan was made for.
+#[derive(Debug)]
+pub struct DefragmentationMove<A> {
+    pub(crate) source: SubAllocation,
+    pub(crate) reservation: SubAllocation,
+    pub(crate) destination: A,
+}
+
+impl<A> DefragmentationMove<A> {
+    /// Returns the allocation the data has to be copied into, which replaces the moved
+    /// allocation when the plan is committed.
+    pub fn destination(&self) -> &A {
+        &self.destination
+    }
+
+    /// Returns the offset of the moved allocation in its current memory block.
+    pub fn source_offset(&self) -> u64 {
+        self.source.offset
+    }
+
+    /// Returns the number of bytes to copy.
+    pub fn size(&self) -> u64 {
+        self.source.size
+    }
+
+    /// Returns [`true`] if this move relocates the allocation found at `key`.
+    pub(crate) fn moves(&self, key: Option<AllocationKey>) -> bool {
+        key == Some(self.source.key())
+    }
+}
+
+/// Moves of allocations that empty whole memory blocks, computed by
+/// `Allocator::plan_defragmentation()`.
+///
+/// The destinations of all moves stay reserved until the plan is either committed with
+/// `Allocator::commit_defragmentation()` or cancelled with `Allocator::cancel_defragmentation()`,
+/// and the moved allocations must not be freed in the meantime.
+#[derive(Debug)]
+#[must_use = "the destinations of the moves stay reserved until the plan is committed or cancelled"]
+pub struct DefragmentationPlan<A> {
+    pub(crate) moves: Vec<DefragmentationMove<A>>,
+}
+
+impl<A> DefragmentationPlan<A> {
+    pub fn moves(&self) -> &[DefragmentationMove<A>] {
+        &self.moves
+    }
+
+    /// Returns [`true`] if there is nothing to move.
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
+    }
+
+    /// Returns the total number of bytes to copy to carry out the plan.
+    pub fn bytes_to_move(&self) -> u64 {
+        self.moves.iter().map(DefragmentationMove::size).sum()
+    }
+}
+
+impl<B: MemoryBackend> MemoryType<B> {
+    /// Plans moves that empty the least used memory blocks of this memory type into the most used
+    /// ones, as long as they fit in `bytes_left`.
+    ///
+    /// Allocations only move between the shared memory blocks, or between the memory blocks of a
+    /// single custom pool.
+    fn plan_defragmentation(
+        &mut self,
+        granularity: u64,
+        bytes_left: &mut u64,
+        moves: &mut Vec<AllocationMove>,
+    ) -> Result<()> {
+        let pools = (0..self.pools.len())
+            .filter(|&pool| self.pools[pool].is_some())
+            .collect::<Vec<_>>();
+        for pool in core::iter::once(None).chain(pools.into_iter().map(Some)) {
+            self.plan_pool_defragmentation(pool, granularity, bytes_left, moves)?;
+        }
+
+        Ok(())
+    }
+
+    /// Plans the moves of [`MemoryType::plan_defragmentation()`] within the shared memory blocks
+    /// if `pool` is [`None`], or otherwise within the memory blocks of that custom pool.
+    fn plan_pool_defragmentation(
+        &mut self,
+        pool: Option<usize>,
+        granularity: u64,
+        bytes_left: &mut u64,
+        moves: &mut Vec<AllocationMove>,
+    ) -> Result<()> {
+        let strategy = match pool.and_then(|pool| self.pools[pool].as_ref()) {
+            Some(pool) => pool.allocation_strategy,
+            None => self.allocation_strategy,
+        };
+
+        // Dedicated blocks and linear pools never share their memory with other allocations.
+        let mut blocks = self
+            .memory_blocks
+            .iter()
+            .enumerate()
+            .filter_map(|(index, block)| {
+                let block = block.as_ref()?;
+                // Transient attachments are not worth moving, and must stay apart from the rest.
+                (!block.linear_pool
+                    && !block.transient
+                    && block.pool == pool
+                    && block.sub_allocator.supports_general_allocations())
+                .then(|| (index, block.sub_allocator.allocated()))
+            })
+            .collect::<Vec<_>>();
+        blocks.sort_by_key(|&(_, allocated)| allocated);
+
+        let mut is_emptied = vec![false; self.memory_blocks.len()];
+        let mut is_destination = vec![false; self.memory_blocks.len()];
+
+        for &(source_index, allocated) in &blocks {
+            if allocated == 0 || is_destination[source_index] {
+                continue;
+            }
+
+            let Some(source_block) = &self.memory_blocks[source_index] else {
+                continue;
+            };
+            let mut allocations = source_block.sub_allocator.movable_allocations();
+            let bytes_to_move: u64 = allocations.iter().map(|allocation| allocation.size).sum();
+            if allocations.is_empty() || bytes_to_move > *bytes_left {
+                continue;
+            }
+
+            // Place the largest allocations first, while there is the most room left.
+            allocations.sort_by_key(|allocation| core::cmp::Reverse(allocation.size));
+
+            let first_move = moves.len();
+            for allocation in allocations {
+                let source = SubAllocation {
+                    chunk_id: allocation.chunk_id,
+                    offset: allocation.offset,
+                    size: allocation.size,
+                    memory_type_index: self.memory_type_index,
+                    memory_block_index: source_index,
+                };
+
+                let mut destination = None;
+                for &(destination_index, _) in blocks.iter().rev() {
+                    if destination_index == source_index || is_emptied[destination_index] {
+                        continue;
+                    }
+
+                    let Some(destination_block) = &mut self.memory_blocks[destination_index] else {
+                        continue;
+                    };
+                    match destination_block.sub_allocator.allocate(
+                        allocation.size,
+                        allocation.alignment,
+                        allocation.allocation_type,
+                        granularity,
+                        strategy,
+                        &allocation.name,
+                        allocation.context.clone(),
+                    ) {
+                        Ok((offset, chunk_id)) => {
+                            destination = Some(SubAllocation {
+                                chunk_id,
+                                offset,
+                                size: allocation.size,
+                                memory_type_index: self.memory_type_index,
+                                memory_block_index: destination_index,
+                            });
+                            break;
+                        }
+                        Err(crate::AllocationError::OutOfMemory) => {}
+                        Err(err) => return Err(err),
+                    }
+                }
+
+                let Some(destination) = destination else {
+                    // The block can't be emptied, release what was reserved for it so far.
+                    for planned in moves.drain(first_move..) {
+                        self.free_reservation(&planned.destination)?;
+                    }
+                    break;
+                };
+
+                moves.push(AllocationMove {
+                    source,
+                    destination,
+                    name: allocation.name,
+                });
+            }
+
+            if moves.len() > first_move {
+                is_emptied[source_index] = true;
+                for planned in &moves[first_move..] {
+                    is_destination[planned.destination.memory_block_index] = true;
+                }
+                *bytes_left -= bytes_to_move;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Releases room reserved by [`MemoryType::plan_defragmentation()`], without destroying the
+    /// memory block it was reserved in.
+    fn free_reservation(&mut self, reservation: &SubAllocation) -> Result<()> {
+        self.memory_blocks[reservation.memory_block_index]
+            .as_mut()
+            .ok_or_else(|| crate::AllocationError::Internal("Memory block must be Some.".into()))?
+            .sub_allocator
+            .free(Some(reservation.chunk_id))
+    }
+}
+
+impl<B: MemoryBackend> GenericAllocator<B> {
+    /// Plans moves that empty whole shared memory blocks by moving their allocations into other
+    /// memory blocks of the same memory type, copying at most `max_bytes_to_move` bytes.
+    ///
+    /// Only memory blocks whose sub-allocator lists its allocations through
+    /// [`super::SubAllocator::movable_allocations()`] are emptied.
+    pub(crate) fn plan_defragmentation(
+        &mut self,
+        max_bytes_to_move: u64,
+        granularity: u64,
+    ) -> Result<Vec<AllocationMove>> {
+        let mut bytes_left = max_bytes_to_move;
+        let mut moves = Vec::new();
+        for memory_type in &mut self.memory_types {
+            memory_type.plan_defragmentation(granularity, &mut bytes_left, &mut moves)?;
+        }
+
+        moves.sort_by_key(|planned| planned.source.key());
+
+        Ok(moves)
+    }
+
+    /// Frees the source of every move whose allocation is passed in, replacing the allocation with
+    /// the destination of the move. The moves of all other allocations are cancelled.
+    ///
+    /// `key` returns where an allocation lives, or [`None`] for null allocations.
+    pub(crate) fn commit_defragmentation<'a, A: 'a>(
+        &mut self,
+        plan: DefragmentationPlan<A>,
+        allocations: impl IntoIterator<Item = &'a mut A>,
+        key: impl Fn(&A) -> Option<AllocationKey>,
+    ) -> Result<()> {
+        let keys = plan
+            .moves
+            .iter()
+            .map(|planned| planned.source.key())
+            .collect::<Vec<_>>();
+        let mut moves = plan.moves.into_iter().map(Some).collect::<Vec<_>>();
+
+        for allocation in allocations {
+            let Some(index) = key(allocation).and_then(|key| keys.binary_search(&key).ok()) else {
+                continue;
+            };
+            let Some(planned) = moves[index].take() else {
+                continue;
+            };
+
+            self.free(
+                planned.source.memory_type_index,
+                planned.source.memory_block_index,
+                Some(planned.source.chunk_id),
+            )?;
+            *allocation = planned.destination;
+        }
+
+        for planned in moves.into_iter().flatten() {
+            self.free(
+                planned.reservation.memory_type_index,
+                planned.reservation.memory_block_index,
+                Some(planned.reservation.chunk_id),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Releases the destinations of all moves, leaving the allocations where they are.
+    pub(crate) fn cancel_defragmentation<A>(&mut self, plan: DefragmentationPlan<A>) -> Result<()> {
+        for planned in plan.moves {
+            self.free(
+                planned.reservation.memory_type_index,
+                planned.reservation.memory_block_index,
+                Some(planned.reservation.chunk_id),
+            )?;
+        }
+
+        Ok(())
+    }
+}
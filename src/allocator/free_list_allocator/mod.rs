@@ -1,13 +1,9 @@
 #![deny(unsafe_code, clippy::unwrap_used)]
-#[cfg(feature = "std")]
-use alloc::sync::Arc;
 use alloc::{
     borrow::ToOwned,
     string::{String, ToString},
     vec::Vec,
 };
-#[cfg(feature = "std")]
-use std::backtrace::Backtrace;
 #[cfg(all(feature = "std", not(feature = "hashbrown")))]
 use std::collections::{HashMap, HashSet};
 
@@ -18,31 +14,27 @@ use log::{log, Level};
 #[cfg(feature = "visualizer")]
 pub(crate) mod visualizer;
 
-use super::{AllocationReport, AllocationType, SubAllocator, SubAllocatorBase};
+use super::{
+    align_up, has_granularity_conflict, is_on_same_page, AllocationContext, AllocationReport,
+    AllocationStrategy, AllocationType, MovableAllocation, SubAllocator,
+};
 use crate::{AllocationError, Result};
 
-const USE_BEST_FIT: bool = true;
-
-fn align_down(val: u64, alignment: u64) -> u64 {
-    val & !(alignment - 1u64)
-}
-
-fn align_up(val: u64, alignment: u64) -> u64 {
-    align_down(val + alignment - 1u64, alignment)
-}
-
 #[derive(Debug)]
 pub(crate) struct MemoryChunk {
     pub(crate) chunk_id: core::num::NonZeroU64,
     pub(crate) size: u64,
     pub(crate) offset: u64,
     pub(crate) allocation_type: AllocationType,
+    /// Alignment that was requested for the allocation held by this chunk.
+    pub(crate) alignment: u64,
+    /// Number of bytes at the start of the chunk that precede the aligned allocation.
+    pub(crate) padding: u64,
     pub(crate) name: Option<String>,
     /// Only used if [`crate::AllocatorDebugSettings::store_stack_traces`] is [`true`]
-    #[cfg(feature = "std")]
-    pub(crate) backtrace: Arc<Backtrace>,
-    next: Option<core::num::NonZeroU64>,
-    prev: Option<core::num::NonZeroU64>,
+    pub(crate) context: AllocationContext,
+    pub(crate) next: Option<core::num::NonZeroU64>,
+    pub(crate) prev: Option<core::num::NonZeroU64>,
 }
 
 #[derive(Debug)]
@@ -54,25 +46,6 @@ pub(crate) struct FreeListAllocator {
     free_chunks: HashSet<core::num::NonZeroU64>,
 }
 
-/// Test if two suballocations will overlap the same page.
-fn is_on_same_page(offset_a: u64, size_a: u64, offset_b: u64, page_size: u64) -> bool {
-    let end_a = offset_a + size_a - 1;
-    let end_page_a = align_down(end_a, page_size);
-    let start_b = offset_b;
-    let start_page_b = align_down(start_b, page_size);
-
-    end_page_a == start_page_b
-}
-
-/// Test if two allocation types will be conflicting or not.
-fn has_granularity_conflict(type0: AllocationType, type1: AllocationType) -> bool {
-    if type0 == AllocationType::Free || type1 == AllocationType::Free {
-        return false;
-    }
-
-    type0 != type1
-}
-
 impl FreeListAllocator {
     pub(crate) fn new(size: u64) -> Self {
         #[allow(clippy::unwrap_used)]
@@ -86,9 +59,10 @@ impl FreeListAllocator {
                 size,
                 offset: 0,
                 allocation_type: AllocationType::Free,
+                alignment: 1,
+                padding: 0,
                 name: None,
-                #[cfg(feature = "std")]
-                backtrace: Arc::new(Backtrace::disabled()),
+                context: AllocationContext::default(),
                 prev: None,
                 next: None,
             },
@@ -162,7 +136,6 @@ impl FreeListAllocator {
     }
 }
 
-impl SubAllocatorBase for FreeListAllocator {}
 impl SubAllocator for FreeListAllocator {
     fn allocate(
         &mut self,
@@ -170,8 +143,9 @@ impl SubAllocator for FreeListAllocator {
         alignment: u64,
         allocation_type: AllocationType,
         granularity: u64,
+        strategy: AllocationStrategy,
         name: &str,
-        #[cfg(feature = "std")] backtrace: Arc<Backtrace>,
+        context: AllocationContext,
     ) -> Result<(u64, core::num::NonZeroU64)> {
         let free_size = self.size - self.allocated;
         if size > free_size {
@@ -225,20 +199,23 @@ impl SubAllocator for FreeListAllocator {
                 }
             }
 
-            if USE_BEST_FIT {
-                if best_fit_id.is_none() || current_chunk.size < best_chunk_size {
-                    best_fit_id = Some(*current_chunk_id);
-                    best_aligned_size = aligned_size;
-                    best_offset = offset;
-
-                    best_chunk_size = current_chunk.size;
+            let is_better_fit = best_fit_id.is_none()
+                || match strategy {
+                    AllocationStrategy::BestFit => current_chunk.size < best_chunk_size,
+                    AllocationStrategy::WorstFit => current_chunk.size > best_chunk_size,
+                    AllocationStrategy::LowestOffset => offset < best_offset,
+                    AllocationStrategy::FirstFit => false,
                 };
-            } else {
+
+            if is_better_fit {
                 best_fit_id = Some(*current_chunk_id);
                 best_aligned_size = aligned_size;
                 best_offset = offset;
 
                 best_chunk_size = current_chunk.size;
+            }
+
+            if strategy == AllocationStrategy::FirstFit {
                 break;
             }
         }
@@ -257,9 +234,10 @@ impl SubAllocator for FreeListAllocator {
                     size: best_aligned_size,
                     offset: free_chunk.offset,
                     allocation_type,
+                    alignment,
+                    padding: best_offset - free_chunk.offset,
                     name: Some(name.to_string()),
-                    #[cfg(feature = "std")]
-                    backtrace,
+                    context,
                     prev: free_chunk.prev,
                     next: Some(first_fit_id),
                 };
@@ -287,11 +265,10 @@ impl SubAllocator for FreeListAllocator {
                 .ok_or_else(|| AllocationError::Internal("Invalid chunk reference.".into()))?;
 
             chunk.allocation_type = allocation_type;
+            chunk.alignment = alignment;
+            chunk.padding = best_offset - chunk.offset;
             chunk.name = Some(name.to_string());
-            #[cfg(feature = "std")]
-            {
-                chunk.backtrace = backtrace;
-            }
+            chunk.context = context;
 
             self.remove_id_from_free_list(first_fit_id);
 
@@ -314,11 +291,10 @@ impl SubAllocator for FreeListAllocator {
                 )
             })?;
             chunk.allocation_type = AllocationType::Free;
+            chunk.alignment = 1;
+            chunk.padding = 0;
             chunk.name = None;
-            #[cfg(feature = "std")]
-            {
-                chunk.backtrace = Arc::new(Backtrace::disabled());
-            }
+            chunk.context = AllocationContext::default();
 
             self.allocated -= chunk.size;
 
@@ -385,7 +361,7 @@ impl SubAllocator for FreeListAllocator {
                 backtrace_info = format!(
                     ",
         backtrace: {}",
-                    chunk.backtrace
+                    chunk.context.backtrace
                 )
             }
             #[cfg(not(feature = "std"))]
@@ -428,11 +404,27 @@ impl SubAllocator for FreeListAllocator {
                 offset: chunk.offset,
                 size: chunk.size,
                 #[cfg(feature = "visualizer")]
-                backtrace: chunk.backtrace.clone(),
+                backtrace: chunk.context.backtrace.clone(),
             })
             .collect::<Vec<_>>()
     }
 
+    fn movable_allocations(&self) -> Vec<MovableAllocation> {
+        self.chunks
+            .values()
+            .filter(|chunk| chunk.allocation_type != AllocationType::Free)
+            .map(|chunk| MovableAllocation {
+                chunk_id: chunk.chunk_id,
+                offset: chunk.offset + chunk.padding,
+                size: chunk.size - chunk.padding,
+                alignment: chunk.alignment,
+                allocation_type: chunk.allocation_type,
+                name: chunk.name.clone().unwrap_or_default(),
+                context: chunk.context.clone(),
+            })
+            .collect()
+    }
+
     fn allocated(&self) -> u64 {
         self.allocated
     }
@@ -440,4 +432,85 @@ impl SubAllocator for FreeListAllocator {
     fn supports_general_allocations(&self) -> bool {
         true
     }
+
+    #[cfg(feature = "visualizer")]
+    fn visualizer(&self) -> Option<&dyn crate::visualizer::SubAllocatorVisualizer> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allocate(
+        allocator: &mut FreeListAllocator,
+        size: u64,
+        strategy: AllocationStrategy,
+    ) -> Result<(u64, core::num::NonZeroU64)> {
+        allocator.allocate(
+            size,
+            1,
+            AllocationType::Linear,
+            1,
+            strategy,
+            "test",
+            AllocationContext::default(),
+        )
+    }
+
+    /// Returns an allocator with free chunks of 256 bytes at 0, 1024 bytes at 512, 512 bytes at
+    /// 1792 and 1536 bytes at 2560.
+    fn fragmented() -> Result<FreeListAllocator> {
+        let mut allocator = FreeListAllocator::new(4096);
+        let mut chunk_ids = Vec::new();
+        for size in [256, 256, 1024, 256, 512, 256] {
+            chunk_ids.push(allocate(&mut allocator, size, AllocationStrategy::default())?.1);
+        }
+        for i in [0, 2, 4] {
+            allocator.free(Some(chunk_ids[i]))?;
+        }
+        assert_eq!(allocator.free_chunks.len(), 4);
+
+        Ok(allocator)
+    }
+
+    #[test]
+    fn best_fit_picks_the_smallest_free_chunk() -> Result<()> {
+        let mut allocator = fragmented()?;
+        assert_eq!(
+            allocate(&mut allocator, 300, AllocationStrategy::BestFit)?.0,
+            1792
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn worst_fit_picks_the_largest_free_chunk() -> Result<()> {
+        let mut allocator = fragmented()?;
+        assert_eq!(
+            allocate(&mut allocator, 300, AllocationStrategy::WorstFit)?.0,
+            2560
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn lowest_offset_picks_the_first_free_chunk_in_the_block() -> Result<()> {
+        let mut allocator = fragmented()?;
+        assert_eq!(
+            allocate(&mut allocator, 300, AllocationStrategy::LowestOffset)?.0,
+            512
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn first_fit_picks_any_free_chunk_that_fits() -> Result<()> {
+        let mut allocator = fragmented()?;
+        // Free chunks are not searched in any particular order.
+        let (offset, _) = allocate(&mut allocator, 300, AllocationStrategy::FirstFit)?;
+        assert!([512, 1792, 2560].contains(&offset));
+        Ok(())
+    }
 }
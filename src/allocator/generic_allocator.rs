@@ -0,0 +1,1593 @@
+//! Backend-agnostic bookkeeping of memory types and their memory blocks.
+//!
+//! Finding room for an allocation, creating and destroying memory blocks as needed, and freeing,
+//! renaming and reporting allocations works the same for every graphics API. That logic lives in
+//! [`GenericAllocator`], which only calls into a [`MemoryBackend`] to create and destroy the raw
+//! memory objects backing each [`MemoryBlock`].
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use core::{
+    fmt,
+    num::NonZeroU64,
+    ops::Deref,
+    sync::atomic::{AtomicU64, Ordering},
+};
+#[cfg(feature = "std")]
+use std::backtrace::Backtrace;
+
+use log::Level;
+
+use super::{
+    align_up, AllocationContext, AllocationStrategy, AllocationType, AllocatorReport,
+    DedicatedBlockAllocator, EmptyBlockRetention, MemoryBlockReport, SubAllocator,
+    SubAllocatorKind,
+};
+use crate::{AllocationError, AllocationSizes, AllocatorDebugSettings, MemoryLocation, Result};
+
+/// The graphics API specific part of an allocator: creating and destroying raw memory objects.
+pub(crate) trait MemoryBackend {
+    /// Describes a memory type, with everything needed to create memory objects of that type.
+    type MemoryTypeProperties: fmt::Debug;
+    /// A raw memory object backing a [`MemoryBlock`], including its mapping if it has one.
+    type Memory: fmt::Debug;
+    /// A resource that a memory object can be dedicated to, which some drivers use to optimize
+    /// the memory for that resource.
+    type DedicatedResource: Copy;
+
+    /// Returns [`true`] if memory of this type is visible to the host, which selects between the
+    /// host and device memory block sizes of [`AllocationSizes`].
+    fn is_host_visible(properties: &Self::MemoryTypeProperties) -> bool;
+
+    /// Returns the index of the memory heap that memory of this type is taken from.
+    fn heap_index(properties: &Self::MemoryTypeProperties) -> usize;
+
+    /// Returns the size that the offset and size of allocations in memory of this type are
+    /// aligned to, so that host caches can be flushed and invalidated in whole units of it
+    /// without touching neighbouring allocations. `1` if there is no such constraint.
+    fn atom_size(properties: &Self::MemoryTypeProperties) -> u64;
+
+    /// Creates a memory object of `size` bytes in the given memory type, and maps it when the
+    /// backend hands out mapped pointers for that memory type.
+    ///
+    /// `priority` is the priority of the memory object relative to other memory objects when
+    /// memory is oversubscribed, between `0.0` and `1.0`. Backends without such a concept ignore
+    /// it.
+    fn create_memory(
+        &self,
+        properties: &Self::MemoryTypeProperties,
+        memory_type_index: usize,
+        size: u64,
+        dedicated_resource: Option<Self::DedicatedResource>,
+        priority: Option<f32>,
+    ) -> Result<Self::Memory>;
+
+    /// Destroys a memory object that was created through [`MemoryBackend::create_memory()`].
+    fn destroy_memory(&self, memory: Self::Memory);
+}
+
+#[derive(Debug)]
+pub(crate) struct MemoryBlock<B: MemoryBackend> {
+    pub(crate) memory: B::Memory,
+    pub(crate) size: u64,
+    pub(crate) sub_allocator: Box<dyn SubAllocator>,
+    /// Only drawn by the Vulkan visualizer, other backends never create dedicated memory.
+    #[cfg(feature = "visualizer")]
+    #[cfg_attr(not(feature = "vulkan"), allow(dead_code))]
+    pub(crate) dedicated_allocation: bool,
+    /// Set for the memory block backing a linear pool, which is only allocated from through the
+    /// pool and lives until the pool is destroyed.
+    pub(crate) linear_pool: bool,
+    /// Index in [`MemoryType::pools`] of the custom pool owning this memory block, which is only
+    /// allocated from through that pool.
+    pub(crate) pool: Option<usize>,
+    /// Frame in which this shared memory block became empty, if it is empty.
+    pub(crate) empty_since_frame: Option<u64>,
+    /// Set for shared memory blocks created through [`MemoryType::reserve()`], which are kept
+    /// until the allocator is dropped.
+    pub(crate) reserved: bool,
+    /// Set for shared memory blocks that only hold allocations for [`MemoryLocation::Transient`],
+    /// so that short-lived attachments don't fragment the memory blocks of other allocations.
+    pub(crate) transient: bool,
+}
+
+/// A user-created pool of memory blocks within a memory type, that are kept apart from the shared
+/// memory blocks.
+#[derive(Debug)]
+pub(crate) struct CustomPool {
+    pub(crate) block_size: u64,
+    pub(crate) min_block_count: usize,
+    /// [`None`] if the pool may grow without limit.
+    pub(crate) max_block_count: Option<usize>,
+    pub(crate) sub_allocator: SubAllocatorKind,
+    pub(crate) allocation_strategy: AllocationStrategy,
+    /// Priority of the memory blocks of the pool, see [`MemoryBackend::create_memory()`].
+    pub(crate) priority: Option<f32>,
+    /// Number of memory blocks currently owned by the pool.
+    pub(crate) block_count: usize,
+}
+
+impl CustomPool {
+    pub(crate) fn new(
+        block_size: u64,
+        min_block_count: usize,
+        max_block_count: Option<usize>,
+        sub_allocator: SubAllocatorKind,
+        allocation_strategy: AllocationStrategy,
+        priority: Option<f32>,
+    ) -> Result<Self> {
+        if block_size == 0 || !is_valid_priority(priority) {
+            return Err(AllocationError::InvalidAllocationCreateDesc);
+        }
+        if max_block_count.is_some_and(|max| max == 0 || max < min_block_count) {
+            return Err(AllocationError::InvalidAllocationCreateDesc);
+        }
+
+        Ok(Self {
+            block_size,
+            min_block_count,
+            max_block_count,
+            sub_allocator,
+            allocation_strategy,
+            priority,
+            block_count: 0,
+        })
+    }
+}
+
+/// Returns [`true`] if `priority` is either unset or between `0.0` and `1.0`.
+pub(crate) fn is_valid_priority(priority: Option<f32>) -> bool {
+    priority.map_or(true, |priority| (0.0..=1.0).contains(&priority))
+}
+
+/// Property flags to pick a memory type by, as bitmasks of backend specific flags.
+#[cfg(any(feature = "vulkan", feature = "mock"))]
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct MemoryPropertyPreferences {
+    /// Flags that the memory type must have.
+    pub(crate) required: u32,
+    /// Flags that the memory type should have.
+    pub(crate) preferred: u32,
+    /// Flags that the memory type should not have.
+    pub(crate) not_preferred: u32,
+}
+
+#[cfg(any(feature = "vulkan", feature = "mock"))]
+impl MemoryPropertyPreferences {
+    /// Scores a memory type with the property flags `flags` by the number of preferred flags it
+    /// is missing plus the number of flags it has that are not preferred, or returns [`None`] if
+    /// it is missing a required flag.
+    fn cost(&self, flags: u32) -> Option<u32> {
+        if flags & self.required != self.required {
+            return None;
+        }
+
+        let missing_preferred = self.preferred & !flags;
+        let not_preferred = self.not_preferred & flags;
+        Some(missing_preferred.count_ones() + not_preferred.count_ones())
+    }
+
+    /// Scores every memory type in `memory_type_bits`, given the property flags of all memory
+    /// types in order, and returns the index of the one with the lowest cost. Of several equally
+    /// good memory types, the one with the lowest index is picked.
+    pub(crate) fn find_memory_type_index(
+        &self,
+        memory_type_flags: impl IntoIterator<Item = u32>,
+        memory_type_bits: u32,
+    ) -> Option<usize> {
+        memory_type_flags
+            .into_iter()
+            .enumerate()
+            .filter(|&(memory_type_index, _)| (1 << memory_type_index) & memory_type_bits != 0)
+            .filter_map(|(memory_type_index, flags)| Some((self.cost(flags)?, memory_type_index)))
+            // Takes the first memory type of those with the lowest cost.
+            .min_by_key(|&(cost, _)| cost)
+            .map(|(_, memory_type_index)| memory_type_index)
+    }
+}
+
+/// Describes a memory heap of the backend to a [`GenericAllocator`].
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct HeapProperties {
+    pub(crate) size: u64,
+    pub(crate) size_limit: Option<u64>,
+}
+
+/// Tracks the total size of the memory blocks created in a memory heap, against an optional limit
+/// that is enforced on top of whatever the backend allows.
+#[derive(Debug)]
+pub(crate) struct MemoryHeap {
+    /// [`u64::MAX`] for heaps of which the backend doesn't know the size.
+    pub(crate) size: u64,
+    pub(crate) size_limit: Option<u64>,
+    /// Memory types of a `SyncGenericAllocator` that share a heap create memory blocks
+    /// concurrently.
+    usage: AtomicU64,
+}
+
+impl MemoryHeap {
+    /// Accounts for a new memory block of `size` bytes, failing with
+    /// [`AllocationError::OutOfBudget`] if that would take the heap past its size limit.
+    fn reserve(&self, size: u64) -> Result<()> {
+        let Some(size_limit) = self.size_limit else {
+            self.usage.fetch_add(size, Ordering::Relaxed);
+            return Ok(());
+        };
+
+        let mut usage = self.usage.load(Ordering::Relaxed);
+        loop {
+            if size > size_limit.saturating_sub(usage) {
+                return Err(AllocationError::OutOfBudget);
+            }
+            match self.usage.compare_exchange_weak(
+                usage,
+                usage + size,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Ok(()),
+                Err(current) => usage = current,
+            }
+        }
+    }
+
+    fn release(&self, size: u64) {
+        self.usage.fetch_sub(size, Ordering::Relaxed);
+    }
+
+    /// The size of the heap that memory block sizes are based on, which is its size limit if
+    /// that is smaller.
+    fn effective_size(&self) -> u64 {
+        self.size_limit
+            .map_or(self.size, |limit| limit.min(self.size))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct MemoryType<B: MemoryBackend> {
+    pub(crate) memory_blocks: Vec<Option<MemoryBlock<B>>>,
+    pub(crate) properties: B::MemoryTypeProperties,
+    pub(crate) memory_type_index: usize,
+    /// The memory heap this memory type takes its memory blocks from, shared with the other
+    /// memory types in that heap.
+    pub(crate) heap: Arc<MemoryHeap>,
+    pub(crate) active_general_blocks: usize,
+    pub(crate) sub_allocator: SubAllocatorKind,
+    pub(crate) allocation_strategy: AllocationStrategy,
+    pub(crate) empty_block_retention: EmptyBlockRetention,
+    /// The frame last passed to [`MemoryType::set_current_frame()`].
+    pub(crate) current_frame: u64,
+    pub(crate) pools: Vec<Option<CustomPool>>,
+}
+
+/// Describes an allocation to make in a [`GenericAllocator`].
+pub(crate) struct AllocationRequest<'a> {
+    pub(crate) name: &'a str,
+    pub(crate) size: u64,
+    pub(crate) alignment: u64,
+    pub(crate) allocation_type: AllocationType,
+    pub(crate) granularity: u64,
+    /// Overrides the allocation strategy of the memory type.
+    pub(crate) strategy: Option<AllocationStrategy>,
+    /// Location the allocation is made for, which selects the size of new shared memory blocks.
+    pub(crate) location: MemoryLocation,
+    /// Priority of the memory block, if one is created for this allocation alone.
+    pub(crate) priority: Option<f32>,
+    #[cfg(feature = "std")]
+    pub(crate) backtrace: Arc<Backtrace>,
+}
+
+impl AllocationRequest<'_> {
+    /// The context to pass to [`SubAllocator::allocate()`] for this request.
+    #[cfg_attr(not(feature = "std"), allow(clippy::unused_self))]
+    pub(crate) fn context(&self) -> AllocationContext {
+        AllocationContext {
+            #[cfg(feature = "std")]
+            backtrace: self.backtrace.clone(),
+        }
+    }
+}
+
+/// Where an allocation made by a [`GenericAllocator`] ended up.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SubAllocation {
+    pub(crate) chunk_id: NonZeroU64,
+    pub(crate) offset: u64,
+    pub(crate) size: u64,
+    pub(crate) memory_type_index: usize,
+    pub(crate) memory_block_index: usize,
+}
+
+impl<B: MemoryBackend> MemoryType<B> {
+    pub(crate) fn memory_block_mut(
+        &mut self,
+        memory_block_index: usize,
+    ) -> Result<&mut MemoryBlock<B>> {
+        self.memory_blocks[memory_block_index]
+            .as_mut()
+            .ok_or_else(|| AllocationError::Internal("Memory block must be Some.".into()))
+    }
+
+    /// Puts `memory_block` in the first free slot, returning its index.
+    fn insert_block(&mut self, memory_block: MemoryBlock<B>) -> usize {
+        match self.memory_blocks.iter().position(Option::is_none) {
+            Some(i) => {
+                self.memory_blocks[i] = Some(memory_block);
+                i
+            }
+            None => {
+                self.memory_blocks.push(Some(memory_block));
+                self.memory_blocks.len() - 1
+            }
+        }
+    }
+
+    fn create_block(
+        &self,
+        backend: &B,
+        size: u64,
+        dedicated: bool,
+        dedicated_resource: Option<B::DedicatedResource>,
+        priority: Option<f32>,
+        sub_allocator: SubAllocatorKind,
+    ) -> Result<MemoryBlock<B>> {
+        // Memory dedicated to a resource must be exactly as large as the resource needs.
+        let size = if dedicated_resource.is_some() {
+            size
+        } else {
+            align_up(size, B::atom_size(&self.properties))
+        };
+        self.heap.reserve(size)?;
+        let memory = match backend.create_memory(
+            &self.properties,
+            self.memory_type_index,
+            size,
+            dedicated_resource,
+            priority,
+        ) {
+            Ok(memory) => memory,
+            Err(err) => {
+                self.heap.release(size);
+                return Err(err);
+            }
+        };
+
+        let sub_allocator: Box<dyn SubAllocator> = if dedicated {
+            Box::new(DedicatedBlockAllocator::new(size))
+        } else {
+            sub_allocator.create(size)
+        };
+
+        Ok(MemoryBlock {
+            memory,
+            size,
+            sub_allocator,
+            #[cfg(feature = "visualizer")]
+            dedicated_allocation: dedicated_resource.is_some(),
+            linear_pool: false,
+            pool: None,
+            empty_since_frame: None,
+            reserved: false,
+            transient: false,
+        })
+    }
+
+    /// Destroys the memory object of a memory block that was taken out of this memory type.
+    pub(crate) fn destroy_block(&self, backend: &B, block: MemoryBlock<B>) {
+        self.heap.release(block.size);
+        backend.destroy_memory(block.memory);
+    }
+
+    fn allocate_from_block(
+        &mut self,
+        memory_block_index: usize,
+        request: &AllocationRequest<'_>,
+    ) -> Result<SubAllocation> {
+        let mem_block = self.memory_blocks[memory_block_index]
+            .as_mut()
+            .ok_or_else(|| AllocationError::Internal("Memory block must be Some.".into()))?;
+
+        let strategy = request.strategy.unwrap_or_else(|| {
+            match mem_block.pool.and_then(|pool| self.pools[pool].as_ref()) {
+                Some(pool) => pool.allocation_strategy,
+                None => self.allocation_strategy,
+            }
+        });
+
+        // Memory blocks dedicated to a resource are not rounded up to the atom size, and are only
+        // ever as large as their allocation.
+        let atom_size = B::atom_size(&self.properties);
+        let (offset, chunk_id) = mem_block.sub_allocator.allocate(
+            align_up(request.size, atom_size).min(mem_block.size),
+            request.alignment.max(atom_size),
+            request.allocation_type,
+            request.granularity,
+            strategy,
+            request.name,
+            request.context(),
+        )?;
+        mem_block.empty_since_frame = None;
+
+        Ok(SubAllocation {
+            chunk_id,
+            offset,
+            size: request.size,
+            memory_type_index: self.memory_type_index,
+            memory_block_index,
+        })
+    }
+
+    pub(crate) fn allocate(
+        &mut self,
+        backend: &B,
+        request: &AllocationRequest<'_>,
+        dedicated_resource: Option<B::DedicatedResource>,
+        allocation_sizes: &AllocationSizes,
+    ) -> Result<SubAllocation> {
+        let is_host = B::is_host_visible(&self.properties);
+        let memblock_size = allocation_sizes.get_memblock_size(
+            self.memory_type_index,
+            request.location,
+            is_host,
+            self.heap.effective_size(),
+            self.active_general_blocks,
+        );
+
+        let requires_personal_block = request.size > memblock_size;
+
+        // Create a dedicated block for large memory allocations or allocations that require dedicated memory allocations.
+        if dedicated_resource.is_some() || requires_personal_block {
+            let mem_block = self.create_block(
+                backend,
+                request.size,
+                true,
+                dedicated_resource,
+                request.priority,
+                self.sub_allocator,
+            )?;
+            let block_index = self.insert_block(mem_block);
+
+            return self.allocate_from_block(block_index, request);
+        }
+
+        let transient = request.location == MemoryLocation::Transient;
+        let mut empty_block_index = None;
+        for mem_block_i in (0..self.memory_blocks.len()).rev() {
+            let Some(mem_block) = &self.memory_blocks[mem_block_i] else {
+                empty_block_index.get_or_insert(mem_block_i);
+                continue;
+            };
+            if mem_block.linear_pool || mem_block.pool.is_some() || mem_block.transient != transient
+            {
+                continue;
+            }
+
+            match self.allocate_from_block(mem_block_i, request) {
+                Ok(allocation) => return Ok(allocation),
+                Err(AllocationError::OutOfMemory) => {} // Block is full, continue search.
+                Err(err) => return Err(err),            // Unhandled error, return.
+            }
+        }
+
+        let mut new_memory_block = self.create_block(
+            backend,
+            memblock_size,
+            false,
+            None,
+            None,
+            self.sub_allocator,
+        )?;
+        new_memory_block.transient = transient;
+
+        let new_block_index = if let Some(block_index) = empty_block_index {
+            self.memory_blocks[block_index] = Some(new_memory_block);
+            block_index
+        } else {
+            self.memory_blocks.push(Some(new_memory_block));
+            self.memory_blocks.len() - 1
+        };
+
+        self.active_general_blocks += 1;
+
+        match self.allocate_from_block(new_block_index, request) {
+            Err(AllocationError::OutOfMemory) => Err(AllocationError::Internal(
+                "Allocation that must succeed failed. This is a bug in the allocator.".into(),
+            )),
+            a => a,
+        }
+    }
+
+    /// Creates a memory block for the custom pool at `pool_index`, returning its index.
+    fn create_pool_block(&mut self, backend: &B, pool_index: usize) -> Result<usize> {
+        let pool = self.pools[pool_index]
+            .as_ref()
+            .ok_or_else(|| AllocationError::Internal("Pool must be Some.".into()))?;
+        let mut mem_block = self.create_block(
+            backend,
+            pool.block_size,
+            false,
+            None,
+            pool.priority,
+            pool.sub_allocator,
+        )?;
+        mem_block.pool = Some(pool_index);
+
+        let block_index = self.insert_block(mem_block);
+        if let Some(pool) = &mut self.pools[pool_index] {
+            pool.block_count += 1;
+        }
+
+        Ok(block_index)
+    }
+
+    /// Allocates from the memory blocks of the custom pool at `pool_index`, creating a new memory
+    /// block as long as the pool has not reached its maximum number of memory blocks.
+    pub(crate) fn allocate_from_pool(
+        &mut self,
+        backend: &B,
+        pool_index: usize,
+        request: &AllocationRequest<'_>,
+    ) -> Result<SubAllocation> {
+        let pool = self.pools[pool_index]
+            .as_ref()
+            .ok_or_else(|| AllocationError::Internal("Pool must be Some.".into()))?;
+        // Custom pools never create memory blocks of another size.
+        if request.size > pool.block_size {
+            return Err(AllocationError::OutOfMemory);
+        }
+        let can_grow = pool
+            .max_block_count
+            .map_or(true, |max| pool.block_count < max);
+
+        for mem_block_i in (0..self.memory_blocks.len()).rev() {
+            let Some(mem_block) = &self.memory_blocks[mem_block_i] else {
+                continue;
+            };
+            if mem_block.pool != Some(pool_index) {
+                continue;
+            }
+
+            match self.allocate_from_block(mem_block_i, request) {
+                Ok(allocation) => return Ok(allocation),
+                Err(AllocationError::OutOfMemory) => {} // Block is full, continue search.
+                Err(err) => return Err(err),            // Unhandled error, return.
+            }
+        }
+
+        if !can_grow {
+            return Err(AllocationError::OutOfMemory);
+        }
+
+        let new_block_index = self.create_pool_block(backend, pool_index)?;
+
+        match self.allocate_from_block(new_block_index, request) {
+            Err(AllocationError::OutOfMemory) => Err(AllocationError::Internal(
+                "Allocation that must succeed failed. This is a bug in the allocator.".into(),
+            )),
+            a => a,
+        }
+    }
+
+    /// Destroys all memory blocks of the custom pool at `pool_index` and removes the pool. Fails
+    /// without destroying anything if any allocation is still live in the pool.
+    fn destroy_pool(&mut self, backend: &B, pool_index: usize) -> Result<()> {
+        if self
+            .memory_blocks
+            .iter()
+            .flatten()
+            .any(|block| block.pool == Some(pool_index) && !block.sub_allocator.is_empty())
+        {
+            return Err(AllocationError::Internal(
+                "Attempting to destroy pool that still has live allocations.".into(),
+            ));
+        }
+
+        self.pools[pool_index]
+            .take()
+            .ok_or_else(|| AllocationError::Internal("Pool must be Some.".into()))?;
+
+        for mem_block_i in 0..self.memory_blocks.len() {
+            if self.memory_blocks[mem_block_i]
+                .as_ref()
+                .is_some_and(|block| block.pool == Some(pool_index))
+            {
+                if let Some(block) = self.memory_blocks[mem_block_i].take() {
+                    self.destroy_block(backend, block);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn free(
+        &mut self,
+        backend: &B,
+        memory_block_index: usize,
+        chunk_id: Option<NonZeroU64>,
+    ) -> Result<()> {
+        let mem_block = self.memory_blocks[memory_block_index]
+            .as_mut()
+            .ok_or_else(|| AllocationError::Internal("Memory block must be Some.".into()))?;
+
+        mem_block.sub_allocator.free(chunk_id)?;
+
+        // Linear pool blocks are only destroyed together with their pool.
+        if mem_block.linear_pool {
+            return Ok(());
+        }
+
+        // Custom pools keep at least their minimum number of memory blocks around.
+        if let Some(pool_index) = mem_block.pool {
+            let pool = self.pools[pool_index]
+                .as_mut()
+                .ok_or_else(|| AllocationError::Internal("Pool must be Some.".into()))?;
+            if mem_block.sub_allocator.is_empty() && pool.block_count > pool.min_block_count {
+                let block = self.memory_blocks[memory_block_index]
+                    .take()
+                    .ok_or_else(|| {
+                        AllocationError::Internal("Memory block must be Some.".into())
+                    })?;
+                pool.block_count -= 1;
+                self.destroy_block(backend, block);
+            }
+            return Ok(());
+        }
+
+        if !mem_block.sub_allocator.is_empty() {
+            return Ok(());
+        }
+
+        // Dedicated/personal blocks are destroyed as soon as they are empty. Empty shared blocks
+        // are kept around according to the retention policy, and the last one is always kept
+        // (ensuring there's always at least one block/allocator readily available).
+        if mem_block.sub_allocator.supports_general_allocations() {
+            mem_block.empty_since_frame = Some(self.current_frame);
+            let release = !mem_block.reserved
+                && self.active_general_blocks > 1
+                && match self.empty_block_retention {
+                    EmptyBlockRetention::Blocks(count) => self.empty_shared_block_count() > count,
+                    EmptyBlockRetention::Frames(_) => false,
+                };
+            if !release {
+                return Ok(());
+            }
+        }
+
+        let block = self.memory_blocks[memory_block_index]
+            .take()
+            .ok_or_else(|| AllocationError::Internal("Memory block must be Some.".into()))?;
+
+        if block.sub_allocator.supports_general_allocations() {
+            self.active_general_blocks -= 1;
+        }
+
+        self.destroy_block(backend, block);
+
+        Ok(())
+    }
+
+    /// Returns [`true`] for shared memory blocks without any allocation, that are not reserved.
+    fn is_empty_shared_block(block: &MemoryBlock<B>) -> bool {
+        block.sub_allocator.supports_general_allocations()
+            && !block.linear_pool
+            && block.pool.is_none()
+            && !block.reserved
+            && block.sub_allocator.is_empty()
+    }
+
+    fn empty_shared_block_count(&self) -> usize {
+        self.memory_blocks
+            .iter()
+            .flatten()
+            .filter(|block| Self::is_empty_shared_block(block))
+            .count()
+    }
+
+    /// Destroys the empty shared memory blocks that `release` returns [`true`] for, except for
+    /// the last shared memory block if `keep_last` is set.
+    fn release_empty_blocks(
+        &mut self,
+        backend: &B,
+        keep_last: bool,
+        release: impl Fn(&MemoryBlock<B>) -> bool,
+    ) {
+        for mem_block_i in 0..self.memory_blocks.len() {
+            if keep_last && self.active_general_blocks <= 1 {
+                break;
+            }
+            let Some(mem_block) = &self.memory_blocks[mem_block_i] else {
+                continue;
+            };
+            if !Self::is_empty_shared_block(mem_block) || !release(mem_block) {
+                continue;
+            }
+
+            if let Some(block) = self.memory_blocks[mem_block_i].take() {
+                self.active_general_blocks -= 1;
+                self.destroy_block(backend, block);
+            }
+        }
+    }
+
+    /// Creates reserved shared memory blocks until they add up to at least `size` bytes. Memory
+    /// blocks created by this call are destroyed again if any of them can't be created.
+    pub(crate) fn reserve(
+        &mut self,
+        backend: &B,
+        location: MemoryLocation,
+        size: u64,
+        allocation_sizes: &AllocationSizes,
+    ) -> Result<()> {
+        let is_host = B::is_host_visible(&self.properties);
+        let transient = location == MemoryLocation::Transient;
+        let mut reserved_size = self
+            .memory_blocks
+            .iter()
+            .flatten()
+            .filter(|block| block.reserved && block.transient == transient)
+            .map(|block| block.size)
+            .sum::<u64>();
+        let mut new_block_indices = Vec::new();
+
+        while reserved_size < size {
+            let memblock_size = allocation_sizes.get_memblock_size(
+                self.memory_type_index,
+                location,
+                is_host,
+                self.heap.effective_size(),
+                self.active_general_blocks,
+            );
+            match self.create_block(
+                backend,
+                memblock_size,
+                false,
+                None,
+                None,
+                self.sub_allocator,
+            ) {
+                Ok(mut mem_block) => {
+                    mem_block.reserved = true;
+                    mem_block.transient = transient;
+                    // The memory block may be larger than requested, see `create_block()`.
+                    reserved_size += mem_block.size;
+                    new_block_indices.push(self.insert_block(mem_block));
+                    self.active_general_blocks += 1;
+                }
+                Err(err) => {
+                    for block_index in new_block_indices {
+                        if let Some(block) = self.memory_blocks[block_index].take() {
+                            self.active_general_blocks -= 1;
+                            self.destroy_block(backend, block);
+                        }
+                    }
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Destroys all empty shared memory blocks that are not reserved, including the last one.
+    pub(crate) fn trim(&mut self, backend: &B) {
+        self.release_empty_blocks(backend, false, |_| true);
+    }
+
+    /// Advances the frame counter of [`EmptyBlockRetention::Frames`], destroying the empty shared
+    /// memory blocks that have been kept around for long enough.
+    pub(crate) fn set_current_frame(&mut self, backend: &B, frame: u64) {
+        self.current_frame = frame;
+        if let EmptyBlockRetention::Frames(frames) = self.empty_block_retention {
+            self.release_empty_blocks(backend, true, |block| {
+                block
+                    .empty_since_frame
+                    .is_some_and(|since| frame.saturating_sub(since) >= frames)
+            });
+        }
+    }
+
+    pub(crate) fn report_memory_leaks(&self, log_level: Level) {
+        for (block_i, mem_block) in self.memory_blocks.iter().enumerate() {
+            if let Some(mem_block) = mem_block {
+                mem_block.sub_allocator.report_memory_leaks(
+                    log_level,
+                    self.memory_type_index,
+                    block_i,
+                );
+            }
+        }
+    }
+
+    pub(crate) fn capacity(&self) -> u64 {
+        self.memory_blocks
+            .iter()
+            .flatten()
+            .map(|block| block.size)
+            .sum()
+    }
+}
+
+/// Generates a report covering the memory blocks of all `memory_types`, which may be borrowed
+/// directly or through a lock.
+pub(crate) fn generate_report<B: MemoryBackend, M: Deref<Target = MemoryType<B>>>(
+    memory_types: impl IntoIterator<Item = M>,
+) -> AllocatorReport {
+    let mut allocations = vec![];
+    let mut blocks = vec![];
+    let mut total_capacity_bytes = 0;
+
+    for memory_type in memory_types {
+        for block in memory_type.memory_blocks.iter().flatten() {
+            total_capacity_bytes += block.size;
+            let first_allocation = allocations.len();
+            allocations.extend(block.sub_allocator.report_allocations());
+            blocks.push(MemoryBlockReport {
+                size: block.size,
+                allocations: first_allocation..allocations.len(),
+            });
+        }
+    }
+
+    let total_allocated_bytes = allocations.iter().map(|report| report.size).sum();
+
+    AllocatorReport {
+        allocations,
+        blocks,
+        total_allocated_bytes,
+        total_capacity_bytes,
+    }
+}
+
+/// The backend-agnostic core of an allocator, owning the [`MemoryType`]s and their
+/// [`MemoryBlock`]s.
+///
+/// Picking a memory type for an allocation is left to the backends, as that depends heavily on the
+/// graphics API.
+pub(crate) struct GenericAllocator<B: MemoryBackend> {
+    pub(crate) backend: B,
+    pub(crate) memory_types: Vec<MemoryType<B>>,
+    /// Only read back by the Vulkan backend, to cap the heap budgets it reports.
+    #[cfg_attr(not(feature = "vulkan"), allow(dead_code))]
+    pub(crate) memory_heaps: Vec<Arc<MemoryHeap>>,
+    pub(crate) debug_settings: AllocatorDebugSettings,
+    pub(crate) allocation_sizes: AllocationSizes,
+}
+
+impl<B: MemoryBackend> GenericAllocator<B> {
+    /// Creates an allocator for the given memory types and memory heaps. Heaps that memory types
+    /// refer to past the end of `memory_heaps` are of unknown size, and are not limited.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        backend: B,
+        memory_types: impl IntoIterator<Item = B::MemoryTypeProperties>,
+        memory_heaps: &[HeapProperties],
+        debug_settings: AllocatorDebugSettings,
+        allocation_sizes: &AllocationSizes,
+        sub_allocator: SubAllocatorKind,
+        allocation_strategy: AllocationStrategy,
+        empty_block_retention: EmptyBlockRetention,
+    ) -> Self {
+        let memory_types = memory_types.into_iter().collect::<Vec<_>>();
+        let memory_heap_count = memory_types
+            .iter()
+            .map(|properties| B::heap_index(properties) + 1)
+            .max()
+            .unwrap_or(0)
+            .max(memory_heaps.len());
+        let memory_heaps = (0..memory_heap_count)
+            .map(|heap_index| {
+                let properties = memory_heaps.get(heap_index).copied();
+                Arc::new(MemoryHeap {
+                    size: properties.map_or(u64::MAX, |heap| heap.size),
+                    size_limit: properties.and_then(|heap| heap.size_limit),
+                    usage: AtomicU64::new(0),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let memory_types = memory_types
+            .into_iter()
+            .enumerate()
+            .map(|(memory_type_index, properties)| MemoryType {
+                memory_blocks: Vec::default(),
+                heap: memory_heaps[B::heap_index(&properties)].clone(),
+                properties,
+                memory_type_index,
+                active_general_blocks: 0,
+                sub_allocator,
+                allocation_strategy,
+                empty_block_retention,
+                current_frame: 0,
+                pools: Vec::new(),
+            })
+            .collect();
+
+        Self {
+            backend,
+            memory_types,
+            memory_heaps,
+            debug_settings,
+            allocation_sizes: *allocation_sizes,
+        }
+    }
+
+    /// Captures the backtrace to store with a new allocation, if enabled in the debug settings.
+    #[cfg(feature = "std")]
+    pub(crate) fn capture_backtrace(&self) -> Arc<Backtrace> {
+        self.debug_settings.capture_backtrace()
+    }
+
+    pub(crate) fn log_allocation(&self, name: &str, size: u64, alignment: u64) {
+        self.debug_settings.log_allocation(name, size, alignment);
+    }
+
+    pub(crate) fn memory_block(&self, allocation: &SubAllocation) -> Result<&MemoryBlock<B>> {
+        self.memory_types[allocation.memory_type_index].memory_blocks[allocation.memory_block_index]
+            .as_ref()
+            .ok_or_else(|| AllocationError::Internal("Memory block must be Some.".into()))
+    }
+
+    fn memory_block_mut(
+        &mut self,
+        memory_type_index: usize,
+        memory_block_index: usize,
+    ) -> Result<&mut MemoryBlock<B>> {
+        self.memory_types[memory_type_index].memory_block_mut(memory_block_index)
+    }
+
+    /// Allocates from the shared memory blocks of a memory type, creating a new memory block when
+    /// none of them has enough room.
+    ///
+    /// A memory block of its own is created for allocations that don't fit in a regular memory
+    /// block, or that are dedicated to `dedicated_resource`.
+    pub(crate) fn allocate(
+        &mut self,
+        memory_type_index: usize,
+        request: &AllocationRequest<'_>,
+        dedicated_resource: Option<B::DedicatedResource>,
+    ) -> Result<SubAllocation> {
+        self.memory_types[memory_type_index].allocate(
+            &self.backend,
+            request,
+            dedicated_resource,
+            &self.allocation_sizes,
+        )
+    }
+
+    pub(crate) fn free(
+        &mut self,
+        memory_type_index: usize,
+        memory_block_index: usize,
+        chunk_id: Option<NonZeroU64>,
+    ) -> Result<()> {
+        self.memory_types[memory_type_index].free(&self.backend, memory_block_index, chunk_id)
+    }
+
+    pub(crate) fn rename_allocation(
+        &mut self,
+        memory_type_index: usize,
+        memory_block_index: usize,
+        chunk_id: Option<NonZeroU64>,
+        name: &str,
+    ) -> Result<()> {
+        self.memory_block_mut(memory_type_index, memory_block_index)?
+            .sub_allocator
+            .rename_allocation(chunk_id, name)
+    }
+
+    /// Creates the memory block of a linear pool in a memory type, returning its index.
+    pub(crate) fn create_linear_pool(
+        &mut self,
+        memory_type_index: usize,
+        size: u64,
+    ) -> Result<usize> {
+        let memory_type = &mut self.memory_types[memory_type_index];
+        let mut mem_block = memory_type.create_block(
+            &self.backend,
+            size,
+            false,
+            None,
+            None,
+            SubAllocatorKind::Linear,
+        )?;
+        mem_block.linear_pool = true;
+
+        Ok(memory_type.insert_block(mem_block))
+    }
+
+    pub(crate) fn destroy_linear_pool(
+        &mut self,
+        memory_type_index: usize,
+        memory_block_index: usize,
+    ) -> Result<()> {
+        let memory_type = &mut self.memory_types[memory_type_index];
+        let block = memory_type.memory_blocks[memory_block_index]
+            .take()
+            .ok_or_else(|| AllocationError::Internal("Memory block must be Some.".into()))?;
+
+        memory_type.destroy_block(&self.backend, block);
+
+        Ok(())
+    }
+
+    pub(crate) fn allocate_from_linear_pool(
+        &mut self,
+        memory_type_index: usize,
+        memory_block_index: usize,
+        request: &AllocationRequest<'_>,
+    ) -> Result<SubAllocation> {
+        self.memory_types[memory_type_index].allocate_from_block(memory_block_index, request)
+    }
+
+    pub(crate) fn begin_linear_pool_frame(
+        &mut self,
+        memory_type_index: usize,
+        memory_block_index: usize,
+        frame: u64,
+    ) -> Result<()> {
+        self.memory_block_mut(memory_type_index, memory_block_index)?
+            .sub_allocator
+            .begin_frame(frame);
+
+        Ok(())
+    }
+
+    pub(crate) fn free_linear_pool_frames_before(
+        &mut self,
+        memory_type_index: usize,
+        memory_block_index: usize,
+        frame: u64,
+    ) -> Result<()> {
+        self.memory_block_mut(memory_type_index, memory_block_index)?
+            .sub_allocator
+            .free_frames_before(frame)
+    }
+
+    /// Creates a custom pool in the given memory type along with its minimum number of memory
+    /// blocks, returning the index of the pool.
+    pub(crate) fn create_pool(
+        &mut self,
+        memory_type_index: usize,
+        pool: CustomPool,
+    ) -> Result<usize> {
+        let memory_type = &mut self.memory_types[memory_type_index];
+        let min_block_count = pool.min_block_count;
+
+        let pool_index = match memory_type.pools.iter().position(Option::is_none) {
+            Some(i) => {
+                memory_type.pools[i] = Some(pool);
+                i
+            }
+            None => {
+                memory_type.pools.push(Some(pool));
+                memory_type.pools.len() - 1
+            }
+        };
+
+        for _ in 0..min_block_count {
+            if let Err(err) = memory_type.create_pool_block(&self.backend, pool_index) {
+                memory_type.destroy_pool(&self.backend, pool_index)?;
+                return Err(err);
+            }
+        }
+
+        Ok(pool_index)
+    }
+
+    pub(crate) fn destroy_pool(
+        &mut self,
+        memory_type_index: usize,
+        pool_index: usize,
+    ) -> Result<()> {
+        self.memory_types[memory_type_index].destroy_pool(&self.backend, pool_index)
+    }
+
+    pub(crate) fn allocate_from_pool(
+        &mut self,
+        memory_type_index: usize,
+        pool_index: usize,
+        request: &AllocationRequest<'_>,
+    ) -> Result<SubAllocation> {
+        self.memory_types[memory_type_index].allocate_from_pool(&self.backend, pool_index, request)
+    }
+
+    pub(crate) fn report_memory_leaks(&self, log_level: Level) {
+        for memory_type in &self.memory_types {
+            memory_type.report_memory_leaks(log_level);
+        }
+    }
+
+    pub(crate) fn generate_report(&self) -> AllocatorReport {
+        generate_report(&self.memory_types)
+    }
+
+    pub(crate) fn capacity(&self) -> u64 {
+        self.memory_types.iter().map(MemoryType::capacity).sum()
+    }
+
+    pub(crate) fn reserve(
+        &mut self,
+        memory_type_index: usize,
+        location: MemoryLocation,
+        size: u64,
+    ) -> Result<()> {
+        self.memory_types[memory_type_index].reserve(
+            &self.backend,
+            location,
+            size,
+            &self.allocation_sizes,
+        )
+    }
+
+    pub(crate) fn trim(&mut self) {
+        for memory_type in &mut self.memory_types {
+            memory_type.trim(&self.backend);
+        }
+    }
+
+    pub(crate) fn set_current_frame(&mut self, frame: u64) {
+        for memory_type in &mut self.memory_types {
+            memory_type.set_current_frame(&self.backend, frame);
+        }
+    }
+}
+
+/// The operations the backends build `allocate()`, `free()` and `rename_allocation()` on, so
+/// that those work the same for an allocator that is borrowed mutably and for one that locks its
+/// memory types.
+pub(crate) trait AllocatorCore<B: MemoryBackend> {
+    fn debug_settings(&self) -> AllocatorDebugSettings;
+
+    fn memory_type_count(&self) -> usize;
+
+    fn memory_type_properties(&self, memory_type_index: usize) -> &B::MemoryTypeProperties;
+
+    fn allocate(
+        &mut self,
+        memory_type_index: usize,
+        request: &AllocationRequest<'_>,
+        dedicated_resource: Option<B::DedicatedResource>,
+    ) -> Result<SubAllocation>;
+
+    fn allocate_from_pool(
+        &mut self,
+        memory_type_index: usize,
+        pool_index: usize,
+        request: &AllocationRequest<'_>,
+    ) -> Result<SubAllocation>;
+
+    fn free(
+        &mut self,
+        memory_type_index: usize,
+        memory_block_index: usize,
+        chunk_id: Option<NonZeroU64>,
+    ) -> Result<()>;
+
+    fn rename_allocation(
+        &mut self,
+        memory_type_index: usize,
+        memory_block_index: usize,
+        chunk_id: Option<NonZeroU64>,
+        name: &str,
+    ) -> Result<()>;
+
+    /// Calls `f` with the memory block `allocation` was made in.
+    fn with_memory_block<R>(
+        &self,
+        allocation: &SubAllocation,
+        f: impl FnOnce(&MemoryBlock<B>) -> R,
+    ) -> Result<R>;
+}
+
+impl<B: MemoryBackend> AllocatorCore<B> for GenericAllocator<B> {
+    fn debug_settings(&self) -> AllocatorDebugSettings {
+        self.debug_settings
+    }
+
+    fn memory_type_count(&self) -> usize {
+        self.memory_types.len()
+    }
+
+    fn memory_type_properties(&self, memory_type_index: usize) -> &B::MemoryTypeProperties {
+        &self.memory_types[memory_type_index].properties
+    }
+
+    fn allocate(
+        &mut self,
+        memory_type_index: usize,
+        request: &AllocationRequest<'_>,
+        dedicated_resource: Option<B::DedicatedResource>,
+    ) -> Result<SubAllocation> {
+        Self::allocate(self, memory_type_index, request, dedicated_resource)
+    }
+
+    fn allocate_from_pool(
+        &mut self,
+        memory_type_index: usize,
+        pool_index: usize,
+        request: &AllocationRequest<'_>,
+    ) -> Result<SubAllocation> {
+        Self::allocate_from_pool(self, memory_type_index, pool_index, request)
+    }
+
+    fn free(
+        &mut self,
+        memory_type_index: usize,
+        memory_block_index: usize,
+        chunk_id: Option<NonZeroU64>,
+    ) -> Result<()> {
+        Self::free(self, memory_type_index, memory_block_index, chunk_id)
+    }
+
+    fn rename_allocation(
+        &mut self,
+        memory_type_index: usize,
+        memory_block_index: usize,
+        chunk_id: Option<NonZeroU64>,
+        name: &str,
+    ) -> Result<()> {
+        Self::rename_allocation(self, memory_type_index, memory_block_index, chunk_id, name)
+    }
+
+    fn with_memory_block<R>(
+        &self,
+        allocation: &SubAllocation,
+        f: impl FnOnce(&MemoryBlock<B>) -> R,
+    ) -> Result<R> {
+        self.memory_block(allocation).map(f)
+    }
+}
+
+impl<B: MemoryBackend> Drop for GenericAllocator<B> {
+    fn drop(&mut self) {
+        if self.debug_settings.log_leaks_on_shutdown {
+            self.report_memory_leaks(Level::Warn);
+        }
+
+        // Free all remaining memory blocks, before the backend (and the device it holds on to)
+        // is dropped.
+        for mem_type in self.memory_types.iter_mut() {
+            for mem_block_i in 0..mem_type.memory_blocks.len() {
+                if let Some(block) = mem_type.memory_blocks[mem_block_i].take() {
+                    mem_type.destroy_block(&self.backend, block);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use super::*;
+
+    const BLOCK_SIZE: u64 = 1024;
+
+    /// Backend whose memory objects are just their size, which keeps track of the memory that is
+    /// alive and fails to create memory objects past `capacity` bytes.
+    #[derive(Debug)]
+    struct TestBackend {
+        capacity: u64,
+        live_memory: Cell<u64>,
+        live_objects: Cell<usize>,
+    }
+
+    impl MemoryBackend for TestBackend {
+        /// The atom size of the memory type.
+        type MemoryTypeProperties = u64;
+        type Memory = u64;
+        type DedicatedResource = ();
+
+        fn is_host_visible(_properties: &u64) -> bool {
+            false
+        }
+
+        fn heap_index(_properties: &u64) -> usize {
+            0
+        }
+
+        fn atom_size(properties: &u64) -> u64 {
+            *properties
+        }
+
+        fn create_memory(
+            &self,
+            _properties: &u64,
+            _memory_type_index: usize,
+            size: u64,
+            _dedicated_resource: Option<()>,
+            _priority: Option<f32>,
+        ) -> Result<u64> {
+            if size > self.capacity - self.live_memory.get() {
+                return Err(AllocationError::OutOfMemory);
+            }
+            self.live_memory.set(self.live_memory.get() + size);
+            self.live_objects.set(self.live_objects.get() + 1);
+            Ok(size)
+        }
+
+        fn destroy_memory(&self, memory: u64) {
+            self.live_memory.set(self.live_memory.get() - memory);
+            self.live_objects.set(self.live_objects.get() - 1);
+        }
+    }
+
+    fn allocator(capacity: u64, heap_size_limit: Option<u64>) -> GenericAllocator<TestBackend> {
+        allocator_with_atom_size(capacity, heap_size_limit, 1)
+    }
+
+    fn allocator_with_atom_size(
+        capacity: u64,
+        heap_size_limit: Option<u64>,
+        atom_size: u64,
+    ) -> GenericAllocator<TestBackend> {
+        GenericAllocator::new(
+            TestBackend {
+                capacity,
+                live_memory: Cell::new(0),
+                live_objects: Cell::new(0),
+            },
+            [atom_size],
+            &[HeapProperties {
+                size: u64::MAX,
+                size_limit: heap_size_limit,
+            }],
+            AllocatorDebugSettings::default(),
+            &AllocationSizes::new(BLOCK_SIZE, BLOCK_SIZE),
+            SubAllocatorKind::default(),
+            AllocationStrategy::default(),
+            EmptyBlockRetention::default(),
+        )
+    }
+
+    fn request(size: u64) -> AllocationRequest<'static> {
+        AllocationRequest {
+            name: "test",
+            size,
+            alignment: 1,
+            allocation_type: AllocationType::Linear,
+            granularity: 1,
+            strategy: None,
+            location: MemoryLocation::GpuOnly,
+            priority: None,
+            #[cfg(feature = "std")]
+            backtrace: Arc::new(Backtrace::disabled()),
+        }
+    }
+
+    fn free(
+        allocator: &mut GenericAllocator<TestBackend>,
+        allocation: SubAllocation,
+    ) -> Result<()> {
+        allocator.free(
+            allocation.memory_type_index,
+            allocation.memory_block_index,
+            Some(allocation.chunk_id),
+        )
+    }
+
+    #[test]
+    fn creates_and_reuses_shared_blocks() -> Result<()> {
+        let mut allocator = allocator(u64::MAX, None);
+
+        let a = allocator.allocate(0, &request(256), None)?;
+        let b = allocator.allocate(0, &request(256), None)?;
+        assert_eq!(a.memory_block_index, b.memory_block_index);
+        assert_eq!(allocator.backend.live_objects.get(), 1);
+        assert_eq!(allocator.capacity(), BLOCK_SIZE);
+
+        // Doesn't fit in the remaining 512 bytes of the first block.
+        let c = allocator.allocate(0, &request(768), None)?;
+        assert_ne!(c.memory_block_index, a.memory_block_index);
+        assert_eq!(allocator.backend.live_objects.get(), 2);
+        assert_eq!(allocator.memory_types[0].active_general_blocks, 2);
+
+        // Memory freed in the first block is handed out again.
+        free(&mut allocator, b)?;
+        let d = allocator.allocate(0, &request(512), None)?;
+        assert_eq!(d.memory_block_index, a.memory_block_index);
+        assert_eq!(allocator.backend.live_objects.get(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn destroys_empty_blocks() -> Result<()> {
+        let mut allocator = allocator(u64::MAX, None);
+
+        let a = allocator.allocate(0, &request(768), None)?;
+        let b = allocator.allocate(0, &request(768), None)?;
+        assert_eq!(allocator.backend.live_objects.get(), 2);
+
+        // Empty shared blocks are released right away, except for the last one.
+        free(&mut allocator, b)?;
+        assert_eq!(allocator.backend.live_objects.get(), 1);
+        free(&mut allocator, a)?;
+        assert_eq!(allocator.backend.live_objects.get(), 1);
+        assert_eq!(allocator.memory_types[0].active_general_blocks, 1);
+
+        allocator.trim();
+        assert_eq!(allocator.backend.live_objects.get(), 0);
+        assert_eq!(allocator.backend.live_memory.get(), 0);
+        assert_eq!(allocator.memory_heaps[0].usage.load(Ordering::Relaxed), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn dedicated_allocations_get_their_own_block() -> Result<()> {
+        let mut allocator = allocator(u64::MAX, None);
+
+        // Too large for a shared memory block.
+        let large = allocator.allocate(0, &request(4 * BLOCK_SIZE), None)?;
+        assert_eq!(allocator.backend.live_memory.get(), 4 * BLOCK_SIZE);
+
+        // Dedicated to a resource, even though it would fit in a shared memory block.
+        let dedicated = allocator.allocate(0, &request(256), Some(()))?;
+        assert_ne!(dedicated.memory_block_index, large.memory_block_index);
+        assert_eq!(allocator.backend.live_memory.get(), 4 * BLOCK_SIZE + 256);
+
+        // Neither memory block is shared with other allocations.
+        let shared = allocator.allocate(0, &request(256), None)?;
+        assert_ne!(shared.memory_block_index, large.memory_block_index);
+        assert_ne!(shared.memory_block_index, dedicated.memory_block_index);
+        assert_eq!(allocator.backend.live_objects.get(), 3);
+        assert_eq!(allocator.memory_types[0].active_general_blocks, 1);
+
+        // Dedicated memory blocks are destroyed as soon as they are empty.
+        free(&mut allocator, large)?;
+        free(&mut allocator, dedicated)?;
+        assert_eq!(allocator.backend.live_objects.get(), 1);
+        assert_eq!(allocator.backend.live_memory.get(), BLOCK_SIZE);
+
+        free(&mut allocator, shared)?;
+        Ok(())
+    }
+
+    #[test]
+    fn dedicated_blocks_are_not_rounded_to_the_atom_size() -> Result<()> {
+        let mut allocator = allocator_with_atom_size(u64::MAX, None, 256);
+
+        // Memory dedicated to a resource is exactly as large as the allocation.
+        let dedicated = allocator.allocate(0, &request(100), Some(()))?;
+        assert_eq!(dedicated.offset, 0);
+        assert_eq!(allocator.memory_block(&dedicated)?.size, 100);
+        assert_eq!(allocator.backend.live_memory.get(), 100);
+
+        // Other memory blocks, and the allocations in them, are.
+        let personal = allocator.allocate(0, &request(BLOCK_SIZE + 100), None)?;
+        assert_eq!(allocator.memory_block(&personal)?.size, BLOCK_SIZE + 256);
+        let a = allocator.allocate(0, &request(100), None)?;
+        let b = allocator.allocate(0, &request(100), None)?;
+        assert_eq!((a.offset, b.offset), (0, 256));
+
+        free(&mut allocator, dedicated)?;
+        free(&mut allocator, personal)?;
+        assert_eq!(allocator.backend.live_memory.get(), BLOCK_SIZE);
+
+        Ok(())
+    }
+
+    #[test]
+    fn only_destroys_empty_pools() -> Result<()> {
+        let mut allocator = allocator(u64::MAX, None);
+        let pool = CustomPool::new(
+            BLOCK_SIZE,
+            1,
+            None,
+            SubAllocatorKind::default(),
+            AllocationStrategy::default(),
+            None,
+        )?;
+        let pool_index = allocator.create_pool(0, pool)?;
+
+        let a = allocator.allocate_from_pool(0, pool_index, &request(256))?;
+        assert!(matches!(
+            allocator.destroy_pool(0, pool_index),
+            Err(AllocationError::Internal(_))
+        ));
+        // The pool and its memory block are left alone.
+        assert_eq!(allocator.backend.live_objects.get(), 1);
+        let b = allocator.allocate_from_pool(0, pool_index, &request(256))?;
+        assert_eq!(b.memory_block_index, a.memory_block_index);
+
+        free(&mut allocator, a)?;
+        free(&mut allocator, b)?;
+        allocator.destroy_pool(0, pool_index)?;
+        assert_eq!(allocator.backend.live_objects.get(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn request_strategy_overrides_the_default() -> Result<()> {
+        let mut allocator = allocator(u64::MAX, None);
+        let pool = CustomPool::new(
+            BLOCK_SIZE,
+            0,
+            None,
+            SubAllocatorKind::FreeList,
+            AllocationStrategy::WorstFit,
+            None,
+        )?;
+        let pool_index = allocator.create_pool(0, pool)?;
+
+        // Leaves free chunks of 128 bytes at 0, 256 bytes at 256 and 384 bytes at 640 in a shared
+        // memory block and in the memory block of the pool.
+        for pool_index in [None, Some(pool_index)] {
+            let mut allocations = Vec::new();
+            for size in [128, 128, 256, 128] {
+                let request = request(size);
+                allocations.push(match pool_index {
+                    Some(pool_index) => allocator.allocate_from_pool(0, pool_index, &request)?,
+                    None => allocator.allocate(0, &request, None)?,
+                });
+            }
+            free(&mut allocator, allocations[0])?;
+            free(&mut allocator, allocations[2])?;
+        }
+
+        let mut allocate = |pool_index, strategy| -> Result<u64> {
+            let request = AllocationRequest {
+                strategy,
+                ..request(100)
+            };
+            let allocation = match pool_index {
+                Some(pool_index) => allocator.allocate_from_pool(0, pool_index, &request)?,
+                None => allocator.allocate(0, &request, None)?,
+            };
+            free(&mut allocator, allocation)?;
+            Ok(allocation.offset)
+        };
+
+        // The memory type defaults to best fit, the pool to worst fit.
+        assert_eq!(allocate(None, None)?, 0);
+        assert_eq!(allocate(None, Some(AllocationStrategy::WorstFit))?, 640);
+        assert_eq!(allocate(Some(pool_index), None)?, 640);
+        assert_eq!(
+            allocate(Some(pool_index), Some(AllocationStrategy::LowestOffset))?,
+            0
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn out_of_memory_when_blocks_cant_be_created() -> Result<()> {
+        let mut allocator = allocator(BLOCK_SIZE, None);
+
+        let a = allocator.allocate(0, &request(BLOCK_SIZE), None)?;
+        assert!(matches!(
+            allocator.allocate(0, &request(1), None),
+            Err(AllocationError::OutOfMemory)
+        ));
+        // The failed memory block is not accounted for in its heap.
+        assert_eq!(
+            allocator.memory_heaps[0].usage.load(Ordering::Relaxed),
+            BLOCK_SIZE
+        );
+
+        free(&mut allocator, a)?;
+        allocator.allocate(0, &request(1), None)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn out_of_budget_past_heap_size_limit() -> Result<()> {
+        let mut allocator = allocator(u64::MAX, Some(BLOCK_SIZE + 512));
+
+        allocator.allocate(0, &request(BLOCK_SIZE), None)?;
+        assert!(matches!(
+            allocator.allocate(0, &request(1), None),
+            Err(AllocationError::OutOfBudget)
+        ));
+        assert_eq!(allocator.backend.live_objects.get(), 1);
+
+        // Allocations that fit in the remaining budget get their own memory block.
+        allocator.allocate(0, &request(512), Some(()))?;
+        assert_eq!(allocator.backend.live_objects.get(), 2);
+
+        Ok(())
+    }
+}
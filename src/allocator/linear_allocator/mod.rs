@@ -0,0 +1,516 @@
+#![deny(unsafe_code, clippy::unwrap_used)]
+//! Linear (ring buffer) sub-allocator for short-lived allocations.
+//!
+//! Allocations are bumped one after the other, wrapping around to the start of the block once
+//! its end is reached, so allocating never has to search for a free region. Each allocation is
+//! tagged with the frame it was made in, which allows releasing everything older than a given
+//! frame at once. Individually freed allocations only give their memory back once they become
+//! the oldest or newest live allocation.
+use alloc::{borrow::ToOwned, collections::VecDeque, string::ToString, vec::Vec};
+use core::ops::Range;
+
+use log::{log, Level};
+
+#[cfg(feature = "visualizer")]
+pub(crate) mod visualizer;
+
+use super::{
+    align_up, free_list_allocator::MemoryChunk, has_granularity_conflict, is_on_same_page,
+    AllocationContext, AllocationReport, AllocationStrategy, AllocationType, SubAllocator,
+};
+use crate::{AllocationError, Result};
+
+#[derive(Debug)]
+struct LinearChunk {
+    /// `allocation_type` is set to [`AllocationType::Free`] once the allocation is freed
+    /// individually, until it can be popped off either end of the ring.
+    chunk: MemoryChunk,
+    frame: u64,
+}
+
+#[derive(Debug)]
+pub(crate) struct LinearAllocator {
+    size: u64,
+    allocated: u64,
+    pub(crate) chunk_id_counter: u64,
+    /// Allocations from oldest to newest. Both chunk IDs and frames only ever grow towards the
+    /// back, which keeps lookups by chunk ID a binary search.
+    chunks: VecDeque<LinearChunk>,
+    frame: u64,
+    /// Sorted ranges of the IDs of allocations that were released by
+    /// [`SubAllocator::free_frames_before()`] but not yet freed individually.
+    released_chunk_ids: Vec<Range<u64>>,
+}
+
+impl LinearAllocator {
+    pub(crate) fn new(size: u64) -> Self {
+        Self {
+            size,
+            allocated: 0,
+            // 0 is not allowed as a chunk ID.
+            chunk_id_counter: 1,
+            chunks: VecDeque::new(),
+            frame: 0,
+            released_chunk_ids: Vec::new(),
+        }
+    }
+
+    /// Generates a new unique chunk ID
+    fn get_new_chunk_id(&mut self) -> Result<core::num::NonZeroU64> {
+        if self.chunk_id_counter == u64::MAX {
+            // End of chunk id counter reached, no more allocations are possible.
+            return Err(AllocationError::OutOfMemory);
+        }
+
+        let id = self.chunk_id_counter;
+        self.chunk_id_counter += 1;
+        core::num::NonZeroU64::new(id).ok_or_else(|| {
+            AllocationError::Internal("New chunk id was 0, which is not allowed.".into())
+        })
+    }
+
+    fn chunk_index(&self, chunk_id: core::num::NonZeroU64) -> Option<usize> {
+        self.chunks
+            .binary_search_by_key(&chunk_id, |chunk| chunk.chunk.chunk_id)
+            .ok()
+    }
+
+    /// Records that the allocation with `chunk_id` was released in bulk. IDs must be passed in
+    /// increasing order.
+    fn release_chunk_id(&mut self, chunk_id: u64) {
+        match self.released_chunk_ids.last_mut() {
+            Some(range) if range.end == chunk_id => range.end += 1,
+            _ => self.released_chunk_ids.push(chunk_id..chunk_id + 1),
+        }
+    }
+
+    /// Forgets that the allocation with `chunk_id` was released in bulk, returning [`false`] if
+    /// it was not.
+    fn take_released_chunk_id(&mut self, chunk_id: u64) -> bool {
+        let index = self
+            .released_chunk_ids
+            .partition_point(|range| range.end <= chunk_id);
+        let Some(range) = self.released_chunk_ids.get_mut(index) else {
+            return false;
+        };
+        if !range.contains(&chunk_id) {
+            return false;
+        }
+
+        if range.start == chunk_id {
+            range.start += 1;
+            if range.is_empty() {
+                self.released_chunk_ids.remove(index);
+            }
+        } else if range.end == chunk_id + 1 {
+            range.end -= 1;
+        } else {
+            let tail = chunk_id + 1..range.end;
+            range.end = chunk_id;
+            self.released_chunk_ids.insert(index + 1, tail);
+        }
+
+        true
+    }
+
+    /// Pops individually freed allocations off both ends of the ring, making their memory
+    /// available again.
+    fn pop_freed_chunks(&mut self) {
+        while self
+            .chunks
+            .front()
+            .is_some_and(|chunk| chunk.chunk.allocation_type == AllocationType::Free)
+        {
+            self.chunks.pop_front();
+        }
+        while self
+            .chunks
+            .back()
+            .is_some_and(|chunk| chunk.chunk.allocation_type == AllocationType::Free)
+        {
+            self.chunks.pop_back();
+        }
+    }
+
+    /// Computes the offset of a new allocation, placed right after the newest allocation or
+    /// wrapped around to the start of the block when it does not fit at the end.
+    fn find_offset(
+        &self,
+        size: u64,
+        alignment: u64,
+        allocation_type: AllocationType,
+        granularity: u64,
+    ) -> Option<u64> {
+        let (Some(oldest), Some(newest)) = (self.chunks.front(), self.chunks.back()) else {
+            return (size <= self.size).then_some(0);
+        };
+        let (oldest, newest) = (&oldest.chunk, &newest.chunk);
+
+        let fits_before_oldest = |offset: u64| {
+            offset + size <= oldest.offset
+                && !(is_on_same_page(offset, size, oldest.offset, granularity)
+                    && has_granularity_conflict(allocation_type, oldest.allocation_type))
+        };
+
+        let mut offset = align_up(newest.offset + newest.size, alignment);
+        if is_on_same_page(newest.offset, newest.size, offset, granularity)
+            && has_granularity_conflict(newest.allocation_type, allocation_type)
+        {
+            offset = align_up(offset, granularity);
+        }
+
+        if newest.offset < oldest.offset {
+            // Already wrapped around: the only free region lies between the newest and oldest
+            // allocations.
+            fits_before_oldest(offset).then_some(offset)
+        } else if offset + size <= self.size {
+            Some(offset)
+        } else {
+            fits_before_oldest(0).then_some(0)
+        }
+    }
+}
+
+impl SubAllocator for LinearAllocator {
+    fn allocate(
+        &mut self,
+        size: u64,
+        alignment: u64,
+        allocation_type: AllocationType,
+        granularity: u64,
+        _strategy: AllocationStrategy,
+        name: &str,
+        context: AllocationContext,
+    ) -> Result<(u64, core::num::NonZeroU64)> {
+        let offset = self
+            .find_offset(size, alignment, allocation_type, granularity)
+            .ok_or(AllocationError::OutOfMemory)?;
+        let chunk_id = self.get_new_chunk_id()?;
+
+        self.chunks.push_back(LinearChunk {
+            chunk: MemoryChunk {
+                chunk_id,
+                size,
+                offset,
+                allocation_type,
+                alignment: 1,
+                padding: 0,
+                name: Some(name.to_string()),
+                context,
+                prev: None,
+                next: None,
+            },
+            frame: self.frame,
+        });
+        self.allocated += size;
+
+        Ok((offset, chunk_id))
+    }
+
+    fn free(&mut self, chunk_id: Option<core::num::NonZeroU64>) -> Result<()> {
+        let chunk_id = chunk_id
+            .ok_or_else(|| AllocationError::Internal("Chunk ID must be a valid value.".into()))?;
+
+        let Some(index) = self.chunk_index(chunk_id) else {
+            if self.take_released_chunk_id(chunk_id.get()) {
+                // Already released in bulk by `free_frames_before()`.
+                return Ok(());
+            }
+            return Err(AllocationError::Internal(
+                "Attempting to free chunk that is not in chunk list.".into(),
+            ));
+        };
+
+        let chunk = &mut self.chunks[index].chunk;
+        if chunk.allocation_type == AllocationType::Free {
+            return Err(AllocationError::Internal(
+                "Attempting to free a chunk that is already free.".into(),
+            ));
+        }
+
+        chunk.allocation_type = AllocationType::Free;
+        chunk.name = None;
+        chunk.context = AllocationContext::default();
+        self.allocated -= chunk.size;
+
+        self.pop_freed_chunks();
+
+        Ok(())
+    }
+
+    fn rename_allocation(
+        &mut self,
+        chunk_id: Option<core::num::NonZeroU64>,
+        name: &str,
+    ) -> Result<()> {
+        let chunk_id = chunk_id
+            .ok_or_else(|| AllocationError::Internal("Chunk ID must be a valid value.".into()))?;
+
+        let index = self.chunk_index(chunk_id).ok_or_else(|| {
+            AllocationError::Internal(
+                "Attempting to rename chunk that is not in chunk list.".into(),
+            )
+        })?;
+
+        let chunk = &mut self.chunks[index].chunk;
+        if chunk.allocation_type == AllocationType::Free {
+            return Err(AllocationError::Internal(
+                "Attempting to rename a freed allocation.".into(),
+            ));
+        }
+
+        chunk.name = Some(name.into());
+
+        Ok(())
+    }
+
+    fn report_memory_leaks(
+        &self,
+        log_level: Level,
+        memory_type_index: usize,
+        memory_block_index: usize,
+    ) {
+        for LinearChunk { chunk, frame } in self.chunks.iter() {
+            if chunk.allocation_type == AllocationType::Free {
+                continue;
+            }
+            let empty = "".to_string();
+            let name = chunk.name.as_ref().unwrap_or(&empty);
+            let backtrace_info;
+            #[cfg(feature = "std")]
+            {
+                // TODO: Allocation could be avoided here if https://github.com/rust-lang/rust/pull/139135 is merged and stabilized.
+                backtrace_info = format!(
+                    ",
+        backtrace: {}",
+                    chunk.context.backtrace
+                )
+            }
+            #[cfg(not(feature = "std"))]
+            {
+                backtrace_info = ""
+            }
+            log!(
+                log_level,
+                r#"leak detected: {{
+    memory type: {}
+    memory block: {}
+    chunk: {{
+        chunk_id: {},
+        size: 0x{:x},
+        offset: 0x{:x},
+        frame: {},
+        allocation_type: {:?},
+        name: {}{backtrace_info}
+    }}
+}}"#,
+                memory_type_index,
+                memory_block_index,
+                chunk.chunk_id,
+                chunk.size,
+                chunk.offset,
+                frame,
+                chunk.allocation_type,
+                name,
+            );
+        }
+    }
+
+    fn report_allocations(&self) -> Vec<AllocationReport> {
+        self.chunks
+            .iter()
+            .map(|LinearChunk { chunk, .. }| chunk)
+            .filter(|chunk| chunk.allocation_type != AllocationType::Free)
+            .map(|chunk| AllocationReport {
+                name: chunk
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| "<Unnamed linear allocation>".to_owned()),
+                offset: chunk.offset,
+                size: chunk.size,
+                #[cfg(feature = "visualizer")]
+                backtrace: chunk.context.backtrace.clone(),
+            })
+            .collect::<Vec<_>>()
+    }
+
+    fn allocated(&self) -> u64 {
+        self.allocated
+    }
+
+    fn supports_general_allocations(&self) -> bool {
+        true
+    }
+
+    fn begin_frame(&mut self, frame: u64) {
+        self.frame = frame;
+    }
+
+    fn free_frames_before(&mut self, frame: u64) -> Result<()> {
+        while let Some(oldest) = self.chunks.front() {
+            if oldest.frame >= frame {
+                break;
+            }
+            if oldest.chunk.allocation_type != AllocationType::Free {
+                self.allocated -= oldest.chunk.size;
+                let chunk_id = oldest.chunk.chunk_id.get();
+                self.release_chunk_id(chunk_id);
+            }
+            self.chunks.pop_front();
+        }
+
+        self.pop_freed_chunks();
+
+        Ok(())
+    }
+
+    #[cfg(feature = "visualizer")]
+    fn visualizer(&self) -> Option<&dyn crate::visualizer::SubAllocatorVisualizer> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allocate(
+        allocator: &mut LinearAllocator,
+        size: u64,
+        allocation_type: AllocationType,
+        granularity: u64,
+    ) -> Result<(u64, core::num::NonZeroU64)> {
+        allocator.allocate(
+            size,
+            1,
+            allocation_type,
+            granularity,
+            AllocationStrategy::default(),
+            "test",
+            AllocationContext::default(),
+        )
+    }
+
+    #[test]
+    fn allocate_and_free_round_trip() -> Result<()> {
+        let mut allocator = LinearAllocator::new(1024);
+
+        let (offset_a, a) = allocate(&mut allocator, 100, AllocationType::Linear, 1)?;
+        let (offset_b, b) = allocate(&mut allocator, 200, AllocationType::Linear, 1)?;
+        let (offset_c, c) = allocate(&mut allocator, 300, AllocationType::Linear, 1)?;
+        assert_eq!((offset_a, offset_b, offset_c), (0, 100, 300));
+        assert_eq!(allocator.allocated(), 600);
+
+        // Freeing the middle allocation does not give its memory back until it reaches an end.
+        allocator.free(Some(b))?;
+        assert_eq!(allocator.chunks.len(), 3);
+        allocator.free(Some(c))?;
+        assert_eq!(allocator.chunks.len(), 1);
+        allocator.free(Some(a))?;
+
+        assert_eq!(allocator.allocated(), 0);
+        assert!(allocator.chunks.is_empty());
+        let (offset, _) = allocate(&mut allocator, 1024, AllocationType::Linear, 1)?;
+        assert_eq!(offset, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn respects_buffer_image_granularity() -> Result<()> {
+        let mut allocator = LinearAllocator::new(4096);
+
+        allocate(&mut allocator, 16, AllocationType::Linear, 256)?;
+        let (offset, _) = allocate(&mut allocator, 16, AllocationType::NonLinear, 256)?;
+        assert_eq!(offset, 256);
+        let (offset, _) = allocate(&mut allocator, 16, AllocationType::NonLinear, 256)?;
+        assert_eq!(offset, 272);
+
+        Ok(())
+    }
+
+    #[test]
+    fn wraps_around_after_freeing_frames() -> Result<()> {
+        let mut allocator = LinearAllocator::new(1024);
+
+        allocator.begin_frame(0);
+        let (_, a) = allocate(&mut allocator, 400, AllocationType::Linear, 1)?;
+        allocator.begin_frame(1);
+        let (offset_b, _) = allocate(&mut allocator, 400, AllocationType::Linear, 1)?;
+        assert_eq!(offset_b, 400);
+
+        // The remaining 224 bytes at the end of the block are too small, and the start of the
+        // block is still taken by frame 0.
+        assert!(matches!(
+            allocate(&mut allocator, 400, AllocationType::Linear, 1),
+            Err(AllocationError::OutOfMemory)
+        ));
+
+        allocator.free_frames_before(1)?;
+        assert_eq!(allocator.allocated(), 400);
+
+        allocator.begin_frame(2);
+        let (offset, _) = allocate(&mut allocator, 400, AllocationType::Linear, 1)?;
+        assert_eq!(offset, 0);
+        // Once wrapped around, only the region up to the oldest allocation is available.
+        assert!(matches!(
+            allocate(&mut allocator, 1, AllocationType::Linear, 1),
+            Err(AllocationError::OutOfMemory)
+        ));
+
+        // Allocations released in bulk may still be freed individually, but only once.
+        allocator.free(Some(a))?;
+        assert!(matches!(
+            allocator.free(Some(a)),
+            Err(AllocationError::Internal(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn free_only_accepts_released_chunk_ids() -> Result<()> {
+        let mut allocator = LinearAllocator::new(1024);
+
+        let (_, a) = allocate(&mut allocator, 16, AllocationType::Linear, 1)?;
+        let (_, b) = allocate(&mut allocator, 16, AllocationType::Linear, 1)?;
+        let (_, c) = allocate(&mut allocator, 16, AllocationType::Linear, 1)?;
+        let (_, d) = allocate(&mut allocator, 16, AllocationType::Linear, 1)?;
+        allocator.free(Some(b))?;
+
+        allocator.begin_frame(1);
+        let (_, e) = allocate(&mut allocator, 16, AllocationType::Linear, 1)?;
+        allocator.free_frames_before(1)?;
+        assert_eq!(
+            allocator.released_chunk_ids,
+            [a.get()..b.get(), c.get()..e.get()]
+        );
+
+        // `b` was already freed before its frame was released.
+        assert!(matches!(
+            allocator.free(Some(b)),
+            Err(AllocationError::Internal(_))
+        ));
+
+        allocator.free(Some(c))?;
+        assert_eq!(
+            allocator.released_chunk_ids,
+            [a.get()..b.get(), d.get()..e.get()]
+        );
+        allocator.free(Some(a))?;
+        allocator.free(Some(d))?;
+        assert!(allocator.released_chunk_ids.is_empty());
+
+        // IDs that were never handed out are rejected as well.
+        let unknown = core::num::NonZeroU64::new(allocator.chunk_id_counter);
+        assert!(matches!(
+            allocator.free(unknown),
+            Err(AllocationError::Internal(_))
+        ));
+
+        allocator.free(Some(e))?;
+        assert_eq!(allocator.allocated(), 0);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+use super::LinearAllocator;
+use crate::{
+    allocator::AllocationType,
+    visualizer::{
+        render_sparse_memory_chunks_ui, ColorScheme, MemoryChunksVisualizationSettings,
+        SubAllocatorVisualizer,
+    },
+};
+
+impl SubAllocatorVisualizer for LinearAllocator {
+    fn supports_visualization(&self) -> bool {
+        true
+    }
+
+    fn draw_base_info(&self, ui: &mut egui::Ui) {
+        ui.label("linear sub-allocator");
+        ui.label(format!("chunk count: {}", self.chunks.len()));
+        ui.label(format!("chunk id counter: {}", self.chunk_id_counter));
+        ui.label(format!("current frame: {}", self.frame));
+    }
+
+    fn draw_visualization(
+        &self,
+        color_scheme: &ColorScheme,
+        ui: &mut egui::Ui,
+        settings: &MemoryChunksVisualizationSettings,
+    ) {
+        render_sparse_memory_chunks_ui(
+            ui,
+            color_scheme,
+            settings,
+            self.size,
+            self.chunks
+                .iter()
+                .map(|chunk| &chunk.chunk)
+                .filter(|chunk| chunk.allocation_type != AllocationType::Free),
+        );
+    }
+}
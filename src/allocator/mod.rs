@@ -1,6 +1,6 @@
 #[cfg(feature = "std")]
 use alloc::sync::Arc;
-use alloc::{fmt, string::String, vec::Vec};
+use alloc::{boxed::Box, fmt, string::String, vec::Vec};
 use core::ops::Range;
 #[cfg(feature = "std")]
 use std::backtrace::Backtrace;
@@ -9,17 +9,140 @@ use log::*;
 
 use crate::result::*;
 
+pub(crate) mod buddy_allocator;
+pub(crate) use buddy_allocator::BuddyAllocator;
+
+pub(crate) mod defragmentation;
+pub(crate) use defragmentation::AllocationKey;
+pub use defragmentation::{DefragmentationMove, DefragmentationPlan};
+
 pub(crate) mod dedicated_block_allocator;
 pub(crate) use dedicated_block_allocator::DedicatedBlockAllocator;
 
 pub(crate) mod free_list_allocator;
 pub(crate) use free_list_allocator::FreeListAllocator;
 
+pub(crate) mod generic_allocator;
+#[cfg(any(feature = "vulkan", feature = "mock"))]
+pub(crate) use generic_allocator::MemoryPropertyPreferences;
+pub(crate) use generic_allocator::{
+    AllocationRequest, AllocatorCore, CustomPool, GenericAllocator, HeapProperties, MemoryBackend,
+    SubAllocation,
+};
+
+pub(crate) mod linear_allocator;
+pub(crate) use linear_allocator::LinearAllocator;
+
+#[cfg(feature = "std")]
+pub(crate) mod sync_generic_allocator;
+#[cfg(feature = "std")]
+pub(crate) use sync_generic_allocator::SyncGenericAllocator;
+
+#[cfg(feature = "std")]
+pub(crate) mod thread_cache;
+#[cfg(feature = "std")]
+pub use thread_cache::ThreadCacheCreateDesc;
+#[cfg(feature = "std")]
+pub(crate) use thread_cache::{CacheSlice, GenericThreadCache};
+
+pub(crate) mod tlsf_allocator;
+pub(crate) use tlsf_allocator::TlsfAllocator;
+
+/// The algorithm used to place allocations inside memory blocks that hold multiple allocations.
+///
+/// Allocations that are placed in a dedicated memory block are not affected by this setting.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum SubAllocatorKind {
+    /// Keeps track of all free regions in a block and picks the best fitting one on every
+    /// allocation. Allocating is linear in the number of free regions of the block.
+    #[default]
+    FreeList,
+    /// Two-Level Segregated Fit: free regions are kept in segregated lists by size, making
+    /// both allocating and freeing constant-time regardless of fragmentation, at the cost of
+    /// slightly less tight packing than [`SubAllocatorKind::FreeList`].
+    Tlsf,
+    /// Bumps allocations one after the other through the block as a ring buffer, making
+    /// allocating constant-time. Memory is only reused once the oldest (or newest) allocations
+    /// are released, which makes this a good fit for short-lived per-frame data, typically
+    /// through a dedicated linear pool that can release whole frames at once.
+    Linear,
+    /// Rounds every allocation up to a power-of-two sized block, splitting and merging blocks
+    /// in halves. Allocating and freeing only take a logarithmic number of steps, and
+    /// fragmentation stays predictable, at the cost of wasting up to half of each block on
+    /// allocations that are not a power of two in size. Best suited for workloads made of
+    /// power-of-two sized resources such as textures.
+    Buddy,
+    /// Uses a user-provided [`SubAllocator`], created by calling the given function with the size
+    /// of every new memory block. See [`SubAllocator`] for an example.
+    Custom(fn(u64) -> Box<dyn SubAllocator>),
+}
+
+impl SubAllocatorKind {
+    /// Creates a sub-allocator of this kind managing a memory block of `size` bytes.
+    pub(crate) fn create(self, size: u64) -> Box<dyn SubAllocator> {
+        match self {
+            Self::FreeList => Box::new(FreeListAllocator::new(size)),
+            Self::Tlsf => Box::new(TlsfAllocator::new(size)),
+            Self::Linear => Box::new(LinearAllocator::new(size)),
+            Self::Buddy => Box::new(BuddyAllocator::new(size)),
+            Self::Custom(create) => create(size),
+        }
+    }
+}
+
+/// How a sub-allocator picks between multiple free regions of a memory block that can all hold an
+/// allocation.
+///
+/// Only [`SubAllocatorKind::FreeList`] searches through all free regions and takes this into
+/// account, other sub-allocators have a fixed placement policy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AllocationStrategy {
+    /// Picks the smallest free region the allocation fits in, keeping fragmentation low.
+    #[default]
+    BestFit,
+    /// Picks the first free region the allocation fits in, ending the search as early as
+    /// possible. Fastest, but tends to fragment memory blocks more.
+    FirstFit,
+    /// Picks the largest free region, leaving the largest possible remainder behind.
+    WorstFit,
+    /// Picks the free region with the lowest offset, packing allocations towards the start of
+    /// the memory block.
+    LowestOffset,
+}
+
+/// How long shared memory blocks are kept around once they no longer hold any allocation, to avoid
+/// destroying and recreating memory blocks when memory usage goes up and down around a block
+/// boundary.
+///
+/// The last shared memory block of a memory type is always kept, regardless of this setting. All
+/// empty shared memory blocks, including the last one, can be released on demand through the
+/// `trim()` method of the allocators.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmptyBlockRetention {
+    /// Keeps at most this many empty shared memory blocks per memory type, destroying memory
+    /// blocks that become empty beyond that right away.
+    Blocks(usize),
+    /// Keeps empty shared memory blocks until this many frames have passed since they became
+    /// empty, as counted through the `set_current_frame()` method of the allocators.
+    Frames(u64),
+}
+
+impl Default for EmptyBlockRetention {
+    fn default() -> Self {
+        Self::Blocks(0)
+    }
+}
+
+/// The kind of resource an allocation is made for, which a [`SubAllocator`] has to take into
+/// account to keep linear and non-linear resources on separate pages of `granularity` bytes.
 #[derive(PartialEq, Copy, Clone, Debug)]
 #[repr(u8)]
-pub(crate) enum AllocationType {
+pub enum AllocationType {
+    /// Not used by any allocation, only used internally to mark free regions.
     Free,
+    /// Buffers and images with linear tiling.
     Linear,
+    /// Images with optimal (non-linear) tiling.
     NonLinear,
 }
 
@@ -34,6 +157,28 @@ impl AllocationType {
     }
 }
 
+/// Context of an allocation, like the backtrace captured for it when
+/// [`crate::AllocatorDebugSettings::store_stack_traces`] is set.
+///
+/// Passed to [`SubAllocator::allocate()`], and handed back through
+/// [`AllocationReport::with_context()`] and [`MovableAllocation::context`] so that reports and
+/// the visualizer can show it. Its contents depend on the enabled features, but its API does not.
+#[derive(Clone, Debug)]
+pub struct AllocationContext {
+    #[cfg(feature = "std")]
+    pub(crate) backtrace: Arc<Backtrace>,
+}
+
+#[cfg_attr(not(feature = "std"), allow(clippy::derivable_impls))]
+impl Default for AllocationContext {
+    fn default() -> Self {
+        Self {
+            #[cfg(feature = "std")]
+            backtrace: Arc::new(Backtrace::disabled()),
+        }
+    }
+}
+
 /// Describes an allocation in the [`AllocatorReport`].
 #[derive(Clone)]
 pub struct AllocationReport {
@@ -47,6 +192,50 @@ pub struct AllocationReport {
     pub(crate) backtrace: Arc<Backtrace>,
 }
 
+impl AllocationReport {
+    /// Describes an allocation of `size` bytes at `offset` in its memory block, as returned from
+    /// [`SubAllocator::report_allocations()`].
+    pub fn new(name: String, offset: u64, size: u64) -> Self {
+        Self {
+            name,
+            offset,
+            size,
+            #[cfg(feature = "visualizer")]
+            backtrace: Arc::new(Backtrace::disabled()),
+        }
+    }
+
+    /// Attaches the context that was passed to [`SubAllocator::allocate()`] for this
+    /// allocation, whose backtrace is shown by the visualizer.
+    #[cfg_attr(not(feature = "visualizer"), allow(unused_variables, unused_mut))]
+    pub fn with_context(mut self, context: &AllocationContext) -> Self {
+        #[cfg(feature = "visualizer")]
+        {
+            self.backtrace = context.backtrace.clone();
+        }
+        self
+    }
+}
+
+/// Describes a live allocation that defragmentation may move to another memory block, as returned
+/// from [`SubAllocator::movable_allocations()`].
+#[derive(Clone, Debug)]
+pub struct MovableAllocation {
+    /// The ID of the allocation, as returned from [`SubAllocator::allocate()`].
+    pub chunk_id: core::num::NonZeroU64,
+    /// The offset in bytes of the allocation in its memory block.
+    pub offset: u64,
+    /// The size in bytes of the allocation.
+    pub size: u64,
+    /// The alignment that was requested for the allocation.
+    pub alignment: u64,
+    pub allocation_type: AllocationType,
+    /// The name provided to the `allocate()` function.
+    pub name: String,
+    /// The context that was passed to [`SubAllocator::allocate()`] for this allocation.
+    pub context: AllocationContext,
+}
+
 /// Describes a memory block in the [`AllocatorReport`].
 #[derive(Clone)]
 pub struct MemoryBlockReport {
@@ -105,30 +294,152 @@ impl fmt::Debug for AllocatorReport {
     }
 }
 
-#[cfg(feature = "visualizer")]
-pub(crate) trait SubAllocatorBase: crate::visualizer::SubAllocatorVisualizer {}
-#[cfg(not(feature = "visualizer"))]
-pub(crate) trait SubAllocatorBase {}
-
-pub(crate) trait SubAllocator: SubAllocatorBase + fmt::Debug + Sync + Send {
+/// Places allocations inside a single memory block.
+///
+/// A sub-allocator only does bookkeeping on offsets within a block of the size it was created
+/// with, it never touches the memory itself. Implement this trait and select it through
+/// [`SubAllocatorKind::Custom`] to plug in a placement algorithm that is not provided by this
+/// crate.
+///
+/// ```
+/// use std::num::NonZeroU64;
+///
+/// use gpu_allocator::{
+///     AllocationContext, AllocationError, AllocationReport, AllocationStrategy, AllocationType,
+///     Result, SubAllocator, SubAllocatorKind,
+/// };
+///
+/// /// Places allocations one after the other, only reusing memory once the block is empty.
+/// #[derive(Debug)]
+/// struct BumpAllocator {
+///     size: u64,
+///     offset: u64,
+///     allocations: Vec<(NonZeroU64, u64, u64, String)>,
+///     next_id: NonZeroU64,
+/// }
+///
+/// impl SubAllocator for BumpAllocator {
+///     fn allocate(
+///         &mut self,
+///         size: u64,
+///         alignment: u64,
+///         _allocation_type: AllocationType,
+///         granularity: u64,
+///         _strategy: AllocationStrategy,
+///         name: &str,
+///         _context: AllocationContext,
+///     ) -> Result<(u64, NonZeroU64)> {
+///         // Keeping every allocation on its own pages avoids any granularity conflicts.
+///         let alignment = alignment.max(granularity);
+///         let offset = (self.offset + alignment - 1) & !(alignment - 1);
+///         if offset + size > self.size {
+///             return Err(AllocationError::OutOfMemory);
+///         }
+///         let id = self.next_id;
+///         self.next_id = id.checked_add(1).ok_or(AllocationError::OutOfMemory)?;
+///         self.offset = offset + size;
+///         self.allocations.push((id, offset, size, name.to_owned()));
+///         Ok((offset, id))
+///     }
+///
+///     fn free(&mut self, chunk_id: Option<NonZeroU64>) -> Result<()> {
+///         let index = self
+///             .allocations
+///             .iter()
+///             .position(|&(id, ..)| Some(id) == chunk_id)
+///             .ok_or_else(|| AllocationError::Internal("Unknown allocation".into()))?;
+///         self.allocations.swap_remove(index);
+///         if self.allocations.is_empty() {
+///             self.offset = 0;
+///         }
+///         Ok(())
+///     }
+///
+///     fn rename_allocation(&mut self, chunk_id: Option<NonZeroU64>, name: &str) -> Result<()> {
+///         let allocation = self
+///             .allocations
+///             .iter_mut()
+///             .find(|(id, ..)| Some(*id) == chunk_id)
+///             .ok_or_else(|| AllocationError::Internal("Unknown allocation".into()))?;
+///         allocation.3 = name.to_owned();
+///         Ok(())
+///     }
+///
+///     fn report_memory_leaks(
+///         &self,
+///         log_level: log::Level,
+///         memory_type_index: usize,
+///         memory_block_index: usize,
+///     ) {
+///         for (_, offset, size, name) in &self.allocations {
+///             log::log!(
+///                 log_level,
+///                 "leak detected: {name:?} of {size} bytes at offset {offset} \
+///                  (memory type {memory_type_index}, memory block {memory_block_index})"
+///             );
+///         }
+///     }
+///
+///     fn report_allocations(&self) -> Vec<AllocationReport> {
+///         self.allocations
+///             .iter()
+///             .map(|(_, offset, size, name)| {
+///                 AllocationReport::new(name.clone(), *offset, *size)
+///             })
+///             .collect()
+///     }
+///
+///     fn supports_general_allocations(&self) -> bool {
+///         true
+///     }
+///
+///     fn allocated(&self) -> u64 {
+///         self.allocations.iter().map(|&(_, _, size, _)| size).sum()
+///     }
+/// }
+///
+/// let sub_allocator = SubAllocatorKind::Custom(|size| {
+///     Box::new(BumpAllocator {
+///         size,
+///         offset: 0,
+///         allocations: Vec::new(),
+///         next_id: NonZeroU64::MIN,
+///     })
+/// });
+/// ```
+pub trait SubAllocator: fmt::Debug + Sync + Send {
+    /// Finds room for an allocation of `size` bytes aligned to `alignment`, returning its offset
+    /// and an ID that identifies it in the other calls.
+    ///
+    /// Allocations of a different [`AllocationType`] must not share a page of `granularity`
+    /// bytes. `strategy` is a hint on how to pick between multiple suitable free regions, which
+    /// may be ignored. `context` should be kept with the allocation and handed back when
+    /// reporting it. Return [`AllocationError::OutOfMemory`] when the allocation doesn't fit,
+    /// after which the allocator tries another memory block.
+    #[allow(clippy::too_many_arguments)]
     fn allocate(
         &mut self,
         size: u64,
         alignment: u64,
         allocation_type: AllocationType,
         granularity: u64,
+        strategy: AllocationStrategy,
         name: &str,
-        #[cfg(feature = "std")] backtrace: Arc<Backtrace>,
+        context: AllocationContext,
     ) -> Result<(u64, core::num::NonZeroU64)>;
 
+    /// Frees the allocation identified by `chunk_id`.
     fn free(&mut self, chunk_id: Option<core::num::NonZeroU64>) -> Result<()>;
 
+    /// Changes the name of the allocation identified by `chunk_id`.
     fn rename_allocation(
         &mut self,
         chunk_id: Option<core::num::NonZeroU64>,
         name: &str,
     ) -> Result<()>;
 
+    /// Logs every live allocation at `log_level`, called when the allocator is dropped with
+    /// allocations left in this memory block.
     fn report_memory_leaks(
         &self,
         log_level: Level,
@@ -136,12 +447,14 @@ pub(crate) trait SubAllocator: SubAllocatorBase + fmt::Debug + Sync + Send {
         memory_block_index: usize,
     );
 
+    /// Describes every live allocation, used to build the [`AllocatorReport`].
     fn report_allocations(&self) -> Vec<AllocationReport>;
 
     /// Returns [`true`] if this allocator allows sub-allocating multiple allocations, [`false`] if
     /// it is designed to only represent dedicated allocations.
     #[must_use]
     fn supports_general_allocations(&self) -> bool;
+    /// Returns the number of bytes that are currently allocated.
     #[must_use]
     fn allocated(&self) -> u64;
 
@@ -150,6 +463,106 @@ pub(crate) trait SubAllocator: SubAllocatorBase + fmt::Debug + Sync + Send {
     fn is_empty(&self) -> bool {
         self.allocated() == 0
     }
+
+    /// Describes every live allocation so that defragmentation can move them to other memory
+    /// blocks, emptying this one.
+    ///
+    /// Either all or none of the allocations must be returned. Memory blocks of sub-allocators
+    /// returning none, which is the default, are never emptied by defragmentation, though they
+    /// may still receive allocations from other blocks.
+    fn movable_allocations(&self) -> Vec<MovableAllocation> {
+        Vec::new()
+    }
+
+    /// Tags all allocations made from now on with `frame`, for sub-allocators that support
+    /// releasing them in bulk through [`SubAllocator::free_frames_before()`].
+    fn begin_frame(&mut self, _frame: u64) {}
+
+    /// Frees all allocations that were tagged with a frame older than `frame` at once.
+    fn free_frames_before(&mut self, _frame: u64) -> Result<()> {
+        Err(AllocationError::Internal(
+            "Sub-allocator does not support freeing allocations by frame.".into(),
+        ))
+    }
+
+    /// Returns the hooks that draw this sub-allocator in the memory block windows of the backend
+    /// visualizers. Memory blocks of sub-allocators returning [`None`], which is the default,
+    /// are shown without their layout.
+    #[cfg(feature = "visualizer")]
+    fn visualizer(&self) -> Option<&dyn crate::visualizer::SubAllocatorVisualizer> {
+        None
+    }
+}
+
+impl crate::AllocatorDebugSettings {
+    /// Captures the backtrace to store with a new allocation, if enabled.
+    #[cfg(feature = "std")]
+    pub(crate) fn capture_backtrace(self) -> Arc<Backtrace> {
+        Arc::new(if self.store_stack_traces {
+            Backtrace::force_capture()
+        } else {
+            Backtrace::disabled()
+        })
+    }
+
+    /// Captures the [`AllocationContext`] to pass to [`SubAllocator::allocate()`].
+    #[cfg_attr(not(feature = "std"), allow(clippy::unused_self))]
+    pub(crate) fn capture_context(self) -> AllocationContext {
+        AllocationContext {
+            #[cfg(feature = "std")]
+            backtrace: self.capture_backtrace(),
+        }
+    }
+
+    pub(crate) fn log_allocation(self, name: &str, size: u64, alignment: u64) {
+        if self.log_allocations {
+            debug!("Allocating `{name}` of {size} bytes with an alignment of {alignment}.");
+            #[cfg(feature = "std")]
+            if self.log_stack_traces {
+                let backtrace = Backtrace::force_capture();
+                debug!("Allocation stack trace: {backtrace}");
+            }
+        }
+    }
+
+    pub(crate) fn log_free(self, name: Option<&str>) {
+        if self.log_frees {
+            let name = name.unwrap_or("<null>");
+            debug!("Freeing `{name}`.");
+            #[cfg(feature = "std")]
+            if self.log_stack_traces {
+                let backtrace = Backtrace::force_capture();
+                debug!("Free stack trace: {backtrace}");
+            }
+        }
+    }
+}
+
+pub(crate) fn align_down(val: u64, alignment: u64) -> u64 {
+    val & !(alignment - 1u64)
+}
+
+pub(crate) fn align_up(val: u64, alignment: u64) -> u64 {
+    align_down(val + alignment - 1u64, alignment)
+}
+
+/// Test if two suballocations will overlap the same page.
+pub(crate) fn is_on_same_page(offset_a: u64, size_a: u64, offset_b: u64, page_size: u64) -> bool {
+    let end_a = offset_a + size_a - 1;
+    let end_page_a = align_down(end_a, page_size);
+    let start_b = offset_b;
+    let start_page_b = align_down(start_b, page_size);
+
+    end_page_a == start_page_b
+}
+
+/// Test if two allocation types will be conflicting or not.
+pub(crate) fn has_granularity_conflict(type0: AllocationType, type1: AllocationType) -> bool {
+    if type0 == AllocationType::Free || type1 == AllocationType::Free {
+        return false;
+    }
+
+    type0 != type1
 }
 
 pub(crate) fn fmt_bytes(mut amount: u64) -> String {
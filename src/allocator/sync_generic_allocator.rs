@@ -0,0 +1,183 @@
+//! A [`GenericAllocator`] that can be shared between threads.
+//!
+//! Every [`MemoryType`] sits behind a lock of its own, so allocations from different memory types
+//! never wait on each other. Only allocating, freeing and renaming are supported on the shared
+//! allocator; everything that reorganizes memory blocks across a memory type, like creating pools
+//! or defragmenting, still requires exclusive access to the [`GenericAllocator`].
+use alloc::vec::Vec;
+use core::num::NonZeroU64;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use log::Level;
+
+use super::{
+    generic_allocator::{generate_report, AllocatorCore, MemoryBlock, MemoryType},
+    AllocationRequest, AllocatorReport, GenericAllocator, MemoryBackend, SubAllocation,
+};
+use crate::{AllocatorDebugSettings, Result};
+
+pub(crate) struct SyncGenericAllocator<B: MemoryBackend> {
+    /// Holds on to the backend and settings. Its memory types are moved into `memory_types`, and
+    /// moved back when dropped so that it reports leaks and destroys the memory blocks as usual.
+    inner: GenericAllocator<B>,
+    memory_types: Vec<Mutex<MemoryType<B>>>,
+    /// A copy of the properties of every memory type, to pick memory types without locking.
+    memory_type_properties: Vec<B::MemoryTypeProperties>,
+}
+
+impl<B: MemoryBackend> From<GenericAllocator<B>> for SyncGenericAllocator<B>
+where
+    B::MemoryTypeProperties: Clone,
+{
+    fn from(mut inner: GenericAllocator<B>) -> Self {
+        let memory_types = core::mem::take(&mut inner.memory_types);
+        let memory_type_properties = memory_types
+            .iter()
+            .map(|memory_type| memory_type.properties.clone())
+            .collect();
+
+        Self {
+            inner,
+            memory_types: memory_types.into_iter().map(Mutex::new).collect(),
+            memory_type_properties,
+        }
+    }
+}
+
+impl<B: MemoryBackend> SyncGenericAllocator<B> {
+    /// Only the mock backend reads its backend state back.
+    #[cfg_attr(not(feature = "mock"), allow(dead_code))]
+    pub(crate) fn backend(&self) -> &B {
+        &self.inner.backend
+    }
+
+    pub(crate) fn debug_settings(&self) -> AllocatorDebugSettings {
+        self.inner.debug_settings
+    }
+
+    /// Locks a memory type. A panic while the lock was held leaves the bookkeeping of that
+    /// memory type as it was, which is still usable.
+    fn lock(&self, memory_type_index: usize) -> MutexGuard<'_, MemoryType<B>> {
+        self.memory_types[memory_type_index]
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn lock_all(&self) -> impl Iterator<Item = MutexGuard<'_, MemoryType<B>>> {
+        (0..self.memory_types.len()).map(|memory_type_index| self.lock(memory_type_index))
+    }
+
+    pub(crate) fn report_memory_leaks(&self, log_level: Level) {
+        for memory_type in self.lock_all() {
+            memory_type.report_memory_leaks(log_level);
+        }
+    }
+
+    /// Generates a report while locking one memory type at a time, so it is not a consistent
+    /// snapshot when other threads allocate or free in the meantime.
+    pub(crate) fn generate_report(&self) -> AllocatorReport {
+        generate_report(self.lock_all())
+    }
+
+    pub(crate) fn capacity(&self) -> u64 {
+        self.lock_all()
+            .map(|memory_type| memory_type.capacity())
+            .sum()
+    }
+
+    pub(crate) fn trim(&self) {
+        for mut memory_type in self.lock_all() {
+            memory_type.trim(&self.inner.backend);
+        }
+    }
+
+    pub(crate) fn set_current_frame(&self, frame: u64) {
+        for mut memory_type in self.lock_all() {
+            memory_type.set_current_frame(&self.inner.backend, frame);
+        }
+    }
+}
+
+impl<B: MemoryBackend> AllocatorCore<B> for &SyncGenericAllocator<B> {
+    fn debug_settings(&self) -> AllocatorDebugSettings {
+        self.inner.debug_settings
+    }
+
+    fn memory_type_count(&self) -> usize {
+        self.memory_types.len()
+    }
+
+    fn memory_type_properties(&self, memory_type_index: usize) -> &B::MemoryTypeProperties {
+        &self.memory_type_properties[memory_type_index]
+    }
+
+    fn allocate(
+        &mut self,
+        memory_type_index: usize,
+        request: &AllocationRequest<'_>,
+        dedicated_resource: Option<B::DedicatedResource>,
+    ) -> Result<SubAllocation> {
+        self.lock(memory_type_index).allocate(
+            &self.inner.backend,
+            request,
+            dedicated_resource,
+            &self.inner.allocation_sizes,
+        )
+    }
+
+    fn allocate_from_pool(
+        &mut self,
+        memory_type_index: usize,
+        pool_index: usize,
+        request: &AllocationRequest<'_>,
+    ) -> Result<SubAllocation> {
+        self.lock(memory_type_index)
+            .allocate_from_pool(&self.inner.backend, pool_index, request)
+    }
+
+    fn free(
+        &mut self,
+        memory_type_index: usize,
+        memory_block_index: usize,
+        chunk_id: Option<NonZeroU64>,
+    ) -> Result<()> {
+        self.lock(memory_type_index)
+            .free(&self.inner.backend, memory_block_index, chunk_id)
+    }
+
+    fn rename_allocation(
+        &mut self,
+        memory_type_index: usize,
+        memory_block_index: usize,
+        chunk_id: Option<NonZeroU64>,
+        name: &str,
+    ) -> Result<()> {
+        self.lock(memory_type_index)
+            .memory_block_mut(memory_block_index)?
+            .sub_allocator
+            .rename_allocation(chunk_id, name)
+    }
+
+    fn with_memory_block<R>(
+        &self,
+        allocation: &SubAllocation,
+        f: impl FnOnce(&MemoryBlock<B>) -> R,
+    ) -> Result<R> {
+        self.lock(allocation.memory_type_index)
+            .memory_block_mut(allocation.memory_block_index)
+            .map(|block| f(block))
+    }
+}
+
+impl<B: MemoryBackend> Drop for SyncGenericAllocator<B> {
+    fn drop(&mut self) {
+        self.inner.memory_types = core::mem::take(&mut self.memory_types)
+            .into_iter()
+            .map(|memory_type| {
+                memory_type
+                    .into_inner()
+                    .unwrap_or_else(PoisonError::into_inner)
+            })
+            .collect();
+    }
+}
@@ -0,0 +1,343 @@
+//! Per-thread caches that serve small allocations without taking any lock.
+//!
+//! A cache reserves slices of the shared memory blocks of a [`SyncGenericAllocator`], one
+//! allocation at a time, and places small allocations inside those slices with a sub-allocator of
+//! its own. Allocations made from a slice keep a reference to it, so that freeing them from
+//! another thread than the one owning the cache queues the free on the slice instead of touching
+//! the sub-allocator of the cache. The owning thread picks those frees up on its next allocation.
+//!
+//! Flushing the cache hands slices without live allocations back to their memory block. Slices
+//! that still have live allocations are detached from the cache, and handed back once their last
+//! allocation is freed.
+use alloc::{sync::Arc, vec::Vec};
+use core::{
+    fmt,
+    num::NonZeroU64,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use super::{
+    align_up, AllocationRequest, AllocationType, AllocatorCore, FreeListAllocator, MemoryBackend,
+    SubAllocation, SubAllocator, SyncGenericAllocator,
+};
+use crate::{AllocationError, Result};
+
+/// Alignment of every slice, and with that the largest alignment a cached allocation can have.
+const SLICE_ALIGNMENT: u64 = 64 * 1024;
+
+#[derive(Clone, Copy, Debug)]
+pub struct ThreadCacheCreateDesc {
+    /// Size in bytes of the slices the cache reserves from the shared memory blocks.
+    pub slice_size: u64,
+    /// Allocations larger than this are not cached, and are made directly in the shared memory
+    /// blocks instead. Must not be larger than [`ThreadCacheCreateDesc::slice_size`].
+    pub max_allocation_size: u64,
+}
+
+impl Default for ThreadCacheCreateDesc {
+    fn default() -> Self {
+        Self {
+            slice_size: 4 * 1024 * 1024,
+            max_allocation_size: 64 * 1024,
+        }
+    }
+}
+
+/// A slice of a memory block that was reserved by a thread cache, shared with the allocations
+/// made from it.
+pub(crate) struct CacheSlice {
+    /// The allocation in the shared memory block that covers the whole slice.
+    reservation: SubAllocation,
+    /// Set when `state` holds frees from other threads, so that the owning cache only takes the
+    /// lock when there is something to pick up.
+    has_remote_frees: AtomicBool,
+    state: Mutex<CacheSliceState>,
+}
+
+#[derive(Default)]
+struct CacheSliceState {
+    remote_frees: Vec<NonZeroU64>,
+    /// The sub-allocator of the slice, once the cache has let go of it while it still had live
+    /// allocations.
+    detached: Option<FreeListAllocator>,
+}
+
+impl fmt::Debug for CacheSlice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CacheSlice")
+            .field("reservation", &self.reservation)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CacheSlice {
+    fn lock(&self) -> MutexGuard<'_, CacheSliceState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Frees an allocation made from this slice, from any other place than the cache that owns
+    /// the slice.
+    pub(crate) fn free<B: MemoryBackend>(
+        &self,
+        core: &SyncGenericAllocator<B>,
+        chunk_id: NonZeroU64,
+    ) -> Result<()> {
+        let mut state = self.lock();
+        let Some(sub_allocator) = &mut state.detached else {
+            state.remote_frees.push(chunk_id);
+            self.has_remote_frees.store(true, Ordering::Release);
+            return Ok(());
+        };
+
+        sub_allocator.free(Some(chunk_id))?;
+        if sub_allocator.is_empty() {
+            state.detached = None;
+            self.release(core)?;
+        }
+
+        Ok(())
+    }
+
+    /// Frees the reservation of the slice in its shared memory block.
+    fn release<B: MemoryBackend>(&self, mut core: &SyncGenericAllocator<B>) -> Result<()> {
+        core.free(
+            self.reservation.memory_type_index,
+            self.reservation.memory_block_index,
+            Some(self.reservation.chunk_id),
+        )
+    }
+}
+
+/// The part of a slice that only the owning cache touches.
+struct OwnedSlice<A> {
+    shared: Arc<CacheSlice>,
+    sub_allocator: FreeListAllocator,
+    /// The backend allocation handle of the whole slice, that the handles of cached allocations
+    /// are derived from.
+    template: A,
+}
+
+impl<A> OwnedSlice<A> {
+    /// Applies the frees that other threads queued on this slice.
+    fn collect_remote_frees(&mut self) -> Result<()> {
+        if !self.shared.has_remote_frees.swap(false, Ordering::Acquire) {
+            return Ok(());
+        }
+
+        let remote_frees = core::mem::take(&mut self.shared.lock().remote_frees);
+        for chunk_id in remote_frees {
+            self.sub_allocator.free(Some(chunk_id))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// An allocation made from a slice of a thread cache.
+pub(crate) struct CachedAllocation<'a, A> {
+    /// The handle of the whole slice the allocation was made in.
+    pub(crate) template: &'a A,
+    pub(crate) slice: Arc<CacheSlice>,
+    /// Where the allocation is in the memory block. The chunk ID is only meaningful to the slice.
+    pub(crate) allocation: SubAllocation,
+}
+
+/// The backend-agnostic part of a thread cache, with `A` being the `Allocation` type of the
+/// backend.
+pub(crate) struct GenericThreadCache<A> {
+    slices: Vec<OwnedSlice<A>>,
+    slice_size: u64,
+    max_allocation_size: u64,
+}
+
+impl<A> GenericThreadCache<A> {
+    pub(crate) fn new(desc: &ThreadCacheCreateDesc) -> Result<Self> {
+        if desc.slice_size == 0
+            || desc.max_allocation_size == 0
+            || desc.max_allocation_size > desc.slice_size
+        {
+            return Err(AllocationError::InvalidAllocatorCreateDesc(
+                "ThreadCacheCreateDesc::max_allocation_size must be between 1 and slice_size"
+                    .into(),
+            ));
+        }
+
+        Ok(Self {
+            slices: Vec::new(),
+            slice_size: desc.slice_size,
+            max_allocation_size: desc.max_allocation_size,
+        })
+    }
+
+    /// Allocates from a slice in the memory type at `memory_type_index`, reserving a new slice
+    /// when none of them has room.
+    ///
+    /// Returns [`None`] if the allocation is too large or too strictly aligned to be cached, or
+    /// if there is no memory left to reserve a slice from; it should then be made in the shared
+    /// memory blocks instead. `new_template` creates the backend handle of a newly reserved
+    /// slice.
+    pub(crate) fn allocate<B: MemoryBackend>(
+        &mut self,
+        mut core: &SyncGenericAllocator<B>,
+        memory_type_index: usize,
+        request: &AllocationRequest<'_>,
+        new_template: impl FnOnce(SubAllocation) -> Result<A>,
+    ) -> Result<Option<CachedAllocation<'_, A>>> {
+        let slice_alignment = SLICE_ALIGNMENT.max(request.granularity);
+        let atom_size = B::atom_size(core.memory_type_properties(memory_type_index));
+        if request.size > self.max_allocation_size
+            || request.alignment.max(atom_size) > slice_alignment
+        {
+            return Ok(None);
+        }
+
+        let mut cached = None;
+        for (index, slice) in self.slices.iter_mut().enumerate() {
+            if slice.shared.reservation.memory_type_index != memory_type_index {
+                continue;
+            }
+
+            slice.collect_remote_frees()?;
+            match Self::allocate_from_slice(slice, request, atom_size) {
+                Ok(allocation) => {
+                    cached = Some((index, allocation));
+                    break;
+                }
+                Err(AllocationError::OutOfMemory) => {} // Slice is full, continue search.
+                Err(err) => return Err(err),
+            }
+        }
+        if let Some((index, allocation)) = cached {
+            return Ok(Some(self.cached_allocation(index, allocation)));
+        }
+
+        // Slices never share a page with other allocations, so linear and non-linear
+        // allocations can be mixed freely inside of them.
+        let slice_size = (self.slice_size + slice_alignment - 1) & !(slice_alignment - 1);
+        let reservation = AllocationRequest {
+            name: "thread cache slice",
+            size: slice_size,
+            alignment: slice_alignment,
+            allocation_type: AllocationType::NonLinear,
+            granularity: request.granularity,
+            strategy: None,
+            location: request.location,
+            priority: None,
+            backtrace: request.backtrace.clone(),
+        };
+        let reservation = match core.allocate(memory_type_index, &reservation, None) {
+            Ok(reservation) => reservation,
+            Err(AllocationError::OutOfMemory) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let template = match new_template(reservation) {
+            Ok(template) => template,
+            Err(err) => {
+                core.free(
+                    reservation.memory_type_index,
+                    reservation.memory_block_index,
+                    Some(reservation.chunk_id),
+                )?;
+                return Err(err);
+            }
+        };
+
+        let mut slice = OwnedSlice {
+            shared: Arc::new(CacheSlice {
+                reservation,
+                has_remote_frees: AtomicBool::new(false),
+                state: Mutex::default(),
+            }),
+            sub_allocator: FreeListAllocator::new(slice_size),
+            template,
+        };
+        let allocation = match Self::allocate_from_slice(&mut slice, request, atom_size) {
+            Err(AllocationError::OutOfMemory) => Err(AllocationError::Internal(
+                "Allocation that must succeed failed. This is a bug in the allocator.".into(),
+            )),
+            a => a,
+        }?;
+        self.slices.push(slice);
+
+        Ok(Some(
+            self.cached_allocation(self.slices.len() - 1, allocation),
+        ))
+    }
+
+    fn allocate_from_slice(
+        slice: &mut OwnedSlice<A>,
+        request: &AllocationRequest<'_>,
+        atom_size: u64,
+    ) -> Result<SubAllocation> {
+        let reservation = &slice.shared.reservation;
+        let (offset, chunk_id) = slice.sub_allocator.allocate(
+            align_up(request.size, atom_size),
+            request.alignment.max(atom_size),
+            request.allocation_type,
+            request.granularity,
+            request.strategy.unwrap_or_default(),
+            request.name,
+            request.context(),
+        )?;
+
+        Ok(SubAllocation {
+            chunk_id,
+            offset: reservation.offset + offset,
+            size: request.size,
+            memory_type_index: reservation.memory_type_index,
+            memory_block_index: reservation.memory_block_index,
+        })
+    }
+
+    fn cached_allocation(
+        &self,
+        index: usize,
+        allocation: SubAllocation,
+    ) -> CachedAllocation<'_, A> {
+        let slice = &self.slices[index];
+        CachedAllocation {
+            template: &slice.template,
+            slice: slice.shared.clone(),
+            allocation,
+        }
+    }
+
+    /// Frees an allocation made from `slice`, which doesn't need to be owned by this cache.
+    pub(crate) fn free<B: MemoryBackend>(
+        &mut self,
+        core: &SyncGenericAllocator<B>,
+        slice: &Arc<CacheSlice>,
+        chunk_id: NonZeroU64,
+    ) -> Result<()> {
+        match self
+            .slices
+            .iter_mut()
+            .find(|owned| Arc::ptr_eq(&owned.shared, slice))
+        {
+            Some(owned) => owned.sub_allocator.free(Some(chunk_id)),
+            None => slice.free(core, chunk_id),
+        }
+    }
+
+    /// Hands all slices without live allocations back to their memory block, and detaches the
+    /// other slices from this cache.
+    pub(crate) fn flush<B: MemoryBackend>(&mut self, core: &SyncGenericAllocator<B>) -> Result<()> {
+        for slice in core::mem::take(&mut self.slices) {
+            let mut sub_allocator = slice.sub_allocator;
+            let mut state = slice.shared.lock();
+            for chunk_id in core::mem::take(&mut state.remote_frees) {
+                sub_allocator.free(Some(chunk_id))?;
+            }
+
+            if sub_allocator.is_empty() {
+                drop(state);
+                slice.shared.release(core)?;
+            } else {
+                state.detached = Some(sub_allocator);
+            }
+        }
+
+        Ok(())
+    }
+}
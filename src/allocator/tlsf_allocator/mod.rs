@@ -0,0 +1,707 @@
+#![deny(unsafe_code, clippy::unwrap_used)]
+//! Two-Level Segregated Fit sub-allocator.
+//!
+//! Free chunks are bucketed into segregated free lists by size: a first level of power-of-two
+//! size classes, each split linearly into [`SL_INDEX_COUNT`] second-level lists. Two bitmaps
+//! track which lists are non-empty, so finding a suitable free chunk and returning a chunk to its
+//! list are both constant-time operations, independent of how fragmented the block is.
+use alloc::{borrow::ToOwned, string::ToString, vec::Vec};
+#[cfg(all(feature = "std", not(feature = "hashbrown")))]
+use std::collections::HashMap;
+
+#[cfg(feature = "hashbrown")]
+use hashbrown::HashMap;
+use log::{log, Level};
+
+#[cfg(feature = "visualizer")]
+pub(crate) mod visualizer;
+
+use super::{
+    align_up, free_list_allocator::MemoryChunk, has_granularity_conflict, is_on_same_page,
+    AllocationContext, AllocationReport, AllocationStrategy, AllocationType, SubAllocator,
+};
+use crate::{AllocationError, Result};
+
+/// Number of second-level lists per first-level size class, as a power of two.
+const SL_INDEX_COUNT_LOG2: u32 = 5;
+const SL_INDEX_COUNT: usize = 1 << SL_INDEX_COUNT_LOG2;
+/// Sizes below this are all tracked by first-level class 0, which is subdivided with a
+/// granularity of a single byte.
+const SMALL_BLOCK_SIZE: u64 = 1 << SL_INDEX_COUNT_LOG2;
+
+/// Returns the first- and second-level list indices that a free chunk of `size` is stored in.
+fn mapping_insert(size: u64) -> (usize, usize) {
+    if size < SMALL_BLOCK_SIZE {
+        (0, size as usize)
+    } else {
+        let fl = size.ilog2();
+        let sl = (size >> (fl - SL_INDEX_COUNT_LOG2)) ^ (1 << SL_INDEX_COUNT_LOG2);
+        ((fl - SL_INDEX_COUNT_LOG2 + 1) as usize, sl as usize)
+    }
+}
+
+/// Returns the indices of the first list in which every free chunk is at least `size` bytes.
+fn mapping_search(size: u64) -> (usize, usize) {
+    let size = if size < SMALL_BLOCK_SIZE {
+        size
+    } else {
+        let round = (1u64 << (size.ilog2() - SL_INDEX_COUNT_LOG2)) - 1;
+        size.saturating_add(round)
+    };
+
+    mapping_insert(size)
+}
+
+#[derive(Debug)]
+struct TlsfChunk {
+    chunk: MemoryChunk,
+    /// Links within the segregated free list this chunk is in, only valid while it is free.
+    prev_free: Option<core::num::NonZeroU64>,
+    next_free: Option<core::num::NonZeroU64>,
+}
+
+#[derive(Debug)]
+pub(crate) struct TlsfAllocator {
+    size: u64,
+    allocated: u64,
+    pub(crate) chunk_id_counter: u64,
+    chunks: HashMap<core::num::NonZeroU64, TlsfChunk>,
+    /// Bit `fl` is set when any of the second-level lists of first-level class `fl` is non-empty.
+    fl_bitmap: u64,
+    /// Bit `sl` of entry `fl` is set when `free_lists[fl][sl]` is non-empty.
+    sl_bitmaps: Vec<u32>,
+    /// Heads of the segregated free lists.
+    free_lists: Vec<[Option<core::num::NonZeroU64>; SL_INDEX_COUNT]>,
+}
+
+impl TlsfAllocator {
+    pub(crate) fn new(size: u64) -> Self {
+        #[allow(clippy::unwrap_used)]
+        let initial_chunk_id = core::num::NonZeroU64::new(1).unwrap();
+
+        // No free chunk can ever be larger than the block, so there is no need to allocate
+        // lists for the size classes above it.
+        let fl_count = mapping_insert(size).0 + 1;
+
+        let mut allocator = Self {
+            size,
+            allocated: 0,
+            // 0 is not allowed as a chunk ID, 1 is used by the initial chunk, next chunk is going to be 2.
+            chunk_id_counter: 2,
+            chunks: HashMap::default(),
+            fl_bitmap: 0,
+            sl_bitmaps: vec![0; fl_count],
+            free_lists: vec![[None; SL_INDEX_COUNT]; fl_count],
+        };
+
+        allocator.chunks.insert(
+            initial_chunk_id,
+            TlsfChunk {
+                chunk: MemoryChunk {
+                    chunk_id: initial_chunk_id,
+                    size,
+                    offset: 0,
+                    allocation_type: AllocationType::Free,
+                    alignment: 1,
+                    padding: 0,
+                    name: None,
+                    context: AllocationContext::default(),
+                    prev: None,
+                    next: None,
+                },
+                prev_free: None,
+                next_free: None,
+            },
+        );
+        allocator.link_free_chunk(initial_chunk_id, size);
+
+        allocator
+    }
+
+    /// Generates a new unique chunk ID
+    fn get_new_chunk_id(&mut self) -> Result<core::num::NonZeroU64> {
+        if self.chunk_id_counter == u64::MAX {
+            // End of chunk id counter reached, no more allocations are possible.
+            return Err(AllocationError::OutOfMemory);
+        }
+
+        let id = self.chunk_id_counter;
+        self.chunk_id_counter += 1;
+        core::num::NonZeroU64::new(id).ok_or_else(|| {
+            AllocationError::Internal("New chunk id was 0, which is not allowed.".into())
+        })
+    }
+
+    fn chunk(&self, chunk_id: core::num::NonZeroU64) -> Result<&TlsfChunk> {
+        self.chunks
+            .get(&chunk_id)
+            .ok_or_else(|| AllocationError::Internal("Chunk ID not present in chunk list.".into()))
+    }
+
+    fn chunk_mut(&mut self, chunk_id: core::num::NonZeroU64) -> Result<&mut TlsfChunk> {
+        self.chunks
+            .get_mut(&chunk_id)
+            .ok_or_else(|| AllocationError::Internal("Chunk ID not present in chunk list.".into()))
+    }
+
+    /// Pushes a free chunk of `size` to the front of its segregated free list.
+    fn link_free_chunk(&mut self, chunk_id: core::num::NonZeroU64, size: u64) {
+        let (fl, sl) = mapping_insert(size);
+        let head = self.free_lists[fl][sl];
+
+        if let Some(chunk) = self.chunks.get_mut(&chunk_id) {
+            chunk.prev_free = None;
+            chunk.next_free = head;
+        }
+        if let Some(head) = head.and_then(|head| self.chunks.get_mut(&head)) {
+            head.prev_free = Some(chunk_id);
+        }
+
+        self.free_lists[fl][sl] = Some(chunk_id);
+        self.sl_bitmaps[fl] |= 1 << sl;
+        self.fl_bitmap |= 1 << fl;
+    }
+
+    /// Adds a free chunk to the segregated free list matching its size.
+    fn insert_free_chunk(&mut self, chunk_id: core::num::NonZeroU64) -> Result<()> {
+        let size = self.chunk(chunk_id)?.chunk.size;
+        self.link_free_chunk(chunk_id, size);
+        Ok(())
+    }
+
+    /// Unlinks a free chunk from the segregated free list it is in.
+    fn remove_free_chunk(&mut self, chunk_id: core::num::NonZeroU64) -> Result<()> {
+        let (size, prev_free, next_free) = {
+            let chunk = self.chunk(chunk_id)?;
+            (chunk.chunk.size, chunk.prev_free, chunk.next_free)
+        };
+
+        if let Some(prev_free) = prev_free {
+            self.chunk_mut(prev_free)?.next_free = next_free;
+        }
+        if let Some(next_free) = next_free {
+            self.chunk_mut(next_free)?.prev_free = prev_free;
+        }
+
+        let (fl, sl) = mapping_insert(size);
+        if self.free_lists[fl][sl] == Some(chunk_id) {
+            self.free_lists[fl][sl] = next_free;
+            if next_free.is_none() {
+                self.sl_bitmaps[fl] &= !(1 << sl);
+                if self.sl_bitmaps[fl] == 0 {
+                    self.fl_bitmap &= !(1 << fl);
+                }
+            }
+        }
+
+        let chunk = self.chunk_mut(chunk_id)?;
+        chunk.prev_free = None;
+        chunk.next_free = None;
+
+        Ok(())
+    }
+
+    /// Finds the first non-empty free list at or after `(fl, sl)`.
+    fn find_free_list(&self, fl: usize, sl: usize) -> Option<(usize, usize)> {
+        if fl >= self.free_lists.len() {
+            return None;
+        }
+
+        let sl_map = self.sl_bitmaps[fl] & (!0u32 << sl);
+        if sl_map != 0 {
+            return Some((fl, sl_map.trailing_zeros() as usize));
+        }
+
+        let fl_map = self.fl_bitmap & (!0u64).checked_shl(fl as u32 + 1).unwrap_or(0);
+        if fl_map == 0 {
+            return None;
+        }
+
+        let fl = fl_map.trailing_zeros() as usize;
+        Some((fl, self.sl_bitmaps[fl].trailing_zeros() as usize))
+    }
+
+    /// Computes where an allocation would be placed inside the given free chunk, applying the
+    /// same alignment and granularity rules as the free-list allocator. Returns the offset and
+    /// the number of bytes (including padding) that would be taken from the chunk.
+    fn fit_in_chunk(
+        &self,
+        chunk_id: core::num::NonZeroU64,
+        size: u64,
+        alignment: u64,
+        allocation_type: AllocationType,
+        granularity: u64,
+    ) -> Result<Option<(u64, u64)>> {
+        let current_chunk = &self.chunk(chunk_id)?.chunk;
+
+        if current_chunk.size < size {
+            return Ok(None);
+        }
+
+        let mut offset = align_up(current_chunk.offset, alignment);
+
+        if let Some(prev_idx) = current_chunk.prev {
+            let previous = &self.chunk(prev_idx)?.chunk;
+            if is_on_same_page(previous.offset, previous.size, offset, granularity)
+                && has_granularity_conflict(previous.allocation_type, allocation_type)
+            {
+                offset = align_up(offset, granularity);
+            }
+        }
+
+        let padding = offset - current_chunk.offset;
+        let aligned_size = padding + size;
+
+        if aligned_size > current_chunk.size {
+            return Ok(None);
+        }
+
+        if let Some(next_idx) = current_chunk.next {
+            let next = &self.chunk(next_idx)?.chunk;
+            if is_on_same_page(offset, size, next.offset, granularity)
+                && has_granularity_conflict(allocation_type, next.allocation_type)
+            {
+                return Ok(None);
+            }
+        }
+
+        Ok(Some((offset, aligned_size)))
+    }
+
+    /// Finds a free chunk that can hold the allocation, returning its ID along with the offset
+    /// and padded size as computed by [`Self::fit_in_chunk()`].
+    fn find_chunk(
+        &self,
+        size: u64,
+        alignment: u64,
+        allocation_type: AllocationType,
+        granularity: u64,
+    ) -> Result<(core::num::NonZeroU64, u64, u64)> {
+        // The list that `size` itself maps to may hold chunks that are large enough, and would
+        // be the tightest fit, but is not guaranteed to. Try its head before searching the
+        // larger lists.
+        let (fl, sl) = mapping_insert(size);
+        if let Some(head) = self.free_lists.get(fl).and_then(|lists| lists[sl]) {
+            if let Some((offset, aligned_size)) =
+                self.fit_in_chunk(head, size, alignment, allocation_type, granularity)?
+            {
+                return Ok((head, offset, aligned_size));
+            }
+        }
+
+        // Every chunk in the list returned by `mapping_search()` is large enough to hold `size`
+        // bytes, but alignment and granularity padding may still push the allocation past its
+        // end. Only the head of each candidate list is tried, moving on to the next larger list
+        // on failure, which keeps the search bounded by the (constant) number of lists.
+        let (mut fl, mut sl) = mapping_search(size);
+        loop {
+            let (found_fl, found_sl) = self
+                .find_free_list(fl, sl)
+                .ok_or(AllocationError::OutOfMemory)?;
+            let head = self.free_lists[found_fl][found_sl].ok_or_else(|| {
+                AllocationError::Internal("Free list bitmap is out of sync.".into())
+            })?;
+
+            if let Some((offset, aligned_size)) =
+                self.fit_in_chunk(head, size, alignment, allocation_type, granularity)?
+            {
+                return Ok((head, offset, aligned_size));
+            }
+
+            if found_sl + 1 < SL_INDEX_COUNT {
+                (fl, sl) = (found_fl, found_sl + 1);
+            } else {
+                (fl, sl) = (found_fl + 1, 0);
+            }
+        }
+    }
+
+    /// Merges two adjacent chunks. Right chunk will be merged into the left chunk
+    fn merge_chunks(
+        &mut self,
+        chunk_left: core::num::NonZeroU64,
+        chunk_right: core::num::NonZeroU64,
+    ) -> Result<()> {
+        let right = self
+            .chunks
+            .remove(&chunk_right)
+            .ok_or_else(|| AllocationError::Internal("Chunk ID not present in chunk list.".into()))?
+            .chunk;
+
+        let left = &mut self.chunk_mut(chunk_left)?.chunk;
+        left.next = right.next;
+        left.size += right.size;
+
+        if let Some(right_next) = right.next {
+            self.chunk_mut(right_next)?.chunk.prev = Some(chunk_left);
+        }
+
+        Ok(())
+    }
+}
+
+impl SubAllocator for TlsfAllocator {
+    fn allocate(
+        &mut self,
+        size: u64,
+        alignment: u64,
+        allocation_type: AllocationType,
+        granularity: u64,
+        _strategy: AllocationStrategy,
+        name: &str,
+        context: AllocationContext,
+    ) -> Result<(u64, core::num::NonZeroU64)> {
+        let free_size = self.size - self.allocated;
+        if size > free_size {
+            return Err(AllocationError::OutOfMemory);
+        }
+
+        let (chunk_id, offset, aligned_size) =
+            self.find_chunk(size, alignment, allocation_type, granularity)?;
+
+        let (chunk_offset, chunk_size, chunk_next) = {
+            let chunk = &self.chunk(chunk_id)?.chunk;
+            (chunk.offset, chunk.size, chunk.next)
+        };
+
+        // Reserve the ID up front so that running out of IDs leaves the lists untouched.
+        let remainder_id = if chunk_size > aligned_size {
+            Some(self.get_new_chunk_id()?)
+        } else {
+            None
+        };
+
+        self.remove_free_chunk(chunk_id)?;
+
+        if let Some(remainder_id) = remainder_id {
+            self.chunks.insert(
+                remainder_id,
+                TlsfChunk {
+                    chunk: MemoryChunk {
+                        chunk_id: remainder_id,
+                        size: chunk_size - aligned_size,
+                        offset: chunk_offset + aligned_size,
+                        allocation_type: AllocationType::Free,
+                        alignment: 1,
+                        padding: 0,
+                        name: None,
+                        context: AllocationContext::default(),
+                        prev: Some(chunk_id),
+                        next: chunk_next,
+                    },
+                    prev_free: None,
+                    next_free: None,
+                },
+            );
+
+            if let Some(next_id) = chunk_next {
+                self.chunk_mut(next_id)?.chunk.prev = Some(remainder_id);
+            }
+
+            self.insert_free_chunk(remainder_id)?;
+        }
+
+        let chunk = &mut self.chunk_mut(chunk_id)?.chunk;
+        chunk.size = aligned_size;
+        chunk.allocation_type = allocation_type;
+        chunk.name = Some(name.to_string());
+        chunk.context = context;
+        if remainder_id.is_some() {
+            chunk.next = remainder_id;
+        }
+
+        self.allocated += aligned_size;
+
+        Ok((offset, chunk_id))
+    }
+
+    fn free(&mut self, chunk_id: Option<core::num::NonZeroU64>) -> Result<()> {
+        let chunk_id = chunk_id
+            .ok_or_else(|| AllocationError::Internal("Chunk ID must be a valid value.".into()))?;
+
+        let (next_id, prev_id) = {
+            let chunk = &mut self
+                .chunks
+                .get_mut(&chunk_id)
+                .ok_or_else(|| {
+                    AllocationError::Internal(
+                        "Attempting to free chunk that is not in chunk list.".into(),
+                    )
+                })?
+                .chunk;
+
+            if chunk.allocation_type == AllocationType::Free {
+                return Err(AllocationError::Internal(
+                    "Attempting to free a chunk that is already free.".into(),
+                ));
+            }
+
+            chunk.allocation_type = AllocationType::Free;
+            chunk.name = None;
+            chunk.context = AllocationContext::default();
+
+            self.allocated -= chunk.size;
+
+            (chunk.next, chunk.prev)
+        };
+
+        let mut free_id = chunk_id;
+
+        if let Some(next_id) = next_id {
+            if self.chunk(next_id)?.chunk.allocation_type == AllocationType::Free {
+                self.remove_free_chunk(next_id)?;
+                self.merge_chunks(free_id, next_id)?;
+            }
+        }
+
+        if let Some(prev_id) = prev_id {
+            if self.chunk(prev_id)?.chunk.allocation_type == AllocationType::Free {
+                self.remove_free_chunk(prev_id)?;
+                self.merge_chunks(prev_id, free_id)?;
+                free_id = prev_id;
+            }
+        }
+
+        self.insert_free_chunk(free_id)
+    }
+
+    fn rename_allocation(
+        &mut self,
+        chunk_id: Option<core::num::NonZeroU64>,
+        name: &str,
+    ) -> Result<()> {
+        let chunk_id = chunk_id
+            .ok_or_else(|| AllocationError::Internal("Chunk ID must be a valid value.".into()))?;
+
+        let chunk = &mut self
+            .chunks
+            .get_mut(&chunk_id)
+            .ok_or_else(|| {
+                AllocationError::Internal(
+                    "Attempting to rename chunk that is not in chunk list.".into(),
+                )
+            })?
+            .chunk;
+
+        if chunk.allocation_type == AllocationType::Free {
+            return Err(AllocationError::Internal(
+                "Attempting to rename a freed allocation.".into(),
+            ));
+        }
+
+        chunk.name = Some(name.into());
+
+        Ok(())
+    }
+
+    fn report_memory_leaks(
+        &self,
+        log_level: Level,
+        memory_type_index: usize,
+        memory_block_index: usize,
+    ) {
+        for (chunk_id, TlsfChunk { chunk, .. }) in self.chunks.iter() {
+            if chunk.allocation_type == AllocationType::Free {
+                continue;
+            }
+            let empty = "".to_string();
+            let name = chunk.name.as_ref().unwrap_or(&empty);
+            let backtrace_info;
+            #[cfg(feature = "std")]
+            {
+                // TODO: Allocation could be avoided here if https://github.com/rust-lang/rust/pull/139135 is merged and stabilized.
+                backtrace_info = format!(
+                    ",
+        backtrace: {}",
+                    chunk.context.backtrace
+                )
+            }
+            #[cfg(not(feature = "std"))]
+            {
+                backtrace_info = ""
+            }
+            log!(
+                log_level,
+                r#"leak detected: {{
+    memory type: {}
+    memory block: {}
+    chunk: {{
+        chunk_id: {},
+        size: 0x{:x},
+        offset: 0x{:x},
+        allocation_type: {:?},
+        name: {}{backtrace_info}
+    }}
+}}"#,
+                memory_type_index,
+                memory_block_index,
+                chunk_id,
+                chunk.size,
+                chunk.offset,
+                chunk.allocation_type,
+                name,
+            );
+        }
+    }
+
+    fn report_allocations(&self) -> Vec<AllocationReport> {
+        self.chunks
+            .values()
+            .map(|TlsfChunk { chunk, .. }| chunk)
+            .filter(|chunk| chunk.allocation_type != AllocationType::Free)
+            .map(|chunk| AllocationReport {
+                name: chunk
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| "<Unnamed TLSF allocation>".to_owned()),
+                offset: chunk.offset,
+                size: chunk.size,
+                #[cfg(feature = "visualizer")]
+                backtrace: chunk.context.backtrace.clone(),
+            })
+            .collect::<Vec<_>>()
+    }
+
+    fn allocated(&self) -> u64 {
+        self.allocated
+    }
+
+    fn supports_general_allocations(&self) -> bool {
+        true
+    }
+
+    #[cfg(feature = "visualizer")]
+    fn visualizer(&self) -> Option<&dyn crate::visualizer::SubAllocatorVisualizer> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allocate(
+        allocator: &mut TlsfAllocator,
+        size: u64,
+        alignment: u64,
+        allocation_type: AllocationType,
+        granularity: u64,
+    ) -> Result<(u64, core::num::NonZeroU64)> {
+        allocator.allocate(
+            size,
+            alignment,
+            allocation_type,
+            granularity,
+            AllocationStrategy::default(),
+            "test",
+            AllocationContext::default(),
+        )
+    }
+
+    #[test]
+    fn mapping_at_class_boundaries() {
+        // Small sizes map one-to-one onto the lists of first-level class 0.
+        assert_eq!(mapping_insert(0), (0, 0));
+        assert_eq!(mapping_insert(SMALL_BLOCK_SIZE - 1), (0, 31));
+        // Every following class starts at a power of two, split in `SL_INDEX_COUNT` lists.
+        assert_eq!(mapping_insert(SMALL_BLOCK_SIZE), (1, 0));
+        assert_eq!(mapping_insert(63), (1, 31));
+        assert_eq!(mapping_insert(64), (2, 0));
+        assert_eq!(mapping_insert(65), (2, 0));
+        assert_eq!(mapping_insert(66), (2, 1));
+        assert_eq!(mapping_insert(127), (2, 31));
+        assert_eq!(mapping_insert(128), (3, 0));
+
+        // Searching rounds up to the next list whose chunks are all large enough.
+        assert_eq!(mapping_search(31), (0, 31));
+        assert_eq!(mapping_search(64), (2, 0));
+        assert_eq!(mapping_search(65), (2, 1));
+        assert_eq!(mapping_search(127), (3, 0));
+    }
+
+    #[test]
+    fn bitmaps_track_free_lists() -> Result<()> {
+        let mut allocator = TlsfAllocator::new(1024);
+        let (fl, sl) = mapping_insert(1024);
+        assert_eq!(allocator.fl_bitmap, 1 << fl);
+        assert_eq!(allocator.sl_bitmaps[fl], 1 << sl);
+
+        let (_, chunk_id) = allocate(&mut allocator, 1024, 1, AllocationType::Linear, 1)?;
+        assert_eq!(allocator.fl_bitmap, 0);
+        assert!(allocator.sl_bitmaps.iter().all(|&sl_bitmap| sl_bitmap == 0));
+
+        allocator.free(Some(chunk_id))?;
+        assert_eq!(allocator.fl_bitmap, 1 << fl);
+        assert_eq!(allocator.sl_bitmaps[fl], 1 << sl);
+        assert_eq!(allocator.free_lists[fl][sl], Some(chunk_id));
+
+        Ok(())
+    }
+
+    #[test]
+    fn allocate_and_free_round_trip() -> Result<()> {
+        let mut allocator = TlsfAllocator::new(1024);
+
+        let (offset_a, a) = allocate(&mut allocator, 100, 1, AllocationType::Linear, 1)?;
+        let (offset_b, b) = allocate(&mut allocator, 200, 64, AllocationType::Linear, 1)?;
+        let (offset_c, c) = allocate(&mut allocator, 50, 1, AllocationType::Linear, 1)?;
+        assert_eq!(offset_a, 0);
+        assert_eq!(offset_b, 128);
+        assert!(offset_c >= offset_b + 200);
+        assert_eq!(allocator.allocated(), 128 + 200 + 50);
+
+        // Free out of order, so that both the merge with the previous and the next chunk run.
+        allocator.free(Some(b))?;
+        allocator.free(Some(a))?;
+        allocator.free(Some(c))?;
+
+        assert_eq!(allocator.allocated(), 0);
+        assert_eq!(allocator.chunks.len(), 1);
+        let (offset, _) = allocate(&mut allocator, 1024, 1, AllocationType::Linear, 1)?;
+        assert_eq!(offset, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn respects_buffer_image_granularity() -> Result<()> {
+        let mut allocator = TlsfAllocator::new(4096);
+
+        allocate(&mut allocator, 16, 1, AllocationType::Linear, 256)?;
+        // A non-linear resource may not share a page with the linear one before it.
+        let (offset, _) = allocate(&mut allocator, 16, 1, AllocationType::NonLinear, 256)?;
+        assert_eq!(offset, 256);
+        // Resources of the same type may share a page.
+        let (offset, _) = allocate(&mut allocator, 16, 1, AllocationType::NonLinear, 256)?;
+        assert_eq!(offset, 272);
+
+        Ok(())
+    }
+
+    #[test]
+    fn out_of_memory_when_exhausted() -> Result<()> {
+        let mut allocator = TlsfAllocator::new(1024);
+
+        assert!(matches!(
+            allocate(&mut allocator, 2048, 1, AllocationType::Linear, 1),
+            Err(AllocationError::OutOfMemory)
+        ));
+
+        let (_, a) = allocate(&mut allocator, 512, 1, AllocationType::Linear, 1)?;
+        allocate(&mut allocator, 512, 1, AllocationType::Linear, 1)?;
+        assert!(matches!(
+            allocate(&mut allocator, 1, 1, AllocationType::Linear, 1),
+            Err(AllocationError::OutOfMemory)
+        ));
+
+        allocator.free(Some(a))?;
+        assert!(matches!(
+            allocator.free(Some(a)),
+            Err(AllocationError::Internal(_))
+        ));
+        let (offset, _) = allocate(&mut allocator, 512, 1, AllocationType::Linear, 1)?;
+        assert_eq!(offset, 0);
+
+        Ok(())
+    }
+}
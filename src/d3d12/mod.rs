@@ -1,15 +1,11 @@
-#[cfg(feature = "std")]
-use alloc::sync::Arc;
 use alloc::{boxed::Box, string::String, vec::Vec};
 use core::{
     fmt,
     // TODO: Remove when bumping MSRV to 1.80
     mem::size_of_val,
 };
-#[cfg(feature = "std")]
-use std::backtrace::Backtrace;
 
-use log::{debug, warn, Level};
+use log::{warn, Level};
 use windows::Win32::{
     Foundation::E_OUTOFMEMORY,
     Graphics::{
@@ -25,10 +21,12 @@ pub use visualizer::AllocatorVisualizer;
 
 use crate::{
     allocator::{
-        AllocationType, AllocatorReport, DedicatedBlockAllocator, FreeListAllocator,
-        MemoryBlockReport, SubAllocator,
+        AllocationKey, AllocationRequest, AllocationType, AllocatorReport, CustomPool,
+        GenericAllocator, MemoryBackend, SubAllocation,
     },
-    AllocationError, AllocationSizes, AllocatorDebugSettings, MemoryLocation, Result,
+    AllocationError, AllocationSizes, AllocationStrategy, AllocatorDebugSettings,
+    DefragmentationMove, DefragmentationPlan, EmptyBlockRetention, MemoryLocation, Result,
+    SubAllocatorKind,
 };
 
 /// [`ResourceCategory`] is used for supporting [`D3D12_RESOURCE_HEAP_TIER_1`].
@@ -59,7 +57,7 @@ pub struct ResourceCreateDesc<'a> {
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
-enum HeapCategory {
+pub(crate) enum HeapCategory {
     All,
     Buffer,
     RtvDsvTexture,
@@ -108,6 +106,11 @@ pub struct AllocationCreateDesc<'a> {
     ///
     /// [helper `into()` function]: ResourceCategory::from()
     pub resource_category: ResourceCategory,
+    /// Overrides [`AllocatorCreateDesc::allocation_strategy`] for this allocation.
+    pub allocation_strategy: Option<AllocationStrategy>,
+    /// Allocates from the heaps of this [`Pool`] instead of the shared heaps, in which case
+    /// [`Self::location`] is ignored.
+    pub pool: Option<&'a Pool>,
 }
 
 impl<'a> AllocationCreateDesc<'a> {
@@ -131,6 +134,8 @@ impl<'a> AllocationCreateDesc<'a> {
             size: allocation_info.SizeInBytes,
             alignment: allocation_info.Alignment,
             resource_category,
+            allocation_strategy: None,
+            pool: None,
         }
     }
 }
@@ -164,6 +169,69 @@ pub struct AllocatorCreateDesc {
     pub device: ID3D12DeviceVersion,
     pub debug_settings: AllocatorDebugSettings,
     pub allocation_sizes: AllocationSizes,
+    /// The algorithm used to place allocations inside shared memory blocks.
+    pub sub_allocator: SubAllocatorKind,
+    /// How free regions are picked inside shared memory blocks, unless overridden by
+    /// [`AllocationCreateDesc::allocation_strategy`].
+    pub allocation_strategy: AllocationStrategy,
+    /// How long shared memory blocks are kept around once they no longer hold any allocation.
+    pub empty_block_retention: EmptyBlockRetention,
+}
+
+/// Describes a [`Pool`] to create through [`Allocator::create_pool()`].
+#[derive(Clone, Debug)]
+pub struct PoolCreateDesc {
+    /// Location where the heaps of the pool should be stored
+    pub location: MemoryLocation,
+    /// The category of resources that will be allocated from the pool, see [`ResourceCategory`].
+    pub resource_category: ResourceCategory,
+    /// Size in bytes of every heap of the pool. Larger allocations can't be made from the pool.
+    pub block_size: u64,
+    /// Number of heaps that are created together with the pool, and kept around when they become
+    /// empty.
+    pub min_block_count: usize,
+    /// Maximum number of heaps of the pool, after which allocating from a full pool fails with
+    /// [`AllocationError::OutOfMemory`]. [`None`] lets the pool grow as needed.
+    pub max_block_count: Option<usize>,
+    /// The algorithm used to place allocations inside the heaps of the pool.
+    pub sub_allocator: SubAllocatorKind,
+    /// How allocations pick between the free regions of the heaps of the pool, unless overridden
+    /// through [`AllocationCreateDesc::allocation_strategy`].
+    pub allocation_strategy: AllocationStrategy,
+}
+
+/// A set of heaps of a single memory type, kept apart from the heaps that are shared by all other
+/// allocations.
+///
+/// Allocations are made from a pool by setting [`AllocationCreateDesc::pool`], which allows
+/// isolating the allocations of a subsystem and putting a hard limit on the memory it uses.
+#[derive(Debug)]
+pub struct Pool {
+    memory_type_index: usize,
+    pool_index: usize,
+}
+
+/// Describes a [`LinearPool`] to create through [`Allocator::create_linear_pool()`].
+#[derive(Clone, Debug)]
+pub struct LinearPoolCreateDesc {
+    /// Size in bytes of the single heap backing the pool.
+    pub size: u64,
+    /// Location where the heap of the pool should be stored
+    pub location: MemoryLocation,
+    /// The category of resources that will be allocated from the pool, see [`ResourceCategory`].
+    pub resource_category: ResourceCategory,
+}
+
+/// A dedicated heap for short-lived allocations, such as per-frame upload and constant buffers.
+///
+/// Allocations are bumped through the heap as a ring buffer, see [`SubAllocatorKind::Linear`].
+/// Each allocation is tagged with the frame set through [`Allocator::begin_linear_pool_frame()`],
+/// so that all allocations of completed frames can be released at once with
+/// [`Allocator::free_linear_pool_frames_before()`] instead of freeing them one by one.
+#[derive(Debug)]
+pub struct LinearPool {
+    memory_type_index: usize,
+    memory_block_index: usize,
 }
 
 pub enum ResourceType<'a> {
@@ -256,262 +324,104 @@ impl Allocation {
     pub fn is_null(&self) -> bool {
         self.chunk_id.is_none()
     }
-}
 
-#[derive(Debug)]
-struct MemoryBlock {
-    heap: ID3D12Heap,
-    size: u64,
-    sub_allocator: Box<dyn SubAllocator>,
+    /// Where this allocation lives in the [`GenericAllocator`], or [`None`] for null allocations.
+    fn key(&self) -> Option<AllocationKey> {
+        Some((
+            self.memory_type_index,
+            self.memory_block_index,
+            self.chunk_id?,
+        ))
+    }
 }
-impl MemoryBlock {
-    fn new(
-        device: &ID3D12Device,
-        size: u64,
-        heap_properties: &D3D12_HEAP_PROPERTIES,
-        heap_category: HeapCategory,
-        dedicated: bool,
-    ) -> Result<Self> {
-        let heap = {
-            let mut desc = D3D12_HEAP_DESC {
-                SizeInBytes: size,
-                Properties: *heap_properties,
-                Alignment: D3D12_DEFAULT_MSAA_RESOURCE_PLACEMENT_ALIGNMENT as u64,
-                ..Default::default()
-            };
-            desc.Flags = match heap_category {
-                HeapCategory::All => D3D12_HEAP_FLAG_NONE,
-                HeapCategory::Buffer => D3D12_HEAP_FLAG_ALLOW_ONLY_BUFFERS,
-                HeapCategory::RtvDsvTexture => D3D12_HEAP_FLAG_ALLOW_ONLY_RT_DS_TEXTURES,
-                HeapCategory::OtherTexture => D3D12_HEAP_FLAG_ALLOW_ONLY_NON_RT_DS_TEXTURES,
-            };
-
-            let mut heap = None;
-            let hr = unsafe { device.CreateHeap(&desc, &mut heap) };
-            match hr {
-                Err(e) if e.code() == E_OUTOFMEMORY => Err(AllocationError::OutOfMemory),
-                Err(e) => Err(AllocationError::Internal(format!(
-                    "ID3D12Device::CreateHeap failed: {e}"
-                ))),
-                Ok(()) => heap.ok_or_else(|| {
-                    AllocationError::Internal(
-                        "ID3D12Heap pointer is null, but should not be.".into(),
-                    )
-                }),
-            }?
-        };
 
-        let sub_allocator: Box<dyn SubAllocator> = if dedicated {
-            Box::new(DedicatedBlockAllocator::new(size))
-        } else {
-            Box::new(FreeListAllocator::new(size))
-        };
-
-        Ok(Self {
-            heap,
-            size,
-            sub_allocator,
-        })
+impl DefragmentationMove<Allocation> {
+    /// Returns [`true`] if this move relocates `allocation`.
+    pub fn is_for(&self, allocation: &Allocation) -> bool {
+        self.moves(allocation.key())
     }
 }
 
+/// Properties of a D3D12 memory type.
 #[derive(Debug)]
-struct MemoryType {
-    memory_blocks: Vec<Option<MemoryBlock>>,
-    committed_allocations: CommittedAllocationStatistics,
-    memory_location: MemoryLocation,
-    heap_category: HeapCategory,
-    heap_properties: D3D12_HEAP_PROPERTIES,
-    memory_type_index: usize,
-    active_general_blocks: usize,
+pub(crate) struct MemoryTypeProperties {
+    pub(crate) memory_location: MemoryLocation,
+    pub(crate) heap_category: HeapCategory,
+    pub(crate) heap_properties: D3D12_HEAP_PROPERTIES,
 }
 
-impl MemoryType {
-    fn allocate(
-        &mut self,
-        device: &ID3D12DeviceVersion,
-        desc: &AllocationCreateDesc<'_>,
-        #[cfg(feature = "std")] backtrace: Arc<Backtrace>,
-        allocation_sizes: &AllocationSizes,
-    ) -> Result<Allocation> {
-        let allocation_type = AllocationType::Linear;
-
-        let is_host = self.heap_properties.Type != D3D12_HEAP_TYPE_DEFAULT;
-        let memblock_size = allocation_sizes.get_memblock_size(is_host, self.active_general_blocks);
-
-        let size = desc.size;
-        let alignment = desc.alignment;
-
-        // Create a dedicated block for large memory allocations
-        if size > memblock_size {
-            let mem_block = MemoryBlock::new(
-                device,
-                size,
-                &self.heap_properties,
-                self.heap_category,
-                true,
-            )?;
-
-            let block_index = self.memory_blocks.iter().position(|block| block.is_none());
-            let block_index = match block_index {
-                Some(i) => {
-                    self.memory_blocks[i].replace(mem_block);
-                    i
-                }
-                None => {
-                    self.memory_blocks.push(Some(mem_block));
-                    self.memory_blocks.len() - 1
-                }
-            };
-
-            let mem_block = self.memory_blocks[block_index]
-                .as_mut()
-                .ok_or_else(|| AllocationError::Internal("Memory block must be Some".into()))?;
-
-            let (offset, chunk_id) = mem_block.sub_allocator.allocate(
-                size,
-                alignment,
-                allocation_type,
-                1,
-                desc.name,
-                #[cfg(feature = "std")]
-                backtrace,
-            )?;
-
-            return Ok(Allocation {
-                chunk_id: Some(chunk_id),
-                size,
-                offset,
-                memory_block_index: block_index,
-                memory_type_index: self.memory_type_index,
-                heap: mem_block.heap.clone(),
-                name: Some(desc.name.into()),
-            });
-        }
-
-        let mut empty_block_index = None;
-        for (mem_block_i, mem_block) in self.memory_blocks.iter_mut().enumerate().rev() {
-            if let Some(mem_block) = mem_block {
-                let allocation = mem_block.sub_allocator.allocate(
-                    size,
-                    alignment,
-                    allocation_type,
-                    1,
-                    desc.name,
-                    #[cfg(feature = "std")]
-                    backtrace.clone(),
-                );
-
-                match allocation {
-                    Ok((offset, chunk_id)) => {
-                        return Ok(Allocation {
-                            chunk_id: Some(chunk_id),
-                            offset,
-                            size,
-                            memory_block_index: mem_block_i,
-                            memory_type_index: self.memory_type_index,
-                            heap: mem_block.heap.clone(),
-                            name: Some(desc.name.into()),
-                        });
-                    }
-                    Err(AllocationError::OutOfMemory) => {} // Block is full, continue search.
-                    Err(err) => return Err(err),            // Unhandled error, return.
-                }
-            } else if empty_block_index.is_none() {
-                empty_block_index = Some(mem_block_i);
-            }
-        }
-
-        let new_memory_block = MemoryBlock::new(
-            device,
-            memblock_size,
-            &self.heap_properties,
-            self.heap_category,
-            false,
-        )?;
+pub(crate) struct D3D12Backend {
+    device: ID3D12DeviceVersion,
+}
 
-        let new_block_index = if let Some(block_index) = empty_block_index {
-            self.memory_blocks[block_index] = Some(new_memory_block);
-            block_index
-        } else {
-            self.memory_blocks.push(Some(new_memory_block));
-            self.memory_blocks.len() - 1
-        };
+impl MemoryBackend for D3D12Backend {
+    type MemoryTypeProperties = MemoryTypeProperties;
+    type Memory = ID3D12Heap;
+    /// Heaps can't be dedicated to a resource, committed resources are used for that instead.
+    type DedicatedResource = core::convert::Infallible;
 
-        self.active_general_blocks += 1;
+    fn is_host_visible(properties: &MemoryTypeProperties) -> bool {
+        properties.heap_properties.Type != D3D12_HEAP_TYPE_DEFAULT
+    }
 
-        let mem_block = self.memory_blocks[new_block_index]
-            .as_mut()
-            .ok_or_else(|| AllocationError::Internal("Memory block must be Some".into()))?;
-        let allocation = mem_block.sub_allocator.allocate(
-            size,
-            alignment,
-            allocation_type,
-            1,
-            desc.name,
-            #[cfg(feature = "std")]
-            backtrace,
-        );
-        let (offset, chunk_id) = match allocation {
-            Err(AllocationError::OutOfMemory) => Err(AllocationError::Internal(
-                "Allocation that must succeed failed. This is a bug in the allocator.".into(),
-            )),
-            a => a,
-        }?;
+    /// D3D12 does not expose its memory heaps, so every memory type shares a single heap.
+    fn heap_index(_properties: &MemoryTypeProperties) -> usize {
+        0
+    }
 
-        Ok(Allocation {
-            chunk_id: Some(chunk_id),
-            offset,
-            size,
-            memory_block_index: new_block_index,
-            memory_type_index: self.memory_type_index,
-            heap: mem_block.heap.clone(),
-            name: Some(desc.name.into()),
-        })
+    fn atom_size(_properties: &MemoryTypeProperties) -> u64 {
+        1
     }
 
-    #[allow(clippy::needless_pass_by_value)]
-    fn free(&mut self, allocation: Allocation) -> Result<()> {
-        let block_idx = allocation.memory_block_index;
-
-        let mem_block = self.memory_blocks[block_idx]
-            .as_mut()
-            .ok_or_else(|| AllocationError::Internal("Memory block must be Some.".into()))?;
-
-        mem_block.sub_allocator.free(allocation.chunk_id)?;
-
-        // We only want to destroy this now-empty block if it is either a dedicated/personal
-        // allocation, or a block supporting sub-allocations that is not the last one (ensuring
-        // there's always at least one block/allocator readily available).
-        let is_dedicated_or_not_last_general_block =
-            !mem_block.sub_allocator.supports_general_allocations()
-                || self.active_general_blocks > 1;
-        if mem_block.sub_allocator.is_empty() && is_dedicated_or_not_last_general_block {
-            let block = self.memory_blocks[block_idx]
-                .take()
-                .ok_or_else(|| AllocationError::Internal("Memory block must be Some.".into()))?;
-
-            if block.sub_allocator.supports_general_allocations() {
-                self.active_general_blocks -= 1;
-            }
+    fn create_memory(
+        &self,
+        properties: &MemoryTypeProperties,
+        _memory_type_index: usize,
+        size: u64,
+        _dedicated_resource: Option<Self::DedicatedResource>,
+        _priority: Option<f32>,
+    ) -> Result<ID3D12Heap> {
+        let mut desc = D3D12_HEAP_DESC {
+            SizeInBytes: size,
+            Properties: properties.heap_properties,
+            Alignment: D3D12_DEFAULT_MSAA_RESOURCE_PLACEMENT_ALIGNMENT as u64,
+            ..Default::default()
+        };
+        desc.Flags = match properties.heap_category {
+            HeapCategory::All => D3D12_HEAP_FLAG_NONE,
+            HeapCategory::Buffer => D3D12_HEAP_FLAG_ALLOW_ONLY_BUFFERS,
+            HeapCategory::RtvDsvTexture => D3D12_HEAP_FLAG_ALLOW_ONLY_RT_DS_TEXTURES,
+            HeapCategory::OtherTexture => D3D12_HEAP_FLAG_ALLOW_ONLY_NON_RT_DS_TEXTURES,
+        };
 
-            // Note that `block` will be destroyed on `drop` here
+        let mut heap = None;
+        let hr = unsafe { self.device.CreateHeap(&desc, &mut heap) };
+        match hr {
+            Err(e) if e.code() == E_OUTOFMEMORY => Err(AllocationError::OutOfMemory),
+            Err(e) => Err(AllocationError::Internal(format!(
+                "ID3D12Device::CreateHeap failed: {e}"
+            ))),
+            Ok(()) => heap.ok_or_else(|| {
+                AllocationError::Internal("ID3D12Heap pointer is null, but should not be.".into())
+            }),
         }
+    }
 
-        Ok(())
+    fn destroy_memory(&self, _heap: ID3D12Heap) {
+        // Note that the heap will be destroyed on `drop` here
     }
 }
 
 pub struct Allocator {
-    device: ID3D12DeviceVersion,
-    debug_settings: AllocatorDebugSettings,
-    memory_types: Vec<MemoryType>,
-    allocation_sizes: AllocationSizes,
+    inner: GenericAllocator<D3D12Backend>,
+    /// Statistics of the committed resources created through [`Allocator::create_resource()`],
+    /// per memory type.
+    committed_allocations: Vec<CommittedAllocationStatistics>,
 }
 
 impl Allocator {
     pub fn device(&self) -> &ID3D12DeviceVersion {
-        &self.device
+        &self.inner.backend.device
     }
 
     pub fn new(desc: &AllocatorCreateDesc) -> Result<Self> {
@@ -590,30 +500,83 @@ impl Allocator {
                 .collect::<Vec<_>>()
         };
 
-        let memory_types = heap_types
+        let committed_allocations = heap_types
             .iter()
-            .enumerate()
-            .map(
-                |(i, &(heap_category, memory_location, heap_properties))| MemoryType {
-                    memory_blocks: Vec::default(),
-                    memory_location,
-                    heap_category,
-                    heap_properties,
-                    memory_type_index: i,
-                    active_general_blocks: 0,
-                    committed_allocations: CommittedAllocationStatistics {
-                        num_allocations: 0,
-                        total_size: 0,
+            .map(|_| CommittedAllocationStatistics {
+                num_allocations: 0,
+                total_size: 0,
+            })
+            .collect();
+
+        let memory_types =
+            heap_types
+                .into_iter()
+                .map(
+                    |(heap_category, memory_location, heap_properties)| MemoryTypeProperties {
+                        memory_location,
+                        heap_category,
+                        heap_properties,
                     },
-                },
-            )
-            .collect::<Vec<_>>();
+                );
+
+        // D3D12 doesn't expose the sizes of its memory heaps to check memory block sizes against.
+        let allocation_sizes = desc.allocation_sizes.resolve(u64::MAX)?;
 
         Ok(Self {
-            memory_types,
-            device,
-            debug_settings: desc.debug_settings,
-            allocation_sizes: desc.allocation_sizes,
+            inner: GenericAllocator::new(
+                D3D12Backend { device },
+                memory_types,
+                &[],
+                desc.debug_settings,
+                &allocation_sizes,
+                desc.sub_allocator,
+                desc.allocation_strategy,
+                desc.empty_block_retention,
+            ),
+            committed_allocations,
+        })
+    }
+
+    /// Finds the memory type for resources of `resource_category` in `location`.
+    fn find_memory_type_index(
+        &self,
+        location: MemoryLocation,
+        resource_category: ResourceCategory,
+    ) -> Result<usize> {
+        // D3D12 has no lazily allocated memory, transient allocations use the default heap type.
+        let location = match location {
+            MemoryLocation::Transient => MemoryLocation::GpuOnly,
+            location => location,
+        };
+        self.inner
+            .memory_types
+            .iter()
+            .find(|memory_type| {
+                let is_location_compatible = location == MemoryLocation::Unknown
+                    || location == memory_type.properties.memory_location;
+
+                let is_category_compatible = memory_type.properties.heap_category
+                    == HeapCategory::All
+                    || memory_type.properties.heap_category == resource_category.into();
+
+                is_location_compatible && is_category_compatible
+            })
+            .map(|memory_type| memory_type.memory_type_index)
+            .ok_or(AllocationError::NoCompatibleMemoryTypeFound)
+    }
+
+    /// Creates the [`Allocation`] handle for memory allocated through the [`GenericAllocator`].
+    fn new_allocation(&self, allocation: SubAllocation, name: &str) -> Result<Allocation> {
+        let mem_block = self.inner.memory_block(&allocation)?;
+
+        Ok(Allocation {
+            chunk_id: Some(allocation.chunk_id),
+            offset: allocation.offset,
+            size: allocation.size,
+            memory_block_index: allocation.memory_block_index,
+            memory_type_index: allocation.memory_type_index,
+            heap: mem_block.memory.clone(),
+            name: Some(name.into()),
         })
     }
 
@@ -622,72 +585,269 @@ impl Allocator {
         let alignment = desc.alignment;
 
         #[cfg(feature = "std")]
-        let backtrace = Arc::new(if self.debug_settings.store_stack_traces {
-            Backtrace::force_capture()
-        } else {
-            Backtrace::disabled()
-        });
-
-        if self.debug_settings.log_allocations {
-            debug!(
-                "Allocating `{}` of {} bytes with an alignment of {}.",
-                &desc.name, size, alignment
-            );
-            #[cfg(feature = "std")]
-            if self.debug_settings.log_stack_traces {
-                let backtrace = Backtrace::force_capture();
-                debug!("Allocation stack trace: {backtrace}");
-            }
-        }
+        let backtrace = self.inner.capture_backtrace();
+
+        self.inner.log_allocation(desc.name, size, alignment);
 
         if size == 0 || !alignment.is_power_of_two() {
             return Err(AllocationError::InvalidAllocationCreateDesc);
         }
 
-        // Find memory type
-        let memory_type = self
-            .memory_types
-            .iter_mut()
-            .find(|memory_type| {
-                let is_location_compatible = desc.location == MemoryLocation::Unknown
-                    || desc.location == memory_type.memory_location;
+        let request = AllocationRequest {
+            name: desc.name,
+            size,
+            alignment,
+            allocation_type: AllocationType::Linear,
+            granularity: 1,
+            strategy: desc.allocation_strategy,
+            location: desc.location,
+            priority: None,
+            #[cfg(feature = "std")]
+            backtrace,
+        };
 
-                let is_category_compatible = memory_type.heap_category == HeapCategory::All
-                    || memory_type.heap_category == desc.resource_category.into();
+        if let Some(pool) = desc.pool {
+            let heap_category = self.inner.memory_types[pool.memory_type_index]
+                .properties
+                .heap_category;
+            if heap_category != HeapCategory::All && heap_category != desc.resource_category.into()
+            {
+                return Err(AllocationError::NoCompatibleMemoryTypeFound);
+            }
 
-                is_location_compatible && is_category_compatible
-            })
-            .ok_or(AllocationError::NoCompatibleMemoryTypeFound)?;
+            let allocation =
+                self.inner
+                    .allocate_from_pool(pool.memory_type_index, pool.pool_index, &request)?;
+            return self.new_allocation(allocation, desc.name);
+        }
 
-        memory_type.allocate(
-            &self.device,
-            desc,
-            #[cfg(feature = "std")]
-            backtrace,
-            &self.allocation_sizes,
-        )
+        // Find memory type
+        let memory_type_index =
+            self.find_memory_type_index(desc.location, desc.resource_category)?;
+
+        let allocation = self.inner.allocate(memory_type_index, &request, None)?;
+
+        self.new_allocation(allocation, desc.name)
     }
 
+    #[allow(clippy::needless_pass_by_value)]
     pub fn free(&mut self, allocation: Allocation) -> Result<()> {
-        if self.debug_settings.log_frees {
-            let name = allocation.name.as_deref().unwrap_or("<null>");
-            debug!("Freeing `{name}`.");
-            #[cfg(feature = "std")]
-            if self.debug_settings.log_stack_traces {
-                let backtrace = Backtrace::force_capture();
-                debug!("Free stack trace: {backtrace}");
-            }
-        }
+        self.inner
+            .debug_settings
+            .log_free(allocation.name.as_deref());
 
         if allocation.is_null() {
             return Ok(());
         }
 
-        self.memory_types[allocation.memory_type_index].free(allocation)?;
+        self.inner.free(
+            allocation.memory_type_index,
+            allocation.memory_block_index,
+            allocation.chunk_id,
+        )?;
 
         Ok(())
     }
 
+    /// Sets the algorithm used to place allocations inside the shared heaps of all memory types
+    /// for `location`, overriding [`AllocatorCreateDesc::sub_allocator`] for them.
+    ///
+    /// Only affects heaps that are created after this call.
+    pub fn set_memory_location_sub_allocator(
+        &mut self,
+        location: MemoryLocation,
+        sub_allocator: SubAllocatorKind,
+    ) {
+        for memory_type in &mut self.inner.memory_types {
+            if memory_type.properties.memory_location == location {
+                memory_type.sub_allocator = sub_allocator;
+            }
+        }
+    }
+
+    /// Creates a [`LinearPool`] holding a single heap of [`LinearPoolCreateDesc::size`] bytes, in
+    /// a memory type compatible with the given location and resource category.
+    pub fn create_linear_pool(&mut self, desc: &LinearPoolCreateDesc) -> Result<LinearPool> {
+        if desc.size == 0 {
+            return Err(AllocationError::InvalidAllocationCreateDesc);
+        }
+
+        let memory_type_index =
+            self.find_memory_type_index(desc.location, desc.resource_category)?;
+        let memory_block_index = self
+            .inner
+            .create_linear_pool(memory_type_index, desc.size)?;
+
+        Ok(LinearPool {
+            memory_type_index,
+            memory_block_index,
+        })
+    }
+
+    /// Creates a [`Pool`] in a memory type compatible with the given location and resource
+    /// category, along with its [`PoolCreateDesc::min_block_count`] heaps.
+    pub fn create_pool(&mut self, desc: &PoolCreateDesc) -> Result<Pool> {
+        let pool = CustomPool::new(
+            desc.block_size,
+            desc.min_block_count,
+            desc.max_block_count,
+            desc.sub_allocator,
+            desc.allocation_strategy,
+            None,
+        )?;
+        let memory_type_index =
+            self.find_memory_type_index(desc.location, desc.resource_category)?;
+        let pool_index = self.inner.create_pool(memory_type_index, pool)?;
+
+        Ok(Pool {
+            memory_type_index,
+            pool_index,
+        })
+    }
+
+    /// Destroys all heaps of a [`Pool`].
+    ///
+    /// All allocations made from the pool must have been freed. Otherwise this fails with
+    /// [`AllocationError::Internal`] and destroys nothing, and the heaps of the pool are only
+    /// destroyed together with the allocator.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn destroy_pool(&mut self, pool: Pool) -> Result<()> {
+        self.inner
+            .destroy_pool(pool.memory_type_index, pool.pool_index)
+    }
+
+    /// Destroys the heap of a [`LinearPool`].
+    ///
+    /// Any allocation that is still live in the pool becomes invalid, and must neither be used
+    /// nor freed afterwards.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn destroy_linear_pool(&mut self, pool: LinearPool) -> Result<()> {
+        self.inner
+            .destroy_linear_pool(pool.memory_type_index, pool.memory_block_index)
+    }
+
+    /// Allocates from a [`LinearPool`] instead of the shared heaps.
+    ///
+    /// [`AllocationCreateDesc::location`] is ignored in favor of the location of the pool.
+    /// Returns [`AllocationError::OutOfMemory`] when the pool is full, no other heaps are created
+    /// to make room.
+    ///
+    /// The allocation may be freed through [`Allocator::free()`] like any other, or be released
+    /// together with the rest of its frame through [`Allocator::free_linear_pool_frames_before()`].
+    pub fn allocate_from_linear_pool(
+        &mut self,
+        pool: &LinearPool,
+        desc: &AllocationCreateDesc<'_>,
+    ) -> Result<Allocation> {
+        let size = desc.size;
+        let alignment = desc.alignment;
+
+        #[cfg(feature = "std")]
+        let backtrace = self.inner.capture_backtrace();
+
+        self.inner.log_allocation(desc.name, size, alignment);
+
+        if size == 0 || !alignment.is_power_of_two() {
+            return Err(AllocationError::InvalidAllocationCreateDesc);
+        }
+
+        let heap_category = self.inner.memory_types[pool.memory_type_index]
+            .properties
+            .heap_category;
+        if heap_category != HeapCategory::All && heap_category != desc.resource_category.into() {
+            return Err(AllocationError::NoCompatibleMemoryTypeFound);
+        }
+
+        let request = AllocationRequest {
+            name: desc.name,
+            size,
+            alignment,
+            allocation_type: AllocationType::Linear,
+            granularity: 1,
+            strategy: desc.allocation_strategy,
+            location: desc.location,
+            priority: None,
+            #[cfg(feature = "std")]
+            backtrace,
+        };
+        let allocation = self.inner.allocate_from_linear_pool(
+            pool.memory_type_index,
+            pool.memory_block_index,
+            &request,
+        )?;
+
+        self.new_allocation(allocation, desc.name)
+    }
+
+    /// Tags all allocations made from `pool` from now on with `frame`.
+    ///
+    /// Frame numbers are expected to increase monotonically.
+    pub fn begin_linear_pool_frame(&mut self, pool: &LinearPool, frame: u64) -> Result<()> {
+        self.inner
+            .begin_linear_pool_frame(pool.memory_type_index, pool.memory_block_index, frame)
+    }
+
+    /// Frees all allocations of `pool` that were made before `frame` was begun, typically
+    /// because the GPU has finished processing those frames.
+    ///
+    /// The released [`Allocation`]s become invalid and must no longer be used. Passing them to
+    /// [`Allocator::free()`] afterwards is allowed and does nothing.
+    pub fn free_linear_pool_frames_before(&mut self, pool: &LinearPool, frame: u64) -> Result<()> {
+        self.inner.free_linear_pool_frames_before(
+            pool.memory_type_index,
+            pool.memory_block_index,
+            frame,
+        )
+    }
+
+    /// Plans moving allocations out of the least used shared memory blocks into other memory
+    /// blocks of the same memory type, so that those memory blocks become empty and are freed
+    /// once the plan is committed.
+    ///
+    /// Memory blocks are only emptied as a whole, and only as long as the total size of the
+    /// moved allocations stays within `max_bytes_to_move`. Room for every move is reserved right
+    /// away: copy the data of each moved allocation to [`DefragmentationMove::destination()`],
+    /// recreate or rebind the resources placed on it, and then pass the plan to
+    /// [`Allocator::commit_defragmentation()`], or give up with
+    /// [`Allocator::cancel_defragmentation()`]. Allocations that are part of the plan must not be
+    /// freed before then, and dropping the plan keeps the reserved room allocated until the
+    /// allocator is dropped.
+    pub fn plan_defragmentation(
+        &mut self,
+        max_bytes_to_move: u64,
+    ) -> Result<DefragmentationPlan<Allocation>> {
+        let moves = self
+            .inner
+            .plan_defragmentation(max_bytes_to_move, 1)?
+            .into_iter()
+            .map(|planned| {
+                let destination = self.new_allocation(planned.destination, &planned.name)?;
+                Ok(planned.with_destination(destination))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(DefragmentationPlan { moves })
+    }
+
+    /// Finishes `plan` once the data has been copied: every allocation in `allocations` that is
+    /// moved by the plan is freed and replaced with the destination of its move, which may empty
+    /// and free whole memory blocks.
+    ///
+    /// The moves of allocations that are not passed in are cancelled.
+    pub fn commit_defragmentation<'a>(
+        &mut self,
+        plan: DefragmentationPlan<Allocation>,
+        allocations: impl IntoIterator<Item = &'a mut Allocation>,
+    ) -> Result<()> {
+        self.inner
+            .commit_defragmentation(plan, allocations, Allocation::key)
+    }
+
+    /// Releases the room reserved by `plan`, leaving all allocations where they are.
+    pub fn cancel_defragmentation(&mut self, plan: DefragmentationPlan<Allocation>) -> Result<()> {
+        self.inner.cancel_defragmentation(plan)
+    }
+
     pub fn rename_allocation(&mut self, allocation: &mut Allocation, name: &str) -> Result<()> {
         allocation.name = Some(name.into());
 
@@ -695,28 +855,16 @@ impl Allocator {
             return Ok(());
         }
 
-        let mem_type = &mut self.memory_types[allocation.memory_type_index];
-        let mem_block = mem_type.memory_blocks[allocation.memory_block_index]
-            .as_mut()
-            .ok_or_else(|| AllocationError::Internal("Memory block must be Some.".into()))?;
-
-        mem_block
-            .sub_allocator
-            .rename_allocation(allocation.chunk_id, name)?;
-
-        Ok(())
+        self.inner.rename_allocation(
+            allocation.memory_type_index,
+            allocation.memory_block_index,
+            allocation.chunk_id,
+            name,
+        )
     }
 
     pub fn report_memory_leaks(&self, log_level: Level) {
-        for (mem_type_i, mem_type) in self.memory_types.iter().enumerate() {
-            for (block_i, mem_block) in mem_type.memory_blocks.iter().enumerate() {
-                if let Some(mem_block) = mem_block {
-                    mem_block
-                        .sub_allocator
-                        .report_memory_leaks(log_level, mem_type_i, block_i);
-                }
-            }
-        }
+        self.inner.report_memory_leaks(log_level);
     }
 
     fn d3d12_resource_desc_1(desc: &D3D12_RESOURCE_DESC) -> D3D12_RESOURCE_DESC1 {
@@ -793,7 +941,7 @@ impl Allocator {
                     desc.clear_value.map(|v| -> *const _ { v });
 
                 if let Err(e) = unsafe {
-                    match (&self.device, desc.initial_state_or_layout) {
+                    match (self.device(), desc.initial_state_or_layout) {
                         (_, ResourceStateOrBarrierLayout::ResourceState(_))
                             if !desc.castable_formats.is_empty() =>
                         {
@@ -855,7 +1003,7 @@ impl Allocator {
                     if e.code() == DXGI_ERROR_DEVICE_REMOVED {
                         return Err(AllocationError::Internal(format!(
                             "ID3D12Device::CreateCommittedResource DEVICE_REMOVED: {:?}",
-                            unsafe { self.device.GetDeviceRemovedReason() }
+                            unsafe { self.device().GetDeviceRemovedReason() }
                         )));
                     }
                     return Err(AllocationError::Internal(format!(
@@ -865,25 +1013,14 @@ impl Allocator {
 
                 let resource = result.expect("Allocation succeeded but no resource was returned?");
 
-                let allocation_info = Self::resource_allocation_info(&self.device, desc);
-
-                let memory_type = self
-                    .memory_types
-                    .iter_mut()
-                    .find(|memory_type| {
-                        let is_location_compatible = desc.memory_location
-                            == MemoryLocation::Unknown
-                            || desc.memory_location == memory_type.memory_location;
+                let allocation_info = Self::resource_allocation_info(self.device(), desc);
 
-                        let is_category_compatible = memory_type.heap_category == HeapCategory::All
-                            || memory_type.heap_category == desc.resource_category.into();
+                let memory_type_index =
+                    self.find_memory_type_index(desc.memory_location, desc.resource_category)?;
 
-                        is_location_compatible && is_category_compatible
-                    })
-                    .ok_or(AllocationError::NoCompatibleMemoryTypeFound)?;
-
-                memory_type.committed_allocations.num_allocations += 1;
-                memory_type.committed_allocations.total_size += allocation_info.SizeInBytes;
+                let committed_allocations = &mut self.committed_allocations[memory_type_index];
+                committed_allocations.num_allocations += 1;
+                committed_allocations.total_size += allocation_info.SizeInBytes;
 
                 Ok(Resource {
                     name: desc.name.into(),
@@ -891,12 +1028,12 @@ impl Allocator {
                     resource: Some(resource),
                     size: allocation_info.SizeInBytes,
                     memory_location: desc.memory_location,
-                    memory_type_index: Some(memory_type.memory_type_index),
+                    memory_type_index: Some(memory_type_index),
                 })
             }
             ResourceType::Placed => {
                 let allocation_desc = {
-                    let allocation_info = Self::resource_allocation_info(&self.device, desc);
+                    let allocation_info = Self::resource_allocation_info(self.device(), desc);
 
                     AllocationCreateDesc {
                         name: desc.name,
@@ -904,6 +1041,8 @@ impl Allocator {
                         size: allocation_info.SizeInBytes,
                         alignment: allocation_info.Alignment,
                         resource_category: desc.resource_category,
+                        allocation_strategy: None,
+                        pool: None,
                     }
                 };
 
@@ -911,7 +1050,7 @@ impl Allocator {
 
                 let mut result: Option<ID3D12Resource> = None;
                 if let Err(e) = unsafe {
-                    match (&self.device, desc.initial_state_or_layout) {
+                    match (self.device(), desc.initial_state_or_layout) {
                         (_, ResourceStateOrBarrierLayout::ResourceState(_))
                             if !desc.castable_formats.is_empty() =>
                         {
@@ -970,7 +1109,7 @@ impl Allocator {
                     if e.code() == DXGI_ERROR_DEVICE_REMOVED {
                         return Err(AllocationError::Internal(format!(
                             "ID3D12Device::CreatePlacedResource DEVICE_REMOVED: {:?}",
-                            unsafe { self.device.GetDeviceRemovedReason() }
+                            unsafe { self.device().GetDeviceRemovedReason() }
                         )));
                     }
                     return Err(AllocationError::Internal(format!(
@@ -1007,53 +1146,36 @@ impl Allocator {
             // Dx12 CommittedResources do not have an application managed allocation.
             // We only have to update the tracked allocation count and memory usage.
             if let Some(memory_type_index) = resource.memory_type_index {
-                let memory_type = &mut self.memory_types[memory_type_index];
+                let committed_allocations = &mut self.committed_allocations[memory_type_index];
 
-                memory_type.committed_allocations.num_allocations -= 1;
-                memory_type.committed_allocations.total_size -= resource.size;
+                committed_allocations.num_allocations -= 1;
+                committed_allocations.total_size -= resource.size;
             }
             Ok(())
         }
     }
 
     pub fn generate_report(&self) -> AllocatorReport {
-        let mut allocations = vec![];
-        let mut blocks = vec![];
-        let mut total_capacity_bytes = 0;
-
-        for memory_type in &self.memory_types {
-            for block in memory_type.memory_blocks.iter().flatten() {
-                total_capacity_bytes += block.size;
-                let first_allocation = allocations.len();
-                allocations.extend(block.sub_allocator.report_allocations());
-                blocks.push(MemoryBlockReport {
-                    size: block.size,
-                    allocations: first_allocation..allocations.len(),
-                });
-            }
-        }
-
-        let total_allocated_bytes = allocations.iter().map(|report| report.size).sum();
-
-        AllocatorReport {
-            allocations,
-            blocks,
-            total_allocated_bytes,
-            total_capacity_bytes,
-        }
+        self.inner.generate_report()
     }
 
     /// Current total capacity of memory blocks allocated on the device, in bytes
     pub fn capacity(&self) -> u64 {
-        let mut total_capacity_bytes = 0;
+        self.inner.capacity()
+    }
 
-        for memory_type in &self.memory_types {
-            for block in memory_type.memory_blocks.iter().flatten() {
-                total_capacity_bytes += block.size;
-            }
-        }
+    /// Destroys all shared memory blocks that don't hold any allocation, including the last one of
+    /// every memory type, to give memory back during idle periods. Memory blocks of pools are
+    /// unaffected.
+    pub fn trim(&mut self) {
+        self.inner.trim();
+    }
 
-        total_capacity_bytes
+    /// Advances the frame counter of [`EmptyBlockRetention::Frames`] to `frame`, destroying the
+    /// empty shared memory blocks that have been kept around for long enough. `frame` must never
+    /// go down.
+    pub fn set_current_frame(&mut self, frame: u64) {
+        self.inner.set_current_frame(frame);
     }
 }
 
@@ -1062,18 +1184,3 @@ impl fmt::Debug for Allocator {
         self.generate_report().fmt(f)
     }
 }
-
-impl Drop for Allocator {
-    fn drop(&mut self) {
-        if self.debug_settings.log_leaks_on_shutdown {
-            self.report_memory_leaks(Level::Warn);
-        }
-
-        // Because Rust drop rules drop members in source-code order (that would be the
-        // ID3D12Device before the ID3D12Heaps nested in these memory blocks), free
-        // all remaining memory blocks manually first by dropping.
-        for mem_type in self.memory_types.iter_mut() {
-            mem_type.memory_blocks.clear();
-        }
-    }
-}
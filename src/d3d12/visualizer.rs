@@ -5,7 +5,7 @@ use windows::Win32::Graphics::Direct3D12::*;
 use super::Allocator;
 use crate::visualizer::{
     render_allocation_reports_ui, AllocationReportVisualizeSettings, ColorScheme,
-    MemoryChunksVisualizationSettings,
+    MemoryChunksVisualizationSettings, SubAllocatorVisualizer,
 };
 
 struct AllocatorVisualizerBlockWindow {
@@ -78,9 +78,9 @@ impl AllocatorVisualizer {
 
     pub fn render_memory_block_ui(&mut self, ui: &mut egui::Ui, alloc: &Allocator) {
         ui.collapsing(
-            format!("Memory Types: ({} types)", alloc.memory_types.len()),
+            format!("Memory Types: ({} types)", alloc.inner.memory_types.len()),
             |ui| {
-                for (mem_type_idx, mem_type) in alloc.memory_types.iter().enumerate() {
+                for (mem_type_idx, mem_type) in alloc.inner.memory_types.iter().enumerate() {
                     ui.collapsing(
                         format!(
                             "Type: {} ({} blocks)",
@@ -102,31 +102,38 @@ impl AllocatorVisualizer {
                                 .filter(|block| block.is_some())
                                 .count();
 
-                            ui.label(format!("heap category: {:?}", mem_type.heap_category));
+                            ui.label(format!(
+                                "heap category: {:?}",
+                                mem_type.properties.heap_category
+                            ));
                             ui.label(format!(
                                 "Heap Type: {} ({})",
-                                format_heap_type(mem_type.heap_properties.Type),
-                                mem_type.heap_properties.Type.0
+                                format_heap_type(mem_type.properties.heap_properties.Type),
+                                mem_type.properties.heap_properties.Type.0
                             ));
                             ui.label(format!(
                                 "CpuPageProperty: {} ({})",
-                                format_cpu_page_property(mem_type.heap_properties.CPUPageProperty),
-                                mem_type.heap_properties.CPUPageProperty.0
+                                format_cpu_page_property(
+                                    mem_type.properties.heap_properties.CPUPageProperty
+                                ),
+                                mem_type.properties.heap_properties.CPUPageProperty.0
                             ));
                             ui.label(format!(
                                 "MemoryPoolPreference: {} ({})",
-                                format_memory_pool(mem_type.heap_properties.MemoryPoolPreference),
-                                mem_type.heap_properties.MemoryPoolPreference.0
+                                format_memory_pool(
+                                    mem_type.properties.heap_properties.MemoryPoolPreference
+                                ),
+                                mem_type.properties.heap_properties.MemoryPoolPreference.0
                             ));
                             ui.label(format!("total block size: {} KiB", total_block_size / 1024));
                             ui.label(format!("total allocated:  {} KiB", total_allocated / 1024));
                             ui.label(format!(
                                 "committed resource allocations: {}",
-                                mem_type.committed_allocations.num_allocations
+                                alloc.committed_allocations[mem_type_idx].num_allocations
                             ));
                             ui.label(format!(
                                 "total committed resource allocations: {} KiB",
-                                mem_type.committed_allocations.total_size
+                                alloc.committed_allocations[mem_type_idx].total_size
                             ));
                             ui.label(format!("block count: {active_block_count}"));
 
@@ -139,7 +146,7 @@ impl AllocatorVisualizer {
                                         "allocated: {} KiB",
                                         block.sub_allocator.allocated() / 1024
                                     ));
-                                    ui.label(format!("D3D12 heap: {:?}", block.heap));
+                                    ui.label(format!("D3D12 heap: {:?}", block.memory));
                                     block.sub_allocator.draw_base_info(ui);
 
                                     if block.sub_allocator.supports_visualization()
@@ -194,8 +201,8 @@ impl AllocatorVisualizer {
             .default_size([1920.0 * 0.5, 1080.0 * 0.5])
             .open(&mut open)
             .show(ctx, |ui| {
-                let memblock = &allocator.memory_types[window.memory_type_index].memory_blocks
-                    [window.block_index]
+                let memblock = &allocator.inner.memory_types[window.memory_type_index]
+                    .memory_blocks[window.block_index]
                     .as_ref();
                 if let Some(memblock) = memblock {
                     ui.label(format!(
@@ -207,7 +214,7 @@ impl AllocatorVisualizer {
 
                     window
                         .settings
-                        .ui(ui, allocator.debug_settings.store_stack_traces);
+                        .ui(ui, allocator.inner.debug_settings.store_stack_traces);
 
                     ui.separator();
 
@@ -228,6 +235,7 @@ impl AllocatorVisualizer {
             ui,
             &mut self.breakdown_settings,
             allocator
+                .inner
                 .memory_types
                 .iter()
                 .flat_map(|memory_type| memory_type.memory_blocks.iter())
@@ -17,7 +17,15 @@
 //!     physical_device,
 //!     debug_settings: Default::default(),
 //!     buffer_device_address: true,  // Ideally, check the BufferDeviceAddressFeatures struct.
+//!     memory_budget: false,
+//!     get_memory_requirements2: false,
+//!     memory_priority: false,
+//!     pageable_device_local_memory: false,
+//!     heap_size_limits: vec![],
 //!     allocation_sizes: Default::default(),
+//!     sub_allocator: Default::default(),
+//!     allocation_strategy: Default::default(),
+//!     empty_block_retention: Default::default(),
 //! });
 //! # }
 //! # #[cfg(not(feature = "vulkan"))]
@@ -41,7 +49,15 @@
 //! #     physical_device,
 //! #     debug_settings: Default::default(),
 //! #     buffer_device_address: true,  // Ideally, check the BufferDeviceAddressFeatures struct.
+//! #     memory_budget: false,
+//! #     get_memory_requirements2: false,
+//! #     memory_priority: false,
+//! #     pageable_device_local_memory: false,
+//! #     heap_size_limits: vec![],
 //! #     allocation_sizes: Default::default(),
+//! #     sub_allocator: Default::default(),
+//! #     allocation_strategy: Default::default(),
+//! #     empty_block_retention: Default::default(),
 //! # }).unwrap();
 //!
 //! // Setup vulkan info
@@ -59,6 +75,10 @@
 //!         location: MemoryLocation::CpuToGpu,
 //!         linear: true, // Buffers are always linear
 //!         allocation_scheme: AllocationScheme::GpuAllocatorManaged,
+//!         allocation_strategy: None,
+//!         pool: None,
+//!         priority: None,
+//!         memory_type_preferences: Default::default(),
 //!     }).unwrap();
 //!
 //! // Bind memory to the buffer
@@ -72,6 +92,31 @@
 //! # fn main() {}
 //! ```
 //!
+//! `Allocator::create_buffer()` and `Allocator::create_image()` do all of the above in one call:
+//!
+//! ```no_run
+//! # #[cfg(feature = "vulkan")]
+//! # fn main() {
+//! use gpu_allocator::vulkan::*;
+//! use gpu_allocator::MemoryLocation;
+//! # use ash::vk;
+//! # let mut allocator: Allocator = todo!();
+//!
+//! let vk_info = vk::BufferCreateInfo::default()
+//!     .size(512)
+//!     .usage(vk::BufferUsageFlags::STORAGE_BUFFER);
+//!
+//! let buffer = allocator
+//!     .create_buffer("Example buffer", &vk_info, MemoryLocation::CpuToGpu)
+//!     .unwrap();
+//!
+//! // Cleanup
+//! allocator.destroy_buffer(buffer).unwrap();
+//! # }
+//! # #[cfg(not(feature = "vulkan"))]
+//! # fn main() {}
+//! ```
+//!
 //! # Setting up the D3D12 memory allocator
 //!
 //! ```no_run
@@ -84,6 +129,9 @@
 //!     device: ID3D12DeviceVersion::Device(device),
 //!     debug_settings: Default::default(),
 //!     allocation_sizes: Default::default(),
+//!     sub_allocator: Default::default(),
+//!     allocation_strategy: Default::default(),
+//!     empty_block_retention: Default::default(),
 //! });
 //! # }
 //! # #[cfg(not(feature = "d3d12"))]
@@ -104,6 +152,9 @@
 //! #     device: ID3D12DeviceVersion::Device(device),
 //! #     debug_settings: Default::default(),
 //! #     allocation_sizes: Default::default(),
+//! #     sub_allocator: Default::default(),
+//! #     allocation_strategy: Default::default(),
+//! #     empty_block_retention: Default::default(),
 //! # }).unwrap();
 //!
 //! let buffer_desc = Direct3D12::D3D12_RESOURCE_DESC {
@@ -160,6 +211,9 @@
 //!     device: device.clone(),
 //!     debug_settings: Default::default(),
 //!     allocation_sizes: Default::default(),
+//!     sub_allocator: Default::default(),
+//!     allocation_strategy: Default::default(),
+//!     empty_block_retention: Default::default(),
 //!     create_residency_set: false,
 //! });
 //! # }
@@ -179,6 +233,9 @@
 //! #     device: device.clone(),
 //! #     debug_settings: Default::default(),
 //! #     allocation_sizes: Default::default(),
+//! #     sub_allocator: Default::default(),
+//! #     allocation_strategy: Default::default(),
+//! #     empty_block_retention: Default::default(),
 //! #    create_residency_set: false,
 //! # })
 //! # .unwrap();
@@ -229,7 +286,15 @@ pub use result::*;
 
 pub(crate) mod allocator;
 
-pub use allocator::{AllocationReport, AllocatorReport, MemoryBlockReport};
+#[cfg(feature = "std")]
+pub use allocator::ThreadCacheCreateDesc;
+pub use allocator::{
+    AllocationContext, AllocationReport, AllocationStrategy, AllocationType, AllocatorReport,
+    DefragmentationMove, DefragmentationPlan, EmptyBlockRetention, MemoryBlockReport,
+    MovableAllocation, SubAllocator, SubAllocatorKind,
+};
+
+pub mod virtual_block;
 
 #[cfg(feature = "visualizer")]
 pub mod visualizer;
@@ -243,6 +308,9 @@ pub mod d3d12;
 #[cfg(all(target_vendor = "apple", feature = "metal"))]
 pub mod metal;
 
+#[cfg(feature = "mock")]
+pub mod mock;
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum MemoryLocation {
     /// The allocated resource is stored at an unknown memory location; let the driver decide what's the best location
@@ -254,6 +322,12 @@ pub enum MemoryLocation {
     CpuToGpu,
     /// Memory useful for CPU readback of data
     GpuToCpu,
+    /// Memory for transient attachments, like multisampled color and depth attachments that are
+    /// only used within a render pass. Uses lazily allocated memory on devices that have it, which
+    /// tile-based GPUs may never have to back with physical memory, and regular GPU only memory
+    /// otherwise. These allocations are never mapped, and get memory blocks of their own so they
+    /// don't fragment the memory of other allocations.
+    Transient,
 }
 
 #[non_exhaustive]
@@ -297,7 +371,11 @@ impl Default for AllocatorDebugSettings {
 /// Useful for tuning the allocator to your application's needs. For example most games will be fine with the default
 /// values, but eg. an app might want to use smaller block sizes to reduce the amount of memory used.
 ///
-/// Clamped between 4MB and 256MB, and rounds up to the nearest multiple of 4MB for alignment reasons.
+/// When the allocator is created, all sizes are clamped between 4MB and 256MB and rounded up to
+/// the nearest multiple of 4MB for alignment reasons. These bounds can be changed through
+/// [`AllocationSizes::with_memblock_size_bounds`] and
+/// [`AllocationSizes::with_memblock_size_granularity`], and the upper bound is further limited to
+/// the size of the largest memory heap of the device.
 ///
 /// Note that these limits only apply to shared memory blocks that can hold multiple allocations.
 /// If an allocation does not fit within the corresponding maximum block size, it will be placed
@@ -335,6 +413,42 @@ impl Default for AllocatorDebugSettings {
 /// let growing = AllocationSizes::new(8 * MB, 8 * MB)
 ///     .with_max_device_memblock_size(256 * MB)
 ///     .with_max_host_memblock_size(64 * MB);
+///
+/// // Memory blocks of 1MB to 1GB, in multiples of 1MB.
+/// let wide = AllocationSizes::new(MB, MB)
+///     .with_max_device_memblock_size(1024 * MB)
+///     .with_memblock_size_bounds(MB, 1024 * MB)
+///     .with_memblock_size_granularity(MB);
+/// ```
+///
+/// # Heap size aware sizing
+///
+/// A single block size rarely fits all devices: 256MB blocks are far too large for a 256MB
+/// Resizable BAR heap or for integrated GPUs with a small carve-out. With
+/// [`AllocationSizes::with_heap_size_divisor`], or [`AllocationSizes::automatic`], shared memory
+/// blocks are additionally limited to a fraction of the size of the heap they are created in.
+///
+/// ```
+/// use gpu_allocator::AllocationSizes;
+/// // The default sizes, but never more than an eighth of the heap.
+/// let automatic = AllocationSizes::automatic();
+/// ```
+///
+/// # Overrides
+///
+/// The split between device and host memory blocks doesn't fit every memory type, like the small
+/// device local and host visible heap of Resizable BAR and UMA systems. The range of block sizes can be
+/// overridden for allocations made for a [`MemoryLocation`], and for a specific memory type of
+/// the backend. Memory type overrides take precedence over location overrides, which take
+/// precedence over the device and host sizes.
+///
+/// ```
+/// use gpu_allocator::{AllocationSizes, MemoryLocation};
+/// const MB: u64 = 1024 * 1024;
+/// // Uploads go to small blocks, while memory type 0 uses large blocks.
+/// let sizes = AllocationSizes::default()
+///     .with_location_memblock_size(MemoryLocation::CpuToGpu, 16 * MB, 16 * MB)
+///     .with_memory_type_memblock_size(0, 64 * MB, 256 * MB);
 /// ```
 #[derive(Clone, Copy, Debug)]
 pub struct AllocationSizes {
@@ -360,6 +474,33 @@ pub struct AllocationSizes {
     ///
     /// Defaults to the value of [`AllocationSizes::min_host_memblock_size`].
     max_host_memblock_size: u64,
+    /// Block sizes for allocations made for a [`MemoryLocation`], indexed by
+    /// [`AllocationSizes::location_index()`].
+    location_memblock_sizes: [Option<MemblockSizeRange>; 5],
+    /// Block sizes for the memory types of the backend, indexed by memory type index.
+    memory_type_memblock_sizes: [Option<MemblockSizeRange>; MAX_MEMORY_TYPES],
+    /// The bounds that all other sizes are clamped to.
+    ///
+    /// Defaults to 4MB and 256MB.
+    memblock_size_bounds: MemblockSizeRange,
+    /// The multiple that all other sizes are rounded up to.
+    ///
+    /// Defaults to 4MB.
+    memblock_size_granularity: u64,
+    /// Limits memory blocks to the size of their heap divided by this, if set.
+    ///
+    /// Defaults to [`None`].
+    heap_size_divisor: Option<u64>,
+}
+
+/// The most memory types any backend has, which is the Vulkan limit.
+const MAX_MEMORY_TYPES: usize = 32;
+
+/// A range of memory block sizes overriding the device and host sizes of [`AllocationSizes`].
+#[derive(Clone, Copy, Debug)]
+struct MemblockSizeRange {
+    min: u64,
+    max: u64,
 }
 
 impl AllocationSizes {
@@ -369,47 +510,206 @@ impl AllocationSizes {
     /// can be increased using [`AllocationSizes::with_max_device_memblock_size`] and
     /// [`AllocationSizes::with_max_host_memblock_size`].
     pub fn new(device_memblock_size: u64, host_memblock_size: u64) -> Self {
-        let device_memblock_size = Self::adjust_memblock_size(device_memblock_size, "Device");
-        let host_memblock_size = Self::adjust_memblock_size(host_memblock_size, "Host");
-
         Self {
             min_device_memblock_size: device_memblock_size,
             max_device_memblock_size: device_memblock_size,
             min_host_memblock_size: host_memblock_size,
             max_host_memblock_size: host_memblock_size,
+            ..Self::default()
         }
     }
 
+    /// The default sizes, with memory blocks limited to an eighth of the size of their heap like
+    /// VMA does, so that the same configuration works on devices with very different heap sizes.
+    pub fn automatic() -> Self {
+        Self::default().with_heap_size_divisor(8)
+    }
+
     /// Sets the maximum device memblock size, in bytes.
     pub fn with_max_device_memblock_size(mut self, size: u64) -> Self {
-        self.max_device_memblock_size =
-            Self::adjust_memblock_size(size, "Device").max(self.min_device_memblock_size);
+        self.max_device_memblock_size = size.max(self.min_device_memblock_size);
 
         self
     }
 
     /// Sets the maximum host memblock size, in bytes.
     pub fn with_max_host_memblock_size(mut self, size: u64) -> Self {
-        self.max_host_memblock_size =
-            Self::adjust_memblock_size(size, "Host").max(self.min_host_memblock_size);
+        self.max_host_memblock_size = size.max(self.min_host_memblock_size);
 
         self
     }
 
-    fn adjust_memblock_size(size: u64, kind: &str) -> u64 {
-        const MB: u64 = 1024 * 1024;
+    /// Sets the minimum and maximum memory block size, in bytes, for allocations made for
+    /// `location`, overriding the device and host sizes.
+    pub fn with_location_memblock_size(
+        mut self,
+        location: MemoryLocation,
+        min_size: u64,
+        max_size: u64,
+    ) -> Self {
+        self.location_memblock_sizes[Self::location_index(location)] = Some(MemblockSizeRange {
+            min: min_size,
+            max: max_size.max(min_size),
+        });
+
+        self
+    }
+
+    /// Sets the minimum and maximum memory block size, in bytes, for the memory type at
+    /// `memory_type_index` of the backend, overriding both the location overrides and the device
+    /// and host sizes.
+    ///
+    /// Indices of 32 and up are ignored, as no backend has that many memory types.
+    pub fn with_memory_type_memblock_size(
+        mut self,
+        memory_type_index: usize,
+        min_size: u64,
+        max_size: u64,
+    ) -> Self {
+        let range = MemblockSizeRange {
+            min: min_size,
+            max: max_size.max(min_size),
+        };
+        match self.memory_type_memblock_sizes.get_mut(memory_type_index) {
+            Some(sizes) => *sizes = Some(range),
+            None => log::warn!("Memory type index {memory_type_index} is out of range, ignoring its memory block size"),
+        }
+
+        self
+    }
+
+    /// Sets the smallest and largest memory block size, in bytes, that all other sizes are
+    /// clamped to. Both must be multiples of
+    /// [`AllocationSizes::with_memblock_size_granularity`].
+    ///
+    /// The largest size is further limited to the size of the largest memory heap of the device.
+    pub fn with_memblock_size_bounds(mut self, min_size: u64, max_size: u64) -> Self {
+        self.memblock_size_bounds = MemblockSizeRange {
+            min: min_size,
+            max: max_size,
+        };
+
+        self
+    }
+
+    /// Sets the multiple, in bytes, that all memory block sizes are rounded up to.
+    pub fn with_memblock_size_granularity(mut self, granularity: u64) -> Self {
+        self.memblock_size_granularity = granularity;
+
+        self
+    }
+
+    /// Limits memory blocks to the size of the heap they are created in divided by `divisor`,
+    /// rounded down to the granularity but no smaller than the lower bound of
+    /// [`AllocationSizes::with_memblock_size_bounds`]. A size limit on the heap is used instead of
+    /// its size when it is smaller.
+    pub fn with_heap_size_divisor(mut self, divisor: u64) -> Self {
+        self.heap_size_divisor = Some(divisor);
+
+        self
+    }
 
-        let size = size.clamp(4 * MB, 256 * MB);
+    fn location_index(location: MemoryLocation) -> usize {
+        match location {
+            MemoryLocation::Unknown => 0,
+            MemoryLocation::GpuOnly => 1,
+            MemoryLocation::CpuToGpu => 2,
+            MemoryLocation::GpuToCpu => 3,
+            MemoryLocation::Transient => 4,
+        }
+    }
+
+    /// Checks the bounds and granularity against each other and against the largest memory block
+    /// the device can hold, and returns these sizes with every size clamped to the bounds and
+    /// rounded up to the granularity.
+    pub(crate) fn resolve(&self, device_max_memblock_size: u64) -> Result<Self> {
+        let granularity = self.memblock_size_granularity;
+        let bounds = self.memblock_size_bounds;
+        if granularity == 0
+            || bounds.min == 0
+            || bounds.min > bounds.max
+            || bounds.min % granularity != 0
+            || bounds.max % granularity != 0
+        {
+            return Err(AllocationError::InvalidAllocatorCreateDesc(
+                "Memory block size bounds must be non-zero multiples of the granularity".into(),
+            ));
+        }
+        if self.heap_size_divisor == Some(0) {
+            return Err(AllocationError::InvalidAllocatorCreateDesc(
+                "Heap size divisor must not be zero".into(),
+            ));
+        }
 
-        if size % (4 * MB) == 0 {
+        let max = bounds
+            .max
+            .min(device_max_memblock_size / granularity * granularity);
+        if max < bounds.min {
+            return Err(AllocationError::InvalidAllocatorCreateDesc(
+                "Minimum memory block size is larger than the largest memory heap".into(),
+            ));
+        }
+
+        let mut sizes = *self;
+        sizes.memblock_size_bounds.max = max;
+        sizes.min_device_memblock_size =
+            sizes.adjust_memblock_size(self.min_device_memblock_size, "Device");
+        sizes.max_device_memblock_size = sizes
+            .adjust_memblock_size(self.max_device_memblock_size, "Device")
+            .max(sizes.min_device_memblock_size);
+        sizes.min_host_memblock_size =
+            sizes.adjust_memblock_size(self.min_host_memblock_size, "Host");
+        sizes.max_host_memblock_size = sizes
+            .adjust_memblock_size(self.max_host_memblock_size, "Host")
+            .max(sizes.min_host_memblock_size);
+        for range in sizes
+            .location_memblock_sizes
+            .iter_mut()
+            .chain(&mut sizes.memory_type_memblock_sizes)
+            .flatten()
+        {
+            let min = Self::adjust_memblock_size_to(
+                range.min,
+                "Overridden",
+                granularity,
+                bounds.min,
+                max,
+            );
+            range.max = Self::adjust_memblock_size_to(
+                range.max,
+                "Overridden",
+                granularity,
+                bounds.min,
+                max,
+            )
+            .max(min);
+            range.min = min;
+        }
+
+        Ok(sizes)
+    }
+
+    fn adjust_memblock_size(&self, size: u64, kind: &str) -> u64 {
+        Self::adjust_memblock_size_to(
+            size,
+            kind,
+            self.memblock_size_granularity,
+            self.memblock_size_bounds.min,
+            self.memblock_size_bounds.max,
+        )
+    }
+
+    fn adjust_memblock_size_to(size: u64, kind: &str, granularity: u64, min: u64, max: u64) -> u64 {
+        let size = size.clamp(min, max);
+
+        if size % granularity == 0 {
             return size;
         }
 
-        let val = size / (4 * MB) + 1;
-        let new_size = val * 4 * MB;
+        // Can't go past `max`, as it is a multiple of the granularity itself.
+        let new_size = (size / granularity + 1) * granularity;
         log::warn!(
-            "{kind} memory block size must be a multiple of 4MB, clamping to {}MB",
-            new_size / MB
+            "{kind} memory block size must be a multiple of {granularity} bytes, rounding up to {new_size} bytes"
         );
 
         new_size
@@ -420,19 +720,39 @@ impl AllocationSizes {
     /// existing allocations. The more blocks there already are
     /// (where the requested allocation didn't fit), the larger
     /// the returned memory block size is going to be (up to
-    /// `max_*_memblock_size`).
-    pub(crate) fn get_memblock_size(&self, is_host: bool, count: usize) -> u64 {
-        let (min_size, max_size) = if is_host {
-            (self.min_host_memblock_size, self.max_host_memblock_size)
-        } else {
-            (self.min_device_memblock_size, self.max_device_memblock_size)
+    /// `max_*_memblock_size`, or the maximum of the override that applies to the memory type
+    /// or location). `heap_size` is the size of the heap the memory block is created in.
+    pub(crate) fn get_memblock_size(
+        &self,
+        memory_type_index: usize,
+        location: MemoryLocation,
+        is_host: bool,
+        heap_size: u64,
+        count: usize,
+    ) -> u64 {
+        let range = self
+            .memory_type_memblock_sizes
+            .get(memory_type_index)
+            .copied()
+            .flatten()
+            .or(self.location_memblock_sizes[Self::location_index(location)]);
+        let (mut min_size, mut max_size) = match range {
+            Some(range) => (range.min, range.max),
+            None if is_host => (self.min_host_memblock_size, self.max_host_memblock_size),
+            None => (self.min_device_memblock_size, self.max_device_memblock_size),
         };
+        if let Some(divisor) = self.heap_size_divisor {
+            let granularity = self.memblock_size_granularity;
+            let heap_fraction = (heap_size / divisor / granularity * granularity)
+                .max(self.memblock_size_bounds.min);
+            min_size = min_size.min(heap_fraction);
+            max_size = max_size.min(heap_fraction);
+        }
 
-        // The ranges are clamped to 4MB..256MB so we never need to
-        // shift by more than 7 bits. Clamping here to avoid having
-        // to worry about overflows.
-        let shift = count.min(7) as u64;
-        (min_size << shift).min(max_size)
+        // The bounds are configurable, so the doubling saturates instead of overflowing once it
+        // gets past any sensible block size.
+        let shift = count.min(63) as u32;
+        min_size.saturating_mul(1 << shift).min(max_size)
     }
 }
 
@@ -444,6 +764,14 @@ impl Default for AllocationSizes {
             max_device_memblock_size: 256 * MB,
             min_host_memblock_size: 64 * MB,
             max_host_memblock_size: 64 * MB,
+            location_memblock_sizes: [None; 5],
+            memory_type_memblock_sizes: [None; MAX_MEMORY_TYPES],
+            memblock_size_bounds: MemblockSizeRange {
+                min: 4 * MB,
+                max: 256 * MB,
+            },
+            memblock_size_granularity: 4 * MB,
+            heap_size_divisor: None,
         }
     }
 }
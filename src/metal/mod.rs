@@ -1,10 +1,5 @@
-#[cfg(feature = "std")]
-use alloc::sync::Arc;
-use alloc::{boxed::Box, string::ToString, vec::Vec};
-#[cfg(feature = "std")]
-use std::backtrace::Backtrace;
+use alloc::{boxed::Box, string::ToString};
 
-use log::debug;
 use objc2::{rc::Retained, runtime::ProtocolObject};
 use objc2_foundation::{ns_string, NSString};
 #[cfg(doc)]
@@ -21,15 +16,19 @@ pub use visualizer::AllocatorVisualizer;
 
 use crate::{
     allocator::{
-        AllocationType, AllocatorReport, DedicatedBlockAllocator, FreeListAllocator,
-        MemoryBlockReport, SubAllocator,
+        AllocationKey, AllocationRequest, AllocationType, AllocatorReport, CustomPool,
+        GenericAllocator, MemoryBackend, SubAllocation,
     },
-    AllocationError, AllocationSizes, AllocatorDebugSettings, MemoryLocation, Result,
+    AllocationError, AllocationSizes, AllocationStrategy, AllocatorDebugSettings,
+    DefragmentationMove, DefragmentationPlan, EmptyBlockRetention, MemoryLocation, Result,
+    SubAllocatorKind,
 };
 
 fn memory_location_to_metal(location: MemoryLocation) -> MTLResourceOptions {
     match location {
-        MemoryLocation::GpuOnly => MTLResourceOptions::StorageModePrivate,
+        MemoryLocation::GpuOnly | MemoryLocation::Transient => {
+            MTLResourceOptions::StorageModePrivate
+        }
         MemoryLocation::CpuToGpu | MemoryLocation::GpuToCpu | MemoryLocation::Unknown => {
             MTLResourceOptions::StorageModeShared
         }
@@ -84,6 +83,22 @@ impl Allocation {
     fn is_null(&self) -> bool {
         self.chunk_id.is_none()
     }
+
+    /// Where this allocation lives in the [`GenericAllocator`], or [`None`] for null allocations.
+    fn key(&self) -> Option<AllocationKey> {
+        Some((
+            self.memory_type_index,
+            self.memory_block_index,
+            self.chunk_id?,
+        ))
+    }
+}
+
+impl DefragmentationMove<Allocation> {
+    /// Returns [`true`] if this move relocates `allocation`.
+    pub fn is_for(&self, allocation: &Allocation) -> bool {
+        self.moves(allocation.key())
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -94,6 +109,11 @@ pub struct AllocationCreateDesc<'a> {
     pub location: MemoryLocation,
     pub size: u64,
     pub alignment: u64,
+    /// Overrides [`AllocatorCreateDesc::allocation_strategy`] for this allocation.
+    pub allocation_strategy: Option<AllocationStrategy>,
+    /// Allocates from the heaps of this [`Pool`] instead of the shared heaps, in which case
+    /// [`Self::location`] is ignored.
+    pub pool: Option<&'a Pool>,
 }
 
 impl<'a> AllocationCreateDesc<'a> {
@@ -112,6 +132,8 @@ impl<'a> AllocationCreateDesc<'a> {
             location,
             size: size_and_align.size as u64,
             alignment: size_and_align.align as u64,
+            allocation_strategy: None,
+            pool: None,
         }
     }
 
@@ -132,6 +154,8 @@ impl<'a> AllocationCreateDesc<'a> {
             },
             size: size_and_align.size as u64,
             alignment: size_and_align.align as u64,
+            allocation_strategy: None,
+            pool: None,
         }
     }
 
@@ -150,16 +174,14 @@ impl<'a> AllocationCreateDesc<'a> {
             location,
             size: size_and_align.size as u64,
             alignment: size_and_align.align as u64,
+            allocation_strategy: None,
+            pool: None,
         }
     }
 }
 
 pub struct Allocator {
-    device: Retained<ProtocolObject<dyn MTLDevice>>,
-    global_residency_set: Option<Retained<ProtocolObject<dyn MTLResidencySet>>>,
-    debug_settings: AllocatorDebugSettings,
-    memory_types: Vec<MemoryType>,
-    allocation_sizes: AllocationSizes,
+    inner: GenericAllocator<MetalBackend>,
 }
 
 impl core::fmt::Debug for Allocator {
@@ -173,251 +195,140 @@ pub struct AllocatorCreateDesc {
     pub device: Retained<ProtocolObject<dyn MTLDevice>>,
     pub debug_settings: AllocatorDebugSettings,
     pub allocation_sizes: AllocationSizes,
+    /// The algorithm used to place allocations inside shared memory blocks.
+    pub sub_allocator: SubAllocatorKind,
+    /// How free regions are picked inside shared memory blocks, unless overridden by
+    /// [`AllocationCreateDesc::allocation_strategy`].
+    pub allocation_strategy: AllocationStrategy,
+    /// How long shared memory blocks are kept around once they no longer hold any allocation.
+    pub empty_block_retention: EmptyBlockRetention,
     /// Whether to create a [`MTLResidencySet`] containing all live heaps, that can be retrieved via
     /// [`Allocator::residency_set()`].  Only supported on `MacOS 15.0+` / `iOS 18.0+`.
     pub create_residency_set: bool,
 }
 
-#[derive(Debug)]
-pub struct CommittedAllocationStatistics {
-    pub num_allocations: usize,
-    pub total_size: u64,
+/// Describes a [`Pool`] to create through [`Allocator::create_pool()`].
+#[derive(Clone, Debug)]
+pub struct PoolCreateDesc {
+    /// Location where the heaps of the pool should be stored
+    pub location: MemoryLocation,
+    /// Size in bytes of every heap of the pool. Larger allocations can't be made from the pool.
+    pub block_size: u64,
+    /// Number of heaps that are created together with the pool, and kept around when they become
+    /// empty.
+    pub min_block_count: usize,
+    /// Maximum number of heaps of the pool, after which allocating from a full pool fails with
+    /// [`AllocationError::OutOfMemory`]. [`None`] lets the pool grow as needed.
+    pub max_block_count: Option<usize>,
+    /// The algorithm used to place allocations inside the heaps of the pool.
+    pub sub_allocator: SubAllocatorKind,
+    /// How allocations pick between the free regions of the heaps of the pool, unless overridden
+    /// through [`AllocationCreateDesc::allocation_strategy`].
+    pub allocation_strategy: AllocationStrategy,
 }
 
+/// A set of heaps of a single memory type, kept apart from the heaps that are shared by all other
+/// allocations.
+///
+/// Allocations are made from a pool by setting [`AllocationCreateDesc::pool`], which allows
+/// isolating the allocations of a subsystem and putting a hard limit on the memory it uses.
 #[derive(Debug)]
-struct MemoryBlock {
-    heap: Retained<ProtocolObject<dyn MTLHeap>>,
-    size: u64,
-    sub_allocator: Box<dyn SubAllocator>,
+pub struct Pool {
+    memory_type_index: usize,
+    pool_index: usize,
 }
 
-impl MemoryBlock {
-    fn new(
-        device: &ProtocolObject<dyn MTLDevice>,
-        size: u64,
-        heap_descriptor: &MTLHeapDescriptor,
-        dedicated: bool,
-        memory_location: MemoryLocation,
-    ) -> Result<Self> {
-        heap_descriptor.setSize(size as usize);
-
-        let heap = device
-            .newHeapWithDescriptor(heap_descriptor)
-            .ok_or_else(|| AllocationError::Internal("No MTLHeap was returned".to_string()))?;
-
-        heap.setLabel(Some(&NSString::from_str(&format!(
-            "MemoryBlock {memory_location:?}"
-        ))));
+/// Describes a [`LinearPool`] to create through [`Allocator::create_linear_pool()`].
+#[derive(Clone, Debug)]
+pub struct LinearPoolCreateDesc {
+    /// Size in bytes of the single heap backing the pool.
+    pub size: u64,
+    /// Location where the heap of the pool should be stored
+    pub location: MemoryLocation,
+}
 
-        let sub_allocator: Box<dyn SubAllocator> = if dedicated {
-            Box::new(DedicatedBlockAllocator::new(size))
-        } else {
-            Box::new(FreeListAllocator::new(size))
-        };
+/// A dedicated heap for short-lived allocations, such as per-frame upload and constant buffers.
+///
+/// Allocations are bumped through the heap as a ring buffer, see [`SubAllocatorKind::Linear`].
+/// Each allocation is tagged with the frame set through [`Allocator::begin_linear_pool_frame()`],
+/// so that all allocations of completed frames can be released at once with
+/// [`Allocator::free_linear_pool_frames_before()`] instead of freeing them one by one.
+#[derive(Debug)]
+pub struct LinearPool {
+    memory_type_index: usize,
+    memory_block_index: usize,
+}
 
-        Ok(Self {
-            heap,
-            size,
-            sub_allocator,
-        })
-    }
+#[derive(Debug)]
+pub struct CommittedAllocationStatistics {
+    pub num_allocations: usize,
+    pub total_size: u64,
 }
 
+/// Properties of a Metal memory type.
 #[derive(Debug)]
-struct MemoryType {
-    global_residency_set: Option<Retained<ProtocolObject<dyn MTLResidencySet>>>,
-    memory_blocks: Vec<Option<MemoryBlock>>,
-    _committed_allocations: CommittedAllocationStatistics,
-    memory_location: MemoryLocation,
-    heap_properties: Retained<MTLHeapDescriptor>,
-    memory_type_index: usize,
-    active_general_blocks: usize,
+pub(crate) struct MemoryTypeProperties {
+    pub(crate) memory_location: MemoryLocation,
+    pub(crate) heap_descriptor: Retained<MTLHeapDescriptor>,
 }
 
-impl MemoryType {
-    fn allocate(
-        &mut self,
-        device: &ProtocolObject<dyn MTLDevice>,
-        desc: &AllocationCreateDesc<'_>,
-        #[cfg(feature = "std")] backtrace: Arc<Backtrace>,
-        allocation_sizes: &AllocationSizes,
-    ) -> Result<Allocation> {
-        let allocation_type = AllocationType::Linear;
+pub(crate) struct MetalBackend {
+    device: Retained<ProtocolObject<dyn MTLDevice>>,
+    global_residency_set: Option<Retained<ProtocolObject<dyn MTLResidencySet>>>,
+}
 
-        let is_host = self.heap_properties.storageMode() != MTLStorageMode::Private;
-        let memblock_size = allocation_sizes.get_memblock_size(is_host, self.active_general_blocks);
+impl MemoryBackend for MetalBackend {
+    type MemoryTypeProperties = MemoryTypeProperties;
+    type Memory = Retained<ProtocolObject<dyn MTLHeap>>;
+    /// Heaps are never dedicated to a single resource.
+    type DedicatedResource = core::convert::Infallible;
 
-        let size = desc.size;
-        let alignment = desc.alignment;
+    fn is_host_visible(properties: &MemoryTypeProperties) -> bool {
+        properties.heap_descriptor.storageMode() != MTLStorageMode::Private
+    }
 
-        // Create a dedicated block for large memory allocations
-        if size > memblock_size {
-            let mem_block = MemoryBlock::new(
-                device,
-                size,
-                &self.heap_properties,
-                true,
-                self.memory_location,
-            )?;
-
-            if let Some(rs) = &self.global_residency_set {
-                unsafe { rs.addAllocation(mem_block.heap.as_ref()) }
-            }
+    /// Metal does not expose memory heaps, so every memory type shares a single heap.
+    fn heap_index(_properties: &MemoryTypeProperties) -> usize {
+        0
+    }
 
-            let block_index = self.memory_blocks.iter().position(|block| block.is_none());
-            let block_index = match block_index {
-                Some(i) => {
-                    self.memory_blocks[i].replace(mem_block);
-                    i
-                }
-                None => {
-                    self.memory_blocks.push(Some(mem_block));
-                    self.memory_blocks.len() - 1
-                }
-            };
-
-            let mem_block = self.memory_blocks[block_index]
-                .as_mut()
-                .ok_or_else(|| AllocationError::Internal("Memory block must be Some".into()))?;
-
-            let (offset, chunk_id) = mem_block.sub_allocator.allocate(
-                size,
-                alignment,
-                allocation_type,
-                1,
-                desc.name,
-                #[cfg(feature = "std")]
-                backtrace,
-            )?;
-
-            return Ok(Allocation {
-                chunk_id: Some(chunk_id),
-                size,
-                offset,
-                memory_block_index: block_index,
-                memory_type_index: self.memory_type_index,
-                heap: mem_block.heap.clone(),
-                name: Some(desc.name.into()),
-            });
-        }
+    fn atom_size(_properties: &MemoryTypeProperties) -> u64 {
+        1
+    }
 
-        let mut empty_block_index = None;
-        for (mem_block_i, mem_block) in self.memory_blocks.iter_mut().enumerate().rev() {
-            if let Some(mem_block) = mem_block {
-                let allocation = mem_block.sub_allocator.allocate(
-                    size,
-                    alignment,
-                    allocation_type,
-                    1,
-                    desc.name,
-                    #[cfg(feature = "std")]
-                    backtrace.clone(),
-                );
-
-                match allocation {
-                    Ok((offset, chunk_id)) => {
-                        return Ok(Allocation {
-                            chunk_id: Some(chunk_id),
-                            offset,
-                            size,
-                            memory_block_index: mem_block_i,
-                            memory_type_index: self.memory_type_index,
-                            heap: mem_block.heap.clone(),
-                            name: Some(desc.name.into()),
-                        });
-                    }
-                    Err(AllocationError::OutOfMemory) => {} // Block is full, continue search.
-                    Err(err) => return Err(err),            // Unhandled error, return.
-                }
-            } else if empty_block_index.is_none() {
-                empty_block_index = Some(mem_block_i);
-            }
-        }
+    fn create_memory(
+        &self,
+        properties: &MemoryTypeProperties,
+        _memory_type_index: usize,
+        size: u64,
+        _dedicated_resource: Option<Self::DedicatedResource>,
+        _priority: Option<f32>,
+    ) -> Result<Self::Memory> {
+        properties.heap_descriptor.setSize(size as usize);
+
+        let heap = self
+            .device
+            .newHeapWithDescriptor(&properties.heap_descriptor)
+            .ok_or_else(|| AllocationError::Internal("No MTLHeap was returned".to_string()))?;
 
-        let mem_block = MemoryBlock::new(
-            device,
-            memblock_size,
-            &self.heap_properties,
-            false,
-            self.memory_location,
-        )?;
+        heap.setLabel(Some(&NSString::from_str(&format!(
+            "MemoryBlock {:?}",
+            properties.memory_location
+        ))));
 
         if let Some(rs) = &self.global_residency_set {
-            unsafe { rs.addAllocation(mem_block.heap.as_ref()) }
+            unsafe { rs.addAllocation(heap.as_ref()) }
         }
 
-        let new_block_index = if let Some(block_index) = empty_block_index {
-            self.memory_blocks[block_index] = Some(mem_block);
-            block_index
-        } else {
-            self.memory_blocks.push(Some(mem_block));
-            self.memory_blocks.len() - 1
-        };
-
-        self.active_general_blocks += 1;
-
-        let mem_block = self.memory_blocks[new_block_index]
-            .as_mut()
-            .ok_or_else(|| AllocationError::Internal("Memory block must be Some".into()))?;
-        let allocation = mem_block.sub_allocator.allocate(
-            size,
-            alignment,
-            allocation_type,
-            1,
-            desc.name,
-            #[cfg(feature = "std")]
-            backtrace,
-        );
-        let (offset, chunk_id) = match allocation {
-            Err(AllocationError::OutOfMemory) => Err(AllocationError::Internal(
-                "Allocation that must succeed failed. This is a bug in the allocator.".into(),
-            )),
-            a => a,
-        }?;
-
-        Ok(Allocation {
-            chunk_id: Some(chunk_id),
-            offset,
-            size,
-            memory_block_index: new_block_index,
-            memory_type_index: self.memory_type_index,
-            heap: mem_block.heap.clone(),
-            name: Some(desc.name.into()),
-        })
+        Ok(heap)
     }
 
-    fn free(&mut self, allocation: &Allocation) -> Result<()> {
-        let block_idx = allocation.memory_block_index;
-
-        let mem_block = self.memory_blocks[block_idx]
-            .as_mut()
-            .ok_or_else(|| AllocationError::Internal("Memory block must be Some.".into()))?;
-
-        mem_block.sub_allocator.free(allocation.chunk_id)?;
-
-        // We only want to destroy this now-empty block if it is either a dedicated/personal
-        // allocation, or a block supporting sub-allocations that is not the last one (ensuring
-        // there's always at least one block/allocator readily available).
-        let is_dedicated_or_not_last_general_block =
-            !mem_block.sub_allocator.supports_general_allocations()
-                || self.active_general_blocks > 1;
-        if mem_block.sub_allocator.is_empty() && is_dedicated_or_not_last_general_block {
-            let block = self.memory_blocks[block_idx]
-                .take()
-                .ok_or_else(|| AllocationError::Internal("Memory block must be Some.".into()))?;
-
-            if block.sub_allocator.supports_general_allocations() {
-                self.active_general_blocks -= 1;
-            }
-
-            if let Some(rs) = &self.global_residency_set {
-                unsafe { rs.removeAllocation(block.heap.as_ref()) }
-            }
-
-            // Note that `block` will be destroyed on `drop` here
+    fn destroy_memory(&self, heap: Self::Memory) {
+        if let Some(rs) = &self.global_residency_set {
+            unsafe { rs.removeAllocation(heap.as_ref()) }
         }
 
-        Ok(())
+        // Note that `heap` will be destroyed on `drop` here
     }
 }
 
@@ -461,27 +372,62 @@ impl Allocator {
 
         let memory_types = heap_types
             .into_iter()
-            .enumerate()
-            .map(|(i, (memory_location, heap_descriptor))| MemoryType {
-                global_residency_set: global_residency_set.clone(),
-                memory_blocks: vec![],
-                _committed_allocations: CommittedAllocationStatistics {
-                    num_allocations: 0,
-                    total_size: 0,
-                },
+            .map(|(memory_location, heap_descriptor)| MemoryTypeProperties {
                 memory_location,
-                heap_properties: heap_descriptor,
-                memory_type_index: i,
-                active_general_blocks: 0,
-            })
-            .collect();
+                heap_descriptor,
+            });
+
+        // Metal doesn't expose the sizes of its memory heaps to check memory block sizes against.
+        let allocation_sizes = desc.allocation_sizes.resolve(u64::MAX)?;
 
         Ok(Self {
-            device: desc.device.clone(),
-            debug_settings: desc.debug_settings,
-            memory_types,
-            allocation_sizes: desc.allocation_sizes,
-            global_residency_set,
+            inner: GenericAllocator::new(
+                MetalBackend {
+                    device: desc.device.clone(),
+                    global_residency_set,
+                },
+                memory_types,
+                &[],
+                desc.debug_settings,
+                &allocation_sizes,
+                desc.sub_allocator,
+                desc.allocation_strategy,
+                desc.empty_block_retention,
+            ),
+        })
+    }
+
+    /// Finds the memory type for allocations in `location`.
+    fn find_memory_type_index(&self, location: MemoryLocation) -> Result<usize> {
+        // Memoryless storage can't be placed in heaps, transient allocations use private memory.
+        let location = match location {
+            MemoryLocation::Transient => MemoryLocation::GpuOnly,
+            location => location,
+        };
+        self.inner
+            .memory_types
+            .iter()
+            .find(|memory_type| {
+                // Is location compatible
+                location == MemoryLocation::Unknown
+                    || location == memory_type.properties.memory_location
+            })
+            .map(|memory_type| memory_type.memory_type_index)
+            .ok_or(AllocationError::NoCompatibleMemoryTypeFound)
+    }
+
+    /// Creates the [`Allocation`] handle for memory allocated through the [`GenericAllocator`].
+    fn new_allocation(&self, allocation: SubAllocation, name: &str) -> Result<Allocation> {
+        let mem_block = self.inner.memory_block(&allocation)?;
+
+        Ok(Allocation {
+            chunk_id: Some(allocation.chunk_id),
+            offset: allocation.offset,
+            size: allocation.size,
+            memory_block_index: allocation.memory_block_index,
+            memory_type_index: allocation.memory_type_index,
+            heap: mem_block.memory.clone(),
+            name: Some(name.into()),
         })
     }
 
@@ -490,115 +436,280 @@ impl Allocator {
         let alignment = desc.alignment;
 
         #[cfg(feature = "std")]
-        let backtrace = Arc::new(if self.debug_settings.store_stack_traces {
-            Backtrace::force_capture()
-        } else {
-            Backtrace::disabled()
-        });
-
-        if self.debug_settings.log_allocations {
-            debug!(
-                "Allocating `{}` of {} bytes with an alignment of {}.",
-                &desc.name, size, alignment
-            );
-            #[cfg(feature = "std")]
-            if self.debug_settings.log_stack_traces {
-                let backtrace = Backtrace::force_capture();
-                debug!("Allocation stack trace: {backtrace}");
-            }
-        }
+        let backtrace = self.inner.capture_backtrace();
+
+        self.inner.log_allocation(desc.name, size, alignment);
 
         if size == 0 || !alignment.is_power_of_two() {
             return Err(AllocationError::InvalidAllocationCreateDesc);
         }
 
-        // Find memory type
-        let memory_type = self
-            .memory_types
-            .iter_mut()
-            .find(|memory_type| {
-                // Is location compatible
-                desc.location == MemoryLocation::Unknown
-                    || desc.location == memory_type.memory_location
-            })
-            .ok_or(AllocationError::NoCompatibleMemoryTypeFound)?;
-
-        memory_type.allocate(
-            &self.device,
-            desc,
+        let request = AllocationRequest {
+            name: desc.name,
+            size,
+            alignment,
+            allocation_type: AllocationType::Linear,
+            granularity: 1,
+            strategy: desc.allocation_strategy,
+            location: desc.location,
+            priority: None,
             #[cfg(feature = "std")]
             backtrace,
-            &self.allocation_sizes,
-        )
+        };
+
+        if let Some(pool) = desc.pool {
+            let allocation =
+                self.inner
+                    .allocate_from_pool(pool.memory_type_index, pool.pool_index, &request)?;
+            return self.new_allocation(allocation, desc.name);
+        }
+
+        // Find memory type
+        let memory_type_index = self.find_memory_type_index(desc.location)?;
+
+        let allocation = self.inner.allocate(memory_type_index, &request, None)?;
+
+        self.new_allocation(allocation, desc.name)
     }
 
     pub fn free(&mut self, allocation: &Allocation) -> Result<()> {
-        if self.debug_settings.log_frees {
-            let name = allocation.name.as_deref().unwrap_or("<null>");
-            debug!("Freeing `{name}`.");
-            #[cfg(feature = "std")]
-            if self.debug_settings.log_stack_traces {
-                let backtrace = Backtrace::force_capture();
-                debug!("Free stack trace: {backtrace}");
-            }
-        }
+        self.inner
+            .debug_settings
+            .log_free(allocation.name.as_deref());
 
         if allocation.is_null() {
             return Ok(());
         }
-        self.memory_types[allocation.memory_type_index].free(allocation)?;
+        self.inner.free(
+            allocation.memory_type_index,
+            allocation.memory_block_index,
+            allocation.chunk_id,
+        )?;
         Ok(())
     }
 
+    /// Sets the algorithm used to place allocations inside the shared heaps of all memory types
+    /// for `location`, overriding [`AllocatorCreateDesc::sub_allocator`] for them.
+    ///
+    /// Only affects heaps that are created after this call.
+    pub fn set_memory_location_sub_allocator(
+        &mut self,
+        location: MemoryLocation,
+        sub_allocator: SubAllocatorKind,
+    ) {
+        for memory_type in &mut self.inner.memory_types {
+            if memory_type.properties.memory_location == location {
+                memory_type.sub_allocator = sub_allocator;
+            }
+        }
+    }
+
+    /// Creates a [`LinearPool`] holding a single heap of [`LinearPoolCreateDesc::size`] bytes, in
+    /// a memory type compatible with the given location.
+    pub fn create_linear_pool(&mut self, desc: &LinearPoolCreateDesc) -> Result<LinearPool> {
+        if desc.size == 0 {
+            return Err(AllocationError::InvalidAllocationCreateDesc);
+        }
+
+        let memory_type_index = self.find_memory_type_index(desc.location)?;
+        let memory_block_index = self
+            .inner
+            .create_linear_pool(memory_type_index, desc.size)?;
+
+        Ok(LinearPool {
+            memory_type_index,
+            memory_block_index,
+        })
+    }
+
+    /// Creates a [`Pool`] in a memory type compatible with the given location, along with its
+    /// [`PoolCreateDesc::min_block_count`] heaps.
+    pub fn create_pool(&mut self, desc: &PoolCreateDesc) -> Result<Pool> {
+        let pool = CustomPool::new(
+            desc.block_size,
+            desc.min_block_count,
+            desc.max_block_count,
+            desc.sub_allocator,
+            desc.allocation_strategy,
+            None,
+        )?;
+        let memory_type_index = self.find_memory_type_index(desc.location)?;
+        let pool_index = self.inner.create_pool(memory_type_index, pool)?;
+
+        Ok(Pool {
+            memory_type_index,
+            pool_index,
+        })
+    }
+
+    /// Destroys all heaps of a [`Pool`].
+    ///
+    /// All allocations made from the pool must have been freed. Otherwise this fails with
+    /// [`AllocationError::Internal`] and destroys nothing, and the heaps of the pool are only
+    /// destroyed together with the allocator.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn destroy_pool(&mut self, pool: Pool) -> Result<()> {
+        self.inner
+            .destroy_pool(pool.memory_type_index, pool.pool_index)
+    }
+
+    /// Destroys the heap of a [`LinearPool`].
+    ///
+    /// Any allocation that is still live in the pool becomes invalid, and must neither be used
+    /// nor freed afterwards.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn destroy_linear_pool(&mut self, pool: LinearPool) -> Result<()> {
+        self.inner
+            .destroy_linear_pool(pool.memory_type_index, pool.memory_block_index)
+    }
+
+    /// Allocates from a [`LinearPool`] instead of the shared heaps.
+    ///
+    /// [`AllocationCreateDesc::location`] is ignored in favor of the location of the pool.
+    /// Returns [`AllocationError::OutOfMemory`] when the pool is full, no other heaps are created
+    /// to make room.
+    ///
+    /// The allocation may be freed through [`Allocator::free()`] like any other, or be released
+    /// together with the rest of its frame through [`Allocator::free_linear_pool_frames_before()`].
+    pub fn allocate_from_linear_pool(
+        &mut self,
+        pool: &LinearPool,
+        desc: &AllocationCreateDesc<'_>,
+    ) -> Result<Allocation> {
+        let size = desc.size;
+        let alignment = desc.alignment;
+
+        #[cfg(feature = "std")]
+        let backtrace = self.inner.capture_backtrace();
+
+        self.inner.log_allocation(desc.name, size, alignment);
+
+        if size == 0 || !alignment.is_power_of_two() {
+            return Err(AllocationError::InvalidAllocationCreateDesc);
+        }
+
+        let request = AllocationRequest {
+            name: desc.name,
+            size,
+            alignment,
+            allocation_type: AllocationType::Linear,
+            granularity: 1,
+            strategy: desc.allocation_strategy,
+            location: desc.location,
+            priority: None,
+            #[cfg(feature = "std")]
+            backtrace,
+        };
+        let allocation = self.inner.allocate_from_linear_pool(
+            pool.memory_type_index,
+            pool.memory_block_index,
+            &request,
+        )?;
+
+        self.new_allocation(allocation, desc.name)
+    }
+
+    /// Tags all allocations made from `pool` from now on with `frame`.
+    ///
+    /// Frame numbers are expected to increase monotonically.
+    pub fn begin_linear_pool_frame(&mut self, pool: &LinearPool, frame: u64) -> Result<()> {
+        self.inner
+            .begin_linear_pool_frame(pool.memory_type_index, pool.memory_block_index, frame)
+    }
+
+    /// Frees all allocations of `pool` that were made before `frame` was begun, typically
+    /// because the GPU has finished processing those frames.
+    ///
+    /// The released [`Allocation`]s become invalid and must no longer be used. Passing them to
+    /// [`Allocator::free()`] afterwards is allowed and does nothing.
+    pub fn free_linear_pool_frames_before(&mut self, pool: &LinearPool, frame: u64) -> Result<()> {
+        self.inner.free_linear_pool_frames_before(
+            pool.memory_type_index,
+            pool.memory_block_index,
+            frame,
+        )
+    }
+
+    /// Plans moving allocations out of the least used shared memory blocks into other memory
+    /// blocks of the same memory type, so that those memory blocks become empty and are freed
+    /// once the plan is committed.
+    ///
+    /// Memory blocks are only emptied as a whole, and only as long as the total size of the
+    /// moved allocations stays within `max_bytes_to_move`. Room for every move is reserved right
+    /// away: copy the data of each moved allocation to [`DefragmentationMove::destination()`],
+    /// recreate or rebind the resources placed on it, and then pass the plan to
+    /// [`Allocator::commit_defragmentation()`], or give up with
+    /// [`Allocator::cancel_defragmentation()`]. Allocations that are part of the plan must not be
+    /// freed before then, and dropping the plan keeps the reserved room allocated until the
+    /// allocator is dropped.
+    pub fn plan_defragmentation(
+        &mut self,
+        max_bytes_to_move: u64,
+    ) -> Result<DefragmentationPlan<Allocation>> {
+        let moves = self
+            .inner
+            .plan_defragmentation(max_bytes_to_move, 1)?
+            .into_iter()
+            .map(|planned| {
+                let destination = self.new_allocation(planned.destination, &planned.name)?;
+                Ok(planned.with_destination(destination))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(DefragmentationPlan { moves })
+    }
+
+    /// Finishes `plan` once the data has been copied: every allocation in `allocations` that is
+    /// moved by the plan is freed and replaced with the destination of its move, which may empty
+    /// and free whole memory blocks.
+    ///
+    /// The moves of allocations that are not passed in are cancelled.
+    pub fn commit_defragmentation<'a>(
+        &mut self,
+        plan: DefragmentationPlan<Allocation>,
+        allocations: impl IntoIterator<Item = &'a mut Allocation>,
+    ) -> Result<()> {
+        self.inner
+            .commit_defragmentation(plan, allocations, Allocation::key)
+    }
+
+    /// Releases the room reserved by `plan`, leaving all allocations where they are.
+    pub fn cancel_defragmentation(&mut self, plan: DefragmentationPlan<Allocation>) -> Result<()> {
+        self.inner.cancel_defragmentation(plan)
+    }
+
     /// Returns heaps for all memory blocks
     pub fn heaps(&self) -> impl Iterator<Item = &ProtocolObject<dyn MTLHeap>> {
-        self.memory_types.iter().flat_map(|memory_type| {
+        self.inner.memory_types.iter().flat_map(|memory_type| {
             memory_type
                 .memory_blocks
                 .iter()
                 .flatten()
-                .map(|block| block.heap.as_ref())
+                .map(|block| block.memory.as_ref())
         })
     }
 
     pub fn generate_report(&self) -> AllocatorReport {
-        let mut allocations = vec![];
-        let mut blocks = vec![];
-        let mut total_capacity_bytes = 0;
-
-        for memory_type in &self.memory_types {
-            for block in memory_type.memory_blocks.iter().flatten() {
-                total_capacity_bytes += block.size;
-                let first_allocation = allocations.len();
-                allocations.extend(block.sub_allocator.report_allocations());
-                blocks.push(MemoryBlockReport {
-                    size: block.size,
-                    allocations: first_allocation..allocations.len(),
-                });
-            }
-        }
-
-        let total_allocated_bytes = allocations.iter().map(|report| report.size).sum();
-
-        AllocatorReport {
-            allocations,
-            blocks,
-            total_allocated_bytes,
-            total_capacity_bytes,
-        }
+        self.inner.generate_report()
     }
 
     /// Current total capacity of memory blocks allocated on the device, in bytes
     pub fn capacity(&self) -> u64 {
-        let mut total_capacity_bytes = 0;
+        self.inner.capacity()
+    }
 
-        for memory_type in &self.memory_types {
-            for block in memory_type.memory_blocks.iter().flatten() {
-                total_capacity_bytes += block.size;
-            }
-        }
+    /// Destroys all shared memory blocks that don't hold any allocation, including the last one of
+    /// every memory type, to give memory back during idle periods. Memory blocks of pools are
+    /// unaffected.
+    pub fn trim(&mut self) {
+        self.inner.trim();
+    }
 
-        total_capacity_bytes
+    /// Advances the frame counter of [`EmptyBlockRetention::Frames`] to `frame`, destroying the
+    /// empty shared memory blocks that have been kept around for long enough. `frame` must never
+    /// go down.
+    pub fn set_current_frame(&mut self, frame: u64) {
+        self.inner.set_current_frame(frame);
     }
 
     /// Optional residency set containing all heap allocations created/owned by this allocator to
@@ -625,6 +736,6 @@ impl Allocator {
     pub fn residency_set(&self) -> Option<&Retained<ProtocolObject<dyn MTLResidencySet>>> {
         // Return the retained object so that the caller also has a way to store it, since we will
         // keep using and updating the same object going forward.
-        self.global_residency_set.as_ref()
+        self.inner.backend.global_residency_set.as_ref()
     }
 }
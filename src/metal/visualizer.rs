@@ -3,7 +3,7 @@
 use super::Allocator;
 use crate::visualizer::{
     render_allocation_reports_ui, AllocationReportVisualizeSettings, ColorScheme,
-    MemoryChunksVisualizationSettings,
+    MemoryChunksVisualizationSettings, SubAllocatorVisualizer,
 };
 
 struct AllocatorVisualizerBlockWindow {
@@ -42,9 +42,9 @@ impl AllocatorVisualizer {
 
     pub fn render_memory_block_ui(&mut self, ui: &mut egui::Ui, alloc: &Allocator) {
         ui.collapsing(
-            format!("Memory Types: ({} types)", alloc.memory_types.len()),
+            format!("Memory Types: ({} types)", alloc.inner.memory_types.len()),
             |ui| {
-                for (mem_type_idx, mem_type) in alloc.memory_types.iter().enumerate() {
+                for (mem_type_idx, mem_type) in alloc.inner.memory_types.iter().enumerate() {
                     ui.collapsing(
                         format!(
                             "Type: {} ({} blocks)",
@@ -66,7 +66,10 @@ impl AllocatorVisualizer {
                                 .filter(|block| block.is_some())
                                 .count();
 
-                            ui.label(format!("properties: {:?}", mem_type.heap_properties));
+                            ui.label(format!(
+                                "properties: {:?}",
+                                mem_type.properties.heap_descriptor
+                            ));
                             ui.label(format!("memory type index: {}", mem_type.memory_type_index));
                             ui.label(format!("total block size: {} KiB", total_block_size / 1024));
                             ui.label(format!("total allocated:  {} KiB", total_allocated / 1024));
@@ -81,7 +84,7 @@ impl AllocatorVisualizer {
                                         "allocated: {} KiB",
                                         block.sub_allocator.allocated() / 1024
                                     ));
-                                    ui.label(format!("Heap: {:?}", &block.heap));
+                                    ui.label(format!("Heap: {:?}", &block.memory));
 
                                     block.sub_allocator.draw_base_info(ui);
 
@@ -137,8 +140,8 @@ impl AllocatorVisualizer {
             .default_size([1920.0 * 0.5, 1080.0 * 0.5])
             .open(&mut open)
             .show(ctx, |ui| {
-                let memblock = &allocator.memory_types[window.memory_type_index].memory_blocks
-                    [window.block_index]
+                let memblock = &allocator.inner.memory_types[window.memory_type_index]
+                    .memory_blocks[window.block_index]
                     .as_ref();
                 if let Some(memblock) = memblock {
                     ui.label(format!(
@@ -150,7 +153,7 @@ impl AllocatorVisualizer {
 
                     window
                         .settings
-                        .ui(ui, allocator.debug_settings.store_stack_traces);
+                        .ui(ui, allocator.inner.debug_settings.store_stack_traces);
 
                     ui.separator();
 
@@ -171,6 +174,7 @@ impl AllocatorVisualizer {
             ui,
             &mut self.breakdown_settings,
             allocator
+                .inner
                 .memory_types
                 .iter()
                 .flat_map(|memory_type| memory_type.memory_blocks.iter())
@@ -0,0 +1,1680 @@
+//! An in-process backend that hands out fake memory objects instead of talking to a GPU.
+//!
+//! The mock [`Allocator`] runs the exact same memory block and sub-allocation logic as the real
+//! backends, on memory types and heaps that are described with plain data. Heaps are only
+//! accounted for and never backed by actual memory, so they can be as large as needed. Memory
+//! creation fails with [`AllocationError::OutOfMemory`] when a heap is exhausted, or on demand
+//! through [`Allocator::fail_memory_creation()`]. This allows allocation policies, the
+//! dedicated-allocation and fallback paths, and leak reporting to be tested on machines without
+//! a GPU.
+//!
+//! Memory types are picked for a [`MemoryLocation`] the same way as with the Vulkan backend with
+//! default `MemoryTypePreferences`: of the memory types with all required properties, the one
+//! missing the fewest preferred properties and having the fewest properties that are not preferred
+//! is used, the first one when several are equally good. The mock has no notion of host coherent
+//! or lazily allocated memory.
+//!
+//! ```
+//! use gpu_allocator::mock::*;
+//! use gpu_allocator::{AllocationError, MemoryLocation};
+//!
+//! const MB: u64 = 1024 * 1024;
+//!
+//! let mut allocator = Allocator::new(&AllocatorCreateDesc {
+//!     memory_heaps: vec![
+//!         MemoryHeapDesc { size: 1024 * MB },
+//!         MemoryHeapDesc { size: 1024 * MB },
+//!         // A small heap that is both device local and host visible, like a PCIe BAR.
+//!         MemoryHeapDesc { size: 16 * MB },
+//!     ],
+//!     memory_types: vec![
+//!         MemoryTypeDesc { heap_index: 0, device_local: true, host_visible: false, host_cached: false },
+//!         MemoryTypeDesc { heap_index: 1, device_local: false, host_visible: true, host_cached: true },
+//!         MemoryTypeDesc { heap_index: 2, device_local: true, host_visible: true, host_cached: false },
+//!     ],
+//!     buffer_image_granularity: 1,
+//!     heap_size_limits: vec![],
+//!     debug_settings: Default::default(),
+//!     allocation_sizes: Default::default(),
+//!     sub_allocator: Default::default(),
+//!     allocation_strategy: Default::default(),
+//!     empty_block_retention: Default::default(),
+//! })?;
+//!
+//! let desc = AllocationCreateDesc {
+//!     name: "vertices",
+//!     size: MB,
+//!     alignment: 256,
+//!     location: MemoryLocation::GpuOnly,
+//!     linear: true,
+//!     dedicated: false,
+//!     allocation_strategy: None,
+//!     pool: None,
+//! };
+//!
+//! // Both allocations share the first memory block of the device local memory type.
+//! let vertices = allocator.allocate(&desc)?;
+//! let indices = allocator.allocate(&AllocationCreateDesc { name: "indices", ..desc.clone() })?;
+//! assert_eq!(vertices.memory_type_index(), 0);
+//! assert_eq!(vertices.memory_id(), indices.memory_id());
+//! assert_eq!(allocator.memory_creations(), 1);
+//! assert_eq!(allocator.heap_usage(0), 256 * MB);
+//!
+//! // Dedicated allocations get a memory object of their own.
+//! let image = allocator.allocate(&AllocationCreateDesc { name: "image", dedicated: true, ..desc.clone() })?;
+//! assert!(image.is_dedicated());
+//! assert_ne!(image.memory_id(), vertices.memory_id());
+//! assert_eq!(allocator.heap_usage(0), 256 * MB + MB);
+//!
+//! // Uploads don't fit in the small device local heap, and fall back to plain host memory.
+//! let upload = allocator.allocate(&AllocationCreateDesc {
+//!     name: "upload",
+//!     size: 32 * MB,
+//!     location: MemoryLocation::CpuToGpu,
+//!     ..desc.clone()
+//! })?;
+//! assert_eq!(upload.memory_type_index(), 1);
+//!
+//! // Make the next memory creation fail.
+//! allocator.fail_memory_creation(1);
+//! let err = allocator.allocate(&AllocationCreateDesc { name: "big", size: 512 * MB, ..desc.clone() });
+//! assert!(matches!(err, Err(AllocationError::OutOfMemory)));
+//!
+//! // Allocations that are not freed are still listed in the report, and are reported as leaks
+//! // when the allocator is dropped if `log_leaks_on_shutdown` is set.
+//! allocator.free(vertices)?;
+//! allocator.free(image)?;
+//! allocator.free(upload)?;
+//! let report = allocator.generate_report();
+//! assert_eq!(report.allocations.len(), 1);
+//! assert_eq!(report.allocations[0].name, "indices");
+//! # Ok::<(), AllocationError>(())
+//! ```
+
+#[cfg(feature = "std")]
+use alloc::sync::Arc;
+use alloc::{boxed::Box, vec::Vec};
+use core::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use log::Level;
+
+use crate::{
+    allocator::{
+        AllocationKey, AllocationRequest, AllocationType, AllocatorCore, AllocatorReport,
+        CustomPool, GenericAllocator, HeapProperties, MemoryBackend, MemoryPropertyPreferences,
+        SubAllocation,
+    },
+    AllocationError, AllocationSizes, AllocationStrategy, AllocatorDebugSettings,
+    DefragmentationMove, DefragmentationPlan, EmptyBlockRetention, MemoryLocation, Result,
+    SubAllocatorKind,
+};
+#[cfg(feature = "std")]
+use crate::{
+    allocator::{CacheSlice, GenericThreadCache, SyncGenericAllocator},
+    ThreadCacheCreateDesc,
+};
+
+/// Describes a heap of the mock device.
+#[derive(Clone, Copy, Debug)]
+pub struct MemoryHeapDesc {
+    /// Size of the heap in bytes, memory creation fails once it is used up.
+    pub size: u64,
+}
+
+/// Describes a memory type of the mock device.
+#[derive(Clone, Copy, Debug)]
+pub struct MemoryTypeDesc {
+    /// Index into [`AllocatorCreateDesc::memory_heaps`] of the heap this memory type allocates
+    /// from.
+    pub heap_index: usize,
+    pub device_local: bool,
+    pub host_visible: bool,
+    pub host_cached: bool,
+}
+
+/// Flags of the properties of a [`MemoryTypeDesc`], to pick memory types by.
+const DEVICE_LOCAL: u32 = 1 << 0;
+const HOST_VISIBLE: u32 = 1 << 1;
+const HOST_CACHED: u32 = 1 << 2;
+
+impl MemoryTypeDesc {
+    fn property_flags(&self) -> u32 {
+        let flag = |property: bool, flag: u32| if property { flag } else { 0 };
+        flag(self.device_local, DEVICE_LOCAL)
+            | flag(self.host_visible, HOST_VISIBLE)
+            | flag(self.host_cached, HOST_CACHED)
+    }
+}
+
+/// The property flags that memory types for `location` are picked by.
+fn memory_property_preferences(location: MemoryLocation) -> MemoryPropertyPreferences {
+    match location {
+        // Leave host visible device local memory, like Resizable BAR, to uploads.
+        MemoryLocation::GpuOnly | MemoryLocation::Transient => MemoryPropertyPreferences {
+            required: DEVICE_LOCAL,
+            preferred: DEVICE_LOCAL,
+            not_preferred: HOST_VISIBLE,
+        },
+        MemoryLocation::CpuToGpu => MemoryPropertyPreferences {
+            required: HOST_VISIBLE,
+            preferred: HOST_VISIBLE | DEVICE_LOCAL,
+            not_preferred: 0,
+        },
+        MemoryLocation::GpuToCpu => MemoryPropertyPreferences {
+            required: HOST_VISIBLE,
+            preferred: HOST_VISIBLE | HOST_CACHED,
+            not_preferred: 0,
+        },
+        MemoryLocation::Unknown => MemoryPropertyPreferences::default(),
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AllocationCreateDesc<'a> {
+    /// Name of the allocation, for tracking and debugging purposes
+    pub name: &'a str,
+    pub size: u64,
+    pub alignment: u64,
+    /// Location where the memory allocation should be stored. Allocations for
+    /// [`MemoryLocation::Transient`] are kept in memory blocks of their own:
+    ///
+    /// ```
+    /// use gpu_allocator::mock::*;
+    /// use gpu_allocator::{AllocationError, MemoryLocation};
+    ///
+    /// let mut allocator = Allocator::new(&AllocatorCreateDesc {
+    ///     memory_heaps: vec![MemoryHeapDesc { size: 8 * 1024 * 1024 * 1024 }],
+    ///     memory_types: vec![
+    ///         MemoryTypeDesc { heap_index: 0, device_local: true, host_visible: false, host_cached: false },
+    ///     ],
+    ///     buffer_image_granularity: 1,
+    ///     heap_size_limits: vec![],
+    ///     debug_settings: Default::default(),
+    ///     allocation_sizes: Default::default(),
+    ///     sub_allocator: Default::default(),
+    ///     allocation_strategy: Default::default(),
+    ///     empty_block_retention: Default::default(),
+    /// })?;
+    ///
+    /// let desc = AllocationCreateDesc {
+    ///     name: "texture",
+    ///     size: 1024 * 1024,
+    ///     alignment: 256,
+    ///     location: MemoryLocation::GpuOnly,
+    ///     linear: false,
+    ///     dedicated: false,
+    ///     allocation_strategy: None,
+    ///     pool: None,
+    /// };
+    /// let texture = allocator.allocate(&desc)?;
+    /// let depth = allocator.allocate(&AllocationCreateDesc {
+    ///     name: "msaa depth",
+    ///     location: MemoryLocation::Transient,
+    ///     ..desc.clone()
+    /// })?;
+    /// assert_eq!(allocator.memory_creations(), 2);
+    ///
+    /// // Other allocations keep using the memory block of `texture`.
+    /// let other = allocator.allocate(&desc)?;
+    /// assert_eq!(allocator.memory_creations(), 2);
+    /// # allocator.free(texture)?;
+    /// # allocator.free(depth)?;
+    /// # allocator.free(other)?;
+    /// # Ok::<(), AllocationError>(())
+    /// ```
+    pub location: MemoryLocation,
+    /// If the resource is linear (buffer / linear texture) or a regular (tiled) texture.
+    pub linear: bool,
+    /// Gives the allocation a memory object of its own, like a dedicated Vulkan allocation.
+    pub dedicated: bool,
+    /// Overrides [`AllocatorCreateDesc::allocation_strategy`] for this allocation.
+    pub allocation_strategy: Option<AllocationStrategy>,
+    /// Allocates from the memory blocks of this [`Pool`] instead of the shared memory blocks, in
+    /// which case [`Self::location`] is ignored and [`Self::dedicated`] must be [`false`].
+    pub pool: Option<&'a Pool>,
+}
+
+#[derive(Clone, Debug)]
+pub struct AllocatorCreateDesc {
+    pub memory_heaps: Vec<MemoryHeapDesc>,
+    pub memory_types: Vec<MemoryTypeDesc>,
+    /// Granularity at which linear and non-linear allocations must not share a page, must be a
+    /// power of two.
+    pub buffer_image_granularity: u64,
+    /// Limits the total size of the memory blocks in each heap, indexed like
+    /// [`AllocatorCreateDesc::memory_heaps`]. Memory block creation fails with
+    /// [`AllocationError::OutOfBudget`] once a heap would go past its limit. Heaps that are
+    /// [`None`] or past the end of the list are only limited by their size.
+    ///
+    /// ```
+    /// use gpu_allocator::mock::*;
+    /// use gpu_allocator::{AllocationError, MemoryLocation};
+    ///
+    /// const MB: u64 = 1024 * 1024;
+    ///
+    /// // Pretend the 8GB heap is only 512MB large, like on a low-end GPU.
+    /// let mut allocator = Allocator::new(&AllocatorCreateDesc {
+    ///     memory_heaps: vec![MemoryHeapDesc { size: 8 * 1024 * MB }],
+    ///     memory_types: vec![
+    ///         MemoryTypeDesc { heap_index: 0, device_local: true, host_visible: false, host_cached: false },
+    ///     ],
+    ///     buffer_image_granularity: 1,
+    ///     heap_size_limits: vec![Some(512 * MB)],
+    ///     debug_settings: Default::default(),
+    ///     allocation_sizes: Default::default(),
+    ///     sub_allocator: Default::default(),
+    ///     allocation_strategy: Default::default(),
+    ///     empty_block_retention: Default::default(),
+    /// })?;
+    ///
+    /// let desc = AllocationCreateDesc {
+    ///     name: "texture",
+    ///     size: 384 * MB,
+    ///     alignment: 256,
+    ///     location: MemoryLocation::GpuOnly,
+    ///     linear: false,
+    ///     dedicated: false,
+    ///     allocation_strategy: None,
+    ///     pool: None,
+    /// };
+    /// let texture = allocator.allocate(&desc)?;
+    ///
+    /// // The heap has plenty of room left, but not within its limit.
+    /// let err = allocator.allocate(&desc);
+    /// assert!(matches!(err, Err(AllocationError::OutOfBudget)));
+    /// assert_eq!(allocator.memory_creations(), 1);
+    ///
+    /// allocator.free(texture)?;
+    /// let texture = allocator.allocate(&desc)?;
+    /// # allocator.free(texture)?;
+    /// # Ok::<(), AllocationError>(())
+    /// ```
+    pub heap_size_limits: Vec<Option<u64>>,
+    pub debug_settings: AllocatorDebugSettings,
+    /// Sizes of the shared memory blocks, with the upper bound limited to the largest heap.
+    ///
+    /// ```
+    /// use gpu_allocator::mock::*;
+    /// use gpu_allocator::{AllocationError, AllocationSizes, MemoryLocation};
+    ///
+    /// const MB: u64 = 1024 * 1024;
+    ///
+    /// // Small blocks for a small device.
+    /// let mut allocator = Allocator::new(&AllocatorCreateDesc {
+    ///     memory_heaps: vec![MemoryHeapDesc { size: 64 * MB }],
+    ///     memory_types: vec![
+    ///         MemoryTypeDesc { heap_index: 0, device_local: true, host_visible: false, host_cached: false },
+    ///     ],
+    ///     buffer_image_granularity: 1,
+    ///     heap_size_limits: vec![],
+    ///     debug_settings: Default::default(),
+    ///     allocation_sizes: AllocationSizes::new(2 * MB, 2 * MB)
+    ///         .with_memblock_size_bounds(MB, 256 * MB)
+    ///         .with_memblock_size_granularity(MB),
+    ///     sub_allocator: Default::default(),
+    ///     allocation_strategy: Default::default(),
+    ///     empty_block_retention: Default::default(),
+    /// })?;
+    ///
+    /// let buffer = allocator.allocate(&AllocationCreateDesc {
+    ///     name: "buffer",
+    ///     size: 1024,
+    ///     alignment: 256,
+    ///     location: MemoryLocation::GpuOnly,
+    ///     linear: true,
+    ///     dedicated: false,
+    ///     allocation_strategy: None,
+    ///     pool: None,
+    /// })?;
+    /// assert_eq!(allocator.capacity(), 2 * MB);
+    /// # allocator.free(buffer)?;
+    /// # Ok::<(), AllocationError>(())
+    /// ```
+    ///
+    /// With [`AllocationSizes::automatic()`], memory blocks are sized after the heap they are
+    /// created in:
+    ///
+    /// ```
+    /// use gpu_allocator::mock::*;
+    /// use gpu_allocator::{AllocationError, AllocationSizes, MemoryLocation};
+    ///
+    /// const MB: u64 = 1024 * 1024;
+    ///
+    /// let mut allocator = Allocator::new(&AllocatorCreateDesc {
+    ///     memory_heaps: vec![MemoryHeapDesc { size: 8 * 1024 * MB }, MemoryHeapDesc { size: 256 * MB }],
+    ///     memory_types: vec![
+    ///         MemoryTypeDesc { heap_index: 0, device_local: true, host_visible: false, host_cached: false },
+    ///         MemoryTypeDesc { heap_index: 1, device_local: true, host_visible: true, host_cached: false },
+    ///     ],
+    ///     buffer_image_granularity: 1,
+    ///     heap_size_limits: vec![],
+    ///     debug_settings: Default::default(),
+    ///     allocation_sizes: AllocationSizes::automatic(),
+    ///     sub_allocator: Default::default(),
+    ///     allocation_strategy: Default::default(),
+    ///     empty_block_retention: Default::default(),
+    /// })?;
+    ///
+    /// let desc = AllocationCreateDesc {
+    ///     name: "buffer",
+    ///     size: 1024,
+    ///     alignment: 256,
+    ///     location: MemoryLocation::GpuOnly,
+    ///     linear: true,
+    ///     dedicated: false,
+    ///     allocation_strategy: None,
+    ///     pool: None,
+    /// };
+    /// // The large heap gets the default 256MB blocks, the small one gets 32MB blocks.
+    /// let device = allocator.allocate(&desc)?;
+    /// assert_eq!(allocator.capacity(), 256 * MB);
+    /// let upload = allocator.allocate(&AllocationCreateDesc { location: MemoryLocation::CpuToGpu, ..desc })?;
+    /// assert_eq!(upload.memory_type_index(), 1);
+    /// assert_eq!(allocator.capacity(), 256 * MB + 32 * MB);
+    /// # allocator.free(device)?;
+    /// # allocator.free(upload)?;
+    /// # Ok::<(), AllocationError>(())
+    /// ```
+    pub allocation_sizes: AllocationSizes,
+    /// The algorithm used to place allocations inside shared memory blocks.
+    pub sub_allocator: SubAllocatorKind,
+    /// How free regions are picked inside shared memory blocks, unless overridden by
+    /// [`AllocationCreateDesc::allocation_strategy`].
+    pub allocation_strategy: AllocationStrategy,
+    /// How long shared memory blocks are kept around once they no longer hold any allocation.
+    pub empty_block_retention: EmptyBlockRetention,
+}
+
+/// Describes a [`Pool`] to create through [`Allocator::create_pool()`].
+#[derive(Clone, Debug)]
+pub struct PoolCreateDesc {
+    /// Location where the memory blocks of the pool should be stored
+    pub location: MemoryLocation,
+    /// Size in bytes of every memory block of the pool. Larger allocations can't be made from the
+    /// pool.
+    pub block_size: u64,
+    /// Number of memory blocks that are created together with the pool, and kept around when they
+    /// become empty.
+    pub min_block_count: usize,
+    /// Maximum number of memory blocks of the pool, after which allocating from a full pool fails
+    /// with [`AllocationError::OutOfMemory`]. [`None`] lets the pool grow as needed.
+    pub max_block_count: Option<usize>,
+    /// The algorithm used to place allocations inside the memory blocks of the pool.
+    pub sub_allocator: SubAllocatorKind,
+    /// How allocations pick between the free regions of the memory blocks of the pool, unless
+    /// overridden through [`AllocationCreateDesc::allocation_strategy`].
+    pub allocation_strategy: AllocationStrategy,
+}
+
+/// A set of memory blocks of a single memory type, kept apart from the shared memory blocks, see
+/// the pools of the other backends.
+#[derive(Debug)]
+pub struct Pool {
+    memory_type_index: usize,
+    pool_index: usize,
+}
+
+/// Describes a [`LinearPool`] to create through [`Allocator::create_linear_pool()`].
+#[derive(Clone, Debug)]
+pub struct LinearPoolCreateDesc {
+    /// Size in bytes of the single memory block backing the pool.
+    pub size: u64,
+    /// Location where the memory block of the pool should be stored
+    pub location: MemoryLocation,
+}
+
+/// A dedicated memory block for short-lived allocations, see the linear pools of the other
+/// backends.
+#[derive(Debug)]
+pub struct LinearPool {
+    memory_type_index: usize,
+    memory_block_index: usize,
+}
+
+#[derive(Debug)]
+pub struct Allocation {
+    chunk_id: Option<core::num::NonZeroU64>,
+    offset: u64,
+    size: u64,
+    memory_block_index: usize,
+    memory_type_index: usize,
+    memory_id: u64,
+    dedicated_allocation: bool,
+    name: Option<Box<str>>,
+    /// The slice of a [`ThreadCache`] this allocation was made in.
+    #[cfg(feature = "std")]
+    cache_slice: Option<Arc<CacheSlice>>,
+}
+
+impl Allocation {
+    pub fn chunk_id(&self) -> Option<core::num::NonZeroU64> {
+        self.chunk_id
+    }
+
+    /// Returns the index of the memory type this allocation was made from.
+    pub fn memory_type_index(&self) -> usize {
+        self.memory_type_index
+    }
+
+    /// Returns a unique identifier of the mock memory object backing this allocation.
+    ///
+    /// Allocations that share a memory block have the same identifier.
+    pub fn memory_id(&self) -> u64 {
+        self.memory_id
+    }
+
+    /// Returns [`true`] if this allocation is using a dedicated underlying allocation.
+    pub fn is_dedicated(&self) -> bool {
+        self.dedicated_allocation
+    }
+
+    /// Returns the offset of the allocation in its memory object.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the size of the allocation
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.chunk_id.is_none()
+    }
+
+    /// Where this allocation lives in the [`GenericAllocator`], or [`None`] for null allocations.
+    fn key(&self) -> Option<AllocationKey> {
+        // Allocations in a thread cache slice are not known to the memory block.
+        #[cfg(feature = "std")]
+        if self.cache_slice.is_some() {
+            return None;
+        }
+
+        Some((
+            self.memory_type_index,
+            self.memory_block_index,
+            self.chunk_id?,
+        ))
+    }
+}
+
+impl DefragmentationMove<Allocation> {
+    /// Returns [`true`] if this move relocates `allocation`.
+    pub fn is_for(&self, allocation: &Allocation) -> bool {
+        self.moves(allocation.key())
+    }
+}
+
+impl Default for Allocation {
+    fn default() -> Self {
+        Self {
+            chunk_id: None,
+            offset: 0,
+            size: 0,
+            memory_block_index: !0,
+            memory_type_index: !0,
+            memory_id: 0,
+            dedicated_allocation: false,
+            name: None,
+            #[cfg(feature = "std")]
+            cache_slice: None,
+        }
+    }
+}
+
+/// A fake memory object, only accounted for in the usage of its heap.
+#[derive(Debug)]
+pub(crate) struct MockMemory {
+    id: u64,
+    heap_index: usize,
+    size: u64,
+}
+
+struct MockHeap {
+    size: u64,
+    usage: AtomicU64,
+}
+
+pub(crate) struct MockBackend {
+    heaps: Vec<MockHeap>,
+    /// Number of memory creations that were attempted so far.
+    memory_creations: AtomicU64,
+    /// Number of the memory creation that is going to fail, or `0` if none is.
+    failing_memory_creation: AtomicU64,
+}
+
+impl MockBackend {
+    fn fail_memory_creation(&self, nth: u64) {
+        let failing_memory_creation = if nth == 0 {
+            0
+        } else {
+            self.memory_creations.load(Ordering::Relaxed) + nth
+        };
+        self.failing_memory_creation
+            .store(failing_memory_creation, Ordering::Relaxed);
+    }
+
+    fn heap_usage(&self, heap_index: usize) -> u64 {
+        self.heaps[heap_index].usage.load(Ordering::Relaxed)
+    }
+}
+
+impl MemoryBackend for MockBackend {
+    type MemoryTypeProperties = MemoryTypeDesc;
+    type Memory = MockMemory;
+    /// Mock memory is not dedicated to any resource in particular.
+    type DedicatedResource = ();
+
+    fn is_host_visible(properties: &MemoryTypeDesc) -> bool {
+        properties.host_visible
+    }
+
+    fn heap_index(properties: &MemoryTypeDesc) -> usize {
+        properties.heap_index
+    }
+
+    fn atom_size(_properties: &MemoryTypeDesc) -> u64 {
+        1
+    }
+
+    fn create_memory(
+        &self,
+        properties: &MemoryTypeDesc,
+        _memory_type_index: usize,
+        size: u64,
+        _dedicated_resource: Option<()>,
+        _priority: Option<f32>,
+    ) -> Result<MockMemory> {
+        let id = self.memory_creations.fetch_add(1, Ordering::Relaxed) + 1;
+        if id == self.failing_memory_creation.load(Ordering::Relaxed) {
+            return Err(AllocationError::OutOfMemory);
+        }
+
+        // Memory types of a `SyncAllocator` that share a heap create memory concurrently.
+        let heap = &self.heaps[properties.heap_index];
+        let mut usage = heap.usage.load(Ordering::Relaxed);
+        loop {
+            if size > heap.size - usage {
+                return Err(AllocationError::OutOfMemory);
+            }
+            match heap.usage.compare_exchange_weak(
+                usage,
+                usage + size,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break,
+                Err(current) => usage = current,
+            }
+        }
+
+        Ok(MockMemory {
+            id,
+            heap_index: properties.heap_index,
+            size,
+        })
+    }
+
+    fn destroy_memory(&self, memory: MockMemory) {
+        self.heaps[memory.heap_index]
+            .usage
+            .fetch_sub(memory.size, Ordering::Relaxed);
+    }
+}
+
+pub struct Allocator {
+    inner: GenericAllocator<MockBackend>,
+    buffer_image_granularity: u64,
+}
+
+impl fmt::Debug for Allocator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.generate_report().fmt(f)
+    }
+}
+
+impl Allocator {
+    pub fn new(desc: &AllocatorCreateDesc) -> Result<Self> {
+        if !desc.buffer_image_granularity.is_power_of_two() {
+            return Err(AllocationError::InvalidAllocatorCreateDesc(
+                "buffer_image_granularity must be a power of two".into(),
+            ));
+        }
+
+        if let Some(memory_type) = desc
+            .memory_types
+            .iter()
+            .find(|memory_type| memory_type.heap_index >= desc.memory_heaps.len())
+        {
+            return Err(AllocationError::InvalidAllocatorCreateDesc(format!(
+                "Memory type refers to heap {} that does not exist",
+                memory_type.heap_index
+            )));
+        }
+
+        if desc.heap_size_limits.len() > desc.memory_heaps.len() {
+            return Err(AllocationError::InvalidAllocatorCreateDesc(
+                "heap_size_limits has more entries than there are memory heaps".into(),
+            ));
+        }
+
+        let heaps = desc
+            .memory_heaps
+            .iter()
+            .map(|heap| MockHeap {
+                size: heap.size,
+                usage: AtomicU64::new(0),
+            })
+            .collect();
+
+        let allocation_sizes = desc.allocation_sizes.resolve(
+            desc.memory_heaps
+                .iter()
+                .map(|heap| heap.size)
+                .max()
+                .unwrap_or(u64::MAX),
+        )?;
+        let heap_properties = desc
+            .memory_heaps
+            .iter()
+            .enumerate()
+            .map(|(heap_index, heap)| HeapProperties {
+                size: heap.size,
+                size_limit: desc.heap_size_limits.get(heap_index).copied().flatten(),
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Self {
+            inner: GenericAllocator::new(
+                MockBackend {
+                    heaps,
+                    memory_creations: AtomicU64::new(0),
+                    failing_memory_creation: AtomicU64::new(0),
+                },
+                desc.memory_types.iter().copied(),
+                &heap_properties,
+                desc.debug_settings,
+                &allocation_sizes,
+                desc.sub_allocator,
+                desc.allocation_strategy,
+                desc.empty_block_retention,
+            ),
+            buffer_image_granularity: desc.buffer_image_granularity,
+        })
+    }
+
+    pub fn allocate(&mut self, desc: &AllocationCreateDesc<'_>) -> Result<Allocation> {
+        allocate(&mut self.inner, self.buffer_image_granularity, desc)
+    }
+
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn free(&mut self, allocation: Allocation) -> Result<()> {
+        free(&mut self.inner, allocation)
+    }
+
+    /// Sets the algorithm used to place allocations inside the shared memory blocks of the memory
+    /// type at `memory_type_index`, overriding [`AllocatorCreateDesc::sub_allocator`] for it.
+    ///
+    /// Only affects memory blocks that are created after this call.
+    pub fn set_memory_type_sub_allocator(
+        &mut self,
+        memory_type_index: usize,
+        sub_allocator: SubAllocatorKind,
+    ) -> Result<()> {
+        let memory_type = self
+            .inner
+            .memory_types
+            .get_mut(memory_type_index)
+            .ok_or(AllocationError::NoCompatibleMemoryTypeFound)?;
+        memory_type.sub_allocator = sub_allocator;
+
+        Ok(())
+    }
+
+    /// Finds the memory type to place memory blocks for `location` in.
+    fn find_block_memory_type_index(&self, location: MemoryLocation) -> Result<usize> {
+        find_memory_type_index(&self.inner, location, u32::MAX)
+            .ok_or(AllocationError::NoCompatibleMemoryTypeFound)
+    }
+
+    /// Creates a [`Pool`] in a memory type compatible with the given location, along with its
+    /// [`PoolCreateDesc::min_block_count`] memory blocks.
+    ///
+    /// ```
+    /// use gpu_allocator::mock::*;
+    /// use gpu_allocator::{AllocationError, MemoryLocation};
+    ///
+    /// const MB: u64 = 1024 * 1024;
+    ///
+    /// let mut allocator = Allocator::new(&AllocatorCreateDesc {
+    ///     memory_heaps: vec![MemoryHeapDesc { size: 1024 * MB }],
+    ///     memory_types: vec![
+    ///         MemoryTypeDesc { heap_index: 0, device_local: true, host_visible: false, host_cached: false },
+    ///     ],
+    ///     buffer_image_granularity: 1,
+    ///     heap_size_limits: vec![],
+    ///     debug_settings: Default::default(),
+    ///     allocation_sizes: Default::default(),
+    ///     sub_allocator: Default::default(),
+    ///     allocation_strategy: Default::default(),
+    ///     empty_block_retention: Default::default(),
+    /// })?;
+    ///
+    /// // Streaming textures get at most two memory blocks of 64MB.
+    /// let streaming = allocator.create_pool(&PoolCreateDesc {
+    ///     location: MemoryLocation::GpuOnly,
+    ///     block_size: 64 * MB,
+    ///     min_block_count: 1,
+    ///     max_block_count: Some(2),
+    ///     sub_allocator: Default::default(),
+    ///     allocation_strategy: Default::default(),
+    /// })?;
+    /// assert_eq!(allocator.capacity(), 64 * MB);
+    ///
+    /// let desc = AllocationCreateDesc {
+    ///     name: "texture",
+    ///     size: 48 * MB,
+    ///     alignment: 256,
+    ///     location: MemoryLocation::GpuOnly,
+    ///     linear: false,
+    ///     dedicated: false,
+    ///     allocation_strategy: None,
+    ///     pool: Some(&streaming),
+    /// };
+    /// let first = allocator.allocate(&desc)?;
+    /// let second = allocator.allocate(&desc)?;
+    /// assert_ne!(first.memory_id(), second.memory_id());
+    ///
+    /// // The pool is full, while the shared memory blocks are unaffected.
+    /// assert!(matches!(allocator.allocate(&desc), Err(AllocationError::OutOfMemory)));
+    /// let geometry = allocator.allocate(&AllocationCreateDesc { name: "geometry", pool: None, ..desc })?;
+    /// assert_eq!(allocator.capacity(), 2 * 64 * MB + 256 * MB);
+    ///
+    /// allocator.free(first)?;
+    /// allocator.free(second)?;
+    /// allocator.free(geometry)?;
+    /// // Only the minimum number of memory blocks of the pool is kept around.
+    /// assert_eq!(allocator.capacity(), 64 * MB + 256 * MB);
+    ///
+    /// allocator.destroy_pool(streaming)?;
+    /// assert_eq!(allocator.capacity(), 256 * MB);
+    /// # Ok::<(), AllocationError>(())
+    /// ```
+    pub fn create_pool(&mut self, desc: &PoolCreateDesc) -> Result<Pool> {
+        let pool = CustomPool::new(
+            desc.block_size,
+            desc.min_block_count,
+            desc.max_block_count,
+            desc.sub_allocator,
+            desc.allocation_strategy,
+            None,
+        )?;
+        let memory_type_index = self.find_block_memory_type_index(desc.location)?;
+        let pool_index = self.inner.create_pool(memory_type_index, pool)?;
+
+        Ok(Pool {
+            memory_type_index,
+            pool_index,
+        })
+    }
+
+    /// Destroys all memory blocks of a [`Pool`].
+    ///
+    /// All allocations made from the pool must have been freed. Otherwise this fails with
+    /// [`AllocationError::Internal`] and destroys nothing, and the memory blocks of the pool are only
+    /// destroyed together with the allocator.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn destroy_pool(&mut self, pool: Pool) -> Result<()> {
+        self.inner
+            .destroy_pool(pool.memory_type_index, pool.pool_index)
+    }
+
+    /// Creates a [`LinearPool`] holding a single memory block of [`LinearPoolCreateDesc::size`]
+    /// bytes, in a memory type compatible with the given location.
+    pub fn create_linear_pool(&mut self, desc: &LinearPoolCreateDesc) -> Result<LinearPool> {
+        if desc.size == 0 {
+            return Err(AllocationError::InvalidAllocationCreateDesc);
+        }
+
+        let memory_type_index = self.find_block_memory_type_index(desc.location)?;
+
+        let memory_block_index = self
+            .inner
+            .create_linear_pool(memory_type_index, desc.size)?;
+
+        Ok(LinearPool {
+            memory_type_index,
+            memory_block_index,
+        })
+    }
+
+    /// Destroys the memory block of a [`LinearPool`].
+    ///
+    /// Any allocation that is still live in the pool becomes invalid, and must neither be used
+    /// nor freed afterwards.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn destroy_linear_pool(&mut self, pool: LinearPool) -> Result<()> {
+        self.inner
+            .destroy_linear_pool(pool.memory_type_index, pool.memory_block_index)
+    }
+
+    /// Allocates from a [`LinearPool`] instead of the shared memory blocks.
+    ///
+    /// [`AllocationCreateDesc::location`] is ignored in favor of the location of the pool, and
+    /// [`AllocationCreateDesc::dedicated`] must be [`false`]. Returns
+    /// [`AllocationError::OutOfMemory`] when the pool is full, no other memory blocks are created
+    /// to make room.
+    pub fn allocate_from_linear_pool(
+        &mut self,
+        pool: &LinearPool,
+        desc: &AllocationCreateDesc<'_>,
+    ) -> Result<Allocation> {
+        let size = desc.size;
+        let alignment = desc.alignment;
+
+        #[cfg(feature = "std")]
+        let backtrace = self.inner.capture_backtrace();
+
+        self.inner.log_allocation(desc.name, size, alignment);
+
+        if size == 0 || !alignment.is_power_of_two() || desc.dedicated {
+            return Err(AllocationError::InvalidAllocationCreateDesc);
+        }
+
+        let request = AllocationRequest {
+            name: desc.name,
+            size,
+            alignment,
+            allocation_type: if desc.linear {
+                AllocationType::Linear
+            } else {
+                AllocationType::NonLinear
+            },
+            granularity: self.buffer_image_granularity,
+            strategy: desc.allocation_strategy,
+            location: desc.location,
+            priority: None,
+            #[cfg(feature = "std")]
+            backtrace,
+        };
+        let allocation = self.inner.allocate_from_linear_pool(
+            pool.memory_type_index,
+            pool.memory_block_index,
+            &request,
+        )?;
+
+        new_allocation(&self.inner, allocation, desc.name, false)
+    }
+
+    /// Tags all allocations made from `pool` from now on with `frame`.
+    ///
+    /// Frame numbers are expected to increase monotonically.
+    pub fn begin_linear_pool_frame(&mut self, pool: &LinearPool, frame: u64) -> Result<()> {
+        self.inner
+            .begin_linear_pool_frame(pool.memory_type_index, pool.memory_block_index, frame)
+    }
+
+    /// Frees all allocations of `pool` that were made before `frame` was begun.
+    ///
+    /// The released [`Allocation`]s become invalid and must no longer be used. Passing them to
+    /// [`Allocator::free()`] afterwards is allowed and does nothing.
+    pub fn free_linear_pool_frames_before(&mut self, pool: &LinearPool, frame: u64) -> Result<()> {
+        self.inner.free_linear_pool_frames_before(
+            pool.memory_type_index,
+            pool.memory_block_index,
+            frame,
+        )
+    }
+
+    /// Plans moving allocations out of the least used shared memory blocks into other memory
+    /// blocks of the same memory type, so that those memory blocks become empty and are freed
+    /// once the plan is committed.
+    ///
+    /// Memory blocks are only emptied as a whole, and only as long as the total size of the
+    /// moved allocations stays within `max_bytes_to_move`. Room for every move is reserved right
+    /// away: copy the data of each moved allocation to [`DefragmentationMove::destination()`],
+    /// recreate or rebind the resources placed on it, and then pass the plan to
+    /// [`Allocator::commit_defragmentation()`], or give up with
+    /// [`Allocator::cancel_defragmentation()`]. Allocations that are part of the plan must not be
+    /// freed before then, and dropping the plan keeps the reserved room allocated until the
+    /// allocator is dropped.
+    ///
+    /// ```
+    /// use gpu_allocator::mock::*;
+    /// use gpu_allocator::{AllocationError, MemoryLocation};
+    ///
+    /// const MB: u64 = 1024 * 1024;
+    ///
+    /// let mut allocator = Allocator::new(&AllocatorCreateDesc {
+    ///     memory_heaps: vec![MemoryHeapDesc { size: 1024 * MB }],
+    ///     memory_types: vec![
+    ///         MemoryTypeDesc { heap_index: 0, device_local: true, host_visible: false, host_cached: false },
+    ///     ],
+    ///     buffer_image_granularity: 1,
+    ///     heap_size_limits: vec![],
+    ///     debug_settings: Default::default(),
+    ///     allocation_sizes: Default::default(),
+    ///     sub_allocator: Default::default(),
+    ///     allocation_strategy: Default::default(),
+    ///     empty_block_retention: Default::default(),
+    /// })?;
+    ///
+    /// let desc = AllocationCreateDesc {
+    ///     name: "buffer",
+    ///     size: 100 * MB,
+    ///     alignment: 256,
+    ///     location: MemoryLocation::GpuOnly,
+    ///     linear: true,
+    ///     dedicated: false,
+    ///     allocation_strategy: None,
+    ///     pool: None,
+    /// };
+    ///
+    /// // Leave two 256MB memory blocks that are both less than half full.
+    /// let first = allocator.allocate(&desc)?;
+    /// let mut second = allocator.allocate(&desc)?;
+    /// let third = allocator.allocate(&desc)?;
+    /// allocator.free(first)?;
+    /// assert_eq!(allocator.capacity(), 512 * MB);
+    ///
+    /// let plan = allocator.plan_defragmentation(u64::MAX)?;
+    /// assert_eq!(plan.moves().len(), 1);
+    /// assert!(plan.moves()[0].is_for(&second));
+    /// assert_eq!(plan.bytes_to_move(), 100 * MB);
+    ///
+    /// // After copying the data, committing the plan moves `second` next to `third`, and frees
+    /// // the memory block it used to live in.
+    /// allocator.commit_defragmentation(plan, [&mut second])?;
+    /// assert_eq!(second.memory_id(), third.memory_id());
+    /// assert_eq!(allocator.capacity(), 256 * MB);
+    /// # Ok::<(), AllocationError>(())
+    /// ```
+    pub fn plan_defragmentation(
+        &mut self,
+        max_bytes_to_move: u64,
+    ) -> Result<DefragmentationPlan<Allocation>> {
+        let moves = self
+            .inner
+            .plan_defragmentation(max_bytes_to_move, self.buffer_image_granularity)?
+            .into_iter()
+            .map(|planned| {
+                let destination =
+                    new_allocation(&self.inner, planned.destination, &planned.name, false)?;
+                Ok(planned.with_destination(destination))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(DefragmentationPlan { moves })
+    }
+
+    /// Finishes `plan` once the data has been copied: every allocation in `allocations` that is
+    /// moved by the plan is freed and replaced with the destination of its move, which may empty
+    /// and free whole memory blocks.
+    ///
+    /// The moves of allocations that are not passed in are cancelled.
+    pub fn commit_defragmentation<'a>(
+        &mut self,
+        plan: DefragmentationPlan<Allocation>,
+        allocations: impl IntoIterator<Item = &'a mut Allocation>,
+    ) -> Result<()> {
+        self.inner
+            .commit_defragmentation(plan, allocations, Allocation::key)
+    }
+
+    /// Releases the room reserved by `plan`, leaving all allocations where they are.
+    pub fn cancel_defragmentation(&mut self, plan: DefragmentationPlan<Allocation>) -> Result<()> {
+        self.inner.cancel_defragmentation(plan)
+    }
+
+    pub fn rename_allocation(&mut self, allocation: &mut Allocation, name: &str) -> Result<()> {
+        rename_allocation(&mut self.inner, allocation, name)
+    }
+
+    pub fn report_memory_leaks(&self, log_level: Level) {
+        self.inner.report_memory_leaks(log_level);
+    }
+
+    /// Makes the `nth` memory creation from now on fail with [`AllocationError::OutOfMemory`],
+    /// `1` being the next one. Passing `0` cancels a failure that did not happen yet.
+    ///
+    /// Only a single failure can be pending at a time.
+    pub fn fail_memory_creation(&mut self, nth: u64) {
+        self.inner.backend.fail_memory_creation(nth);
+    }
+
+    /// Number of memory creations that were attempted so far, including failed ones.
+    pub fn memory_creations(&self) -> u64 {
+        self.inner.backend.memory_creations.load(Ordering::Relaxed)
+    }
+
+    /// Number of bytes of the heap at `heap_index` that are used by live memory objects.
+    pub fn heap_usage(&self, heap_index: usize) -> u64 {
+        self.inner.backend.heap_usage(heap_index)
+    }
+
+    pub fn generate_report(&self) -> AllocatorReport {
+        self.inner.generate_report()
+    }
+
+    /// Current total capacity of memory blocks allocated on the device, in bytes
+    pub fn capacity(&self) -> u64 {
+        self.inner.capacity()
+    }
+
+    /// Creates shared memory blocks up front in the memory type that allocations for `location`
+    /// are placed in, until that memory type has at least `size` bytes of reserved memory blocks.
+    /// This moves the cost of creating memory blocks to a convenient moment, like a loading
+    /// screen, instead of the first allocations that need them. The size is rounded up to whole
+    /// memory blocks.
+    ///
+    /// Reserved memory blocks are shared by all allocations, and are never destroyed when they
+    /// become empty, not even by [`Allocator::trim()`].
+    ///
+    /// ```
+    /// use gpu_allocator::mock::*;
+    /// use gpu_allocator::{AllocationError, AllocationSizes, MemoryLocation};
+    ///
+    /// const MB: u64 = 1024 * 1024;
+    ///
+    /// let mut allocator = Allocator::new(&AllocatorCreateDesc {
+    ///     memory_heaps: vec![MemoryHeapDesc { size: 1024 * MB }],
+    ///     memory_types: vec![
+    ///         MemoryTypeDesc { heap_index: 0, device_local: true, host_visible: false, host_cached: false },
+    ///     ],
+    ///     buffer_image_granularity: 1,
+    ///     heap_size_limits: vec![],
+    ///     debug_settings: Default::default(),
+    ///     allocation_sizes: AllocationSizes::new(64 * MB, 64 * MB),
+    ///     sub_allocator: Default::default(),
+    ///     allocation_strategy: Default::default(),
+    ///     empty_block_retention: Default::default(),
+    /// })?;
+    ///
+    /// // Rounded up to two memory blocks.
+    /// allocator.reserve(MemoryLocation::GpuOnly, 100 * MB)?;
+    /// assert_eq!(allocator.capacity(), 128 * MB);
+    /// assert_eq!(allocator.memory_creations(), 2);
+    ///
+    /// // Allocations fill the reserved memory blocks without creating new ones.
+    /// let desc = AllocationCreateDesc {
+    ///     name: "texture",
+    ///     size: 48 * MB,
+    ///     alignment: 256,
+    ///     location: MemoryLocation::GpuOnly,
+    ///     linear: false,
+    ///     dedicated: false,
+    ///     allocation_strategy: None,
+    ///     pool: None,
+    /// };
+    /// let first = allocator.allocate(&desc)?;
+    /// let second = allocator.allocate(&desc)?;
+    /// assert_eq!(allocator.memory_creations(), 2);
+    ///
+    /// // Reserved memory blocks outlive their allocations.
+    /// allocator.free(first)?;
+    /// allocator.free(second)?;
+    /// allocator.trim();
+    /// assert_eq!(allocator.capacity(), 128 * MB);
+    ///
+    /// // Reserving again only tops up the reservation.
+    /// allocator.reserve(MemoryLocation::GpuOnly, 128 * MB)?;
+    /// assert_eq!(allocator.memory_creations(), 2);
+    /// # Ok::<(), AllocationError>(())
+    /// ```
+    pub fn reserve(&mut self, location: MemoryLocation, size: u64) -> Result<()> {
+        let memory_type_index = self.find_block_memory_type_index(location)?;
+        self.inner.reserve(memory_type_index, location, size)
+    }
+
+    /// Destroys all shared memory blocks that don't hold any allocation, including the last one of
+    /// every memory type, to give memory back during idle periods. Memory blocks of pools and
+    /// reserved memory blocks are unaffected.
+    ///
+    /// ```
+    /// use gpu_allocator::mock::*;
+    /// use gpu_allocator::{AllocationError, AllocationSizes, EmptyBlockRetention, MemoryLocation};
+    ///
+    /// const MB: u64 = 1024 * 1024;
+    ///
+    /// let mut allocator = Allocator::new(&AllocatorCreateDesc {
+    ///     memory_heaps: vec![MemoryHeapDesc { size: 1024 * MB }],
+    ///     memory_types: vec![
+    ///         MemoryTypeDesc { heap_index: 0, device_local: true, host_visible: false, host_cached: false },
+    ///     ],
+    ///     buffer_image_granularity: 1,
+    ///     heap_size_limits: vec![],
+    ///     debug_settings: Default::default(),
+    ///     allocation_sizes: AllocationSizes::new(16 * MB, 16 * MB),
+    ///     sub_allocator: Default::default(),
+    ///     allocation_strategy: Default::default(),
+    ///     empty_block_retention: EmptyBlockRetention::Frames(2),
+    /// })?;
+    ///
+    /// let desc = AllocationCreateDesc {
+    ///     name: "texture",
+    ///     size: 12 * MB,
+    ///     alignment: 256,
+    ///     location: MemoryLocation::GpuOnly,
+    ///     linear: false,
+    ///     dedicated: false,
+    ///     allocation_strategy: None,
+    ///     pool: None,
+    /// };
+    /// let first = allocator.allocate(&desc)?;
+    /// let second = allocator.allocate(&desc)?;
+    /// assert_eq!(allocator.capacity(), 2 * 16 * MB);
+    ///
+    /// // The empty memory block is kept for two frames, so it can be reused in the meantime.
+    /// allocator.free(second)?;
+    /// allocator.set_current_frame(1);
+    /// assert_eq!(allocator.capacity(), 2 * 16 * MB);
+    /// allocator.set_current_frame(2);
+    /// assert_eq!(allocator.capacity(), 16 * MB);
+    ///
+    /// // Trimming also releases the last memory block once it is empty.
+    /// allocator.free(first)?;
+    /// assert_eq!(allocator.capacity(), 16 * MB);
+    /// allocator.trim();
+    /// assert_eq!(allocator.capacity(), 0);
+    /// # Ok::<(), AllocationError>(())
+    /// ```
+    pub fn trim(&mut self) {
+        self.inner.trim();
+    }
+
+    /// Advances the frame counter of [`EmptyBlockRetention::Frames`] to `frame`, destroying the
+    /// empty shared memory blocks that have been kept around for long enough. `frame` must never
+    /// go down.
+    pub fn set_current_frame(&mut self, frame: u64) {
+        self.inner.set_current_frame(frame);
+    }
+}
+
+/// Serves small allocations for a single thread from slices of the memory blocks of a
+/// [`SyncAllocator`], created through [`SyncAllocator::create_thread_cache()`].
+///
+/// Allocations that are larger than [`ThreadCacheCreateDesc::max_allocation_size`], dedicated or
+/// made from a pool are passed on to the [`SyncAllocator`]. Cached allocations may be freed
+/// through any [`ThreadCache`] of the same allocator or through the [`SyncAllocator`] itself, also
+/// from other threads. Dropping the cache hands unused slices back to the shared memory blocks.
+#[cfg(feature = "std")]
+pub struct ThreadCache<'a> {
+    allocator: &'a SyncAllocator,
+    cache: GenericThreadCache<Allocation>,
+}
+
+#[cfg(feature = "std")]
+impl fmt::Debug for ThreadCache<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ThreadCache").finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "std")]
+impl ThreadCache<'_> {
+    pub fn allocate(&mut self, desc: &AllocationCreateDesc<'_>) -> Result<Allocation> {
+        let allocator = self.allocator;
+        if desc.pool.is_some() || desc.dedicated || desc.location == MemoryLocation::Transient {
+            return allocator.allocate(desc);
+        }
+
+        let mut core = &allocator.inner;
+        let size = desc.size;
+        let alignment = desc.alignment;
+
+        let backtrace = core.debug_settings().capture_backtrace();
+
+        core.debug_settings()
+            .log_allocation(desc.name, size, alignment);
+
+        if size == 0 || !alignment.is_power_of_two() {
+            return Err(AllocationError::InvalidAllocationCreateDesc);
+        }
+
+        let request = AllocationRequest {
+            name: desc.name,
+            size,
+            alignment,
+            allocation_type: if desc.linear {
+                AllocationType::Linear
+            } else {
+                AllocationType::NonLinear
+            },
+            granularity: allocator.buffer_image_granularity,
+            strategy: desc.allocation_strategy,
+            location: desc.location,
+            priority: None,
+            backtrace,
+        };
+
+        let memory_type_index = find_memory_type_index(&core, desc.location, u32::MAX)
+            .ok_or(AllocationError::NoCompatibleMemoryTypeFound)?;
+
+        let cached = self
+            .cache
+            .allocate(core, memory_type_index, &request, |reservation| {
+                new_allocation(&core, reservation, "thread cache slice", false)
+            })?;
+        let Some(cached) = cached else {
+            return allocate_request(&mut core, desc, &request);
+        };
+
+        let allocation = cached.allocation;
+        Ok(Allocation {
+            chunk_id: Some(allocation.chunk_id),
+            offset: allocation.offset,
+            size: allocation.size,
+            memory_block_index: allocation.memory_block_index,
+            memory_type_index: allocation.memory_type_index,
+            memory_id: cached.template.memory_id,
+            dedicated_allocation: false,
+            name: Some(desc.name.into()),
+            cache_slice: Some(cached.slice),
+        })
+    }
+
+    /// Frees an allocation, which may also have been made through another [`ThreadCache`] or
+    /// through the [`SyncAllocator`] directly.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn free(&mut self, allocation: Allocation) -> Result<()> {
+        let (Some(slice), Some(chunk_id)) = (&allocation.cache_slice, allocation.chunk_id) else {
+            return self.allocator.free(allocation);
+        };
+
+        let core = &self.allocator.inner;
+        core.debug_settings().log_free(allocation.name.as_deref());
+        self.cache.free(core, slice, chunk_id)
+    }
+
+    /// Hands the slices without live allocations back to the shared memory blocks. Slices that
+    /// are still in use are no longer allocated from by this cache, and are handed back once
+    /// all their allocations are freed.
+    pub fn flush(&mut self) -> Result<()> {
+        self.cache.flush(&self.allocator.inner)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for ThreadCache<'_> {
+    fn drop(&mut self) {
+        if let Err(err) = self.flush() {
+            log::error!("Failed to flush thread cache: {err}");
+        }
+    }
+}
+
+/// An [`Allocator`] that can be shared between threads, with methods that take `&self`.
+///
+/// Every memory type is locked separately, so threads only wait on each other while they
+/// allocate or free in the same memory type. Pools have to be created before the [`Allocator`]
+/// is converted, after which they can still be allocated from; linear pools and
+/// defragmentation are only available on [`Allocator`].
+///
+/// ```
+/// use gpu_allocator::mock::*;
+/// use gpu_allocator::{AllocationError, MemoryLocation};
+///
+/// const MB: u64 = 1024 * 1024;
+///
+/// let allocator = SyncAllocator::from(Allocator::new(&AllocatorCreateDesc {
+///     memory_heaps: vec![MemoryHeapDesc { size: 1024 * MB }, MemoryHeapDesc { size: 1024 * MB }],
+///     memory_types: vec![
+///         MemoryTypeDesc { heap_index: 0, device_local: true, host_visible: false, host_cached: false },
+///         MemoryTypeDesc { heap_index: 1, device_local: false, host_visible: true, host_cached: true },
+///     ],
+///     buffer_image_granularity: 1,
+///     heap_size_limits: vec![],
+///     debug_settings: Default::default(),
+///     allocation_sizes: Default::default(),
+///     sub_allocator: Default::default(),
+///     allocation_strategy: Default::default(),
+///     empty_block_retention: Default::default(),
+/// })?);
+///
+/// let desc = AllocationCreateDesc {
+///     name: "buffer",
+///     size: MB,
+///     alignment: 256,
+///     location: MemoryLocation::GpuOnly,
+///     linear: true,
+///     dedicated: false,
+///     allocation_strategy: None,
+///     pool: None,
+/// };
+///
+/// // Uploads and readbacks live in different memory types, and don't contend for a lock.
+/// std::thread::scope(|scope| {
+///     for location in [MemoryLocation::CpuToGpu, MemoryLocation::GpuToCpu] {
+///         let allocator = &allocator;
+///         let desc = AllocationCreateDesc { location, ..desc.clone() };
+///         scope.spawn(move || {
+///             let allocations = (0..16)
+///                 .map(|_| allocator.allocate(&desc))
+///                 .collect::<Result<Vec<_>, _>>()?;
+///             allocations.into_iter().try_for_each(|allocation| allocator.free(allocation))
+///         });
+///     }
+/// });
+///
+/// let allocation = allocator.allocate(&desc)?;
+/// assert_eq!(allocator.generate_report().allocations.len(), 1);
+/// allocator.free(allocation)?;
+/// # Ok::<(), AllocationError>(())
+/// ```
+#[cfg(feature = "std")]
+pub struct SyncAllocator {
+    inner: SyncGenericAllocator<MockBackend>,
+    buffer_image_granularity: u64,
+}
+
+#[cfg(feature = "std")]
+impl fmt::Debug for SyncAllocator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.generate_report().fmt(f)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Allocator> for SyncAllocator {
+    fn from(allocator: Allocator) -> Self {
+        Self {
+            inner: allocator.inner.into(),
+            buffer_image_granularity: allocator.buffer_image_granularity,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl SyncAllocator {
+    pub fn new(desc: &AllocatorCreateDesc) -> Result<Self> {
+        Allocator::new(desc).map(Self::from)
+    }
+
+    pub fn allocate(&self, desc: &AllocationCreateDesc<'_>) -> Result<Allocation> {
+        allocate(&mut &self.inner, self.buffer_image_granularity, desc)
+    }
+
+    /// Frees an allocation, which may also have been made through a [`ThreadCache`] of this
+    /// allocator.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn free(&self, allocation: Allocation) -> Result<()> {
+        if let (Some(slice), Some(chunk_id)) = (&allocation.cache_slice, allocation.chunk_id) {
+            self.inner
+                .debug_settings()
+                .log_free(allocation.name.as_deref());
+            return slice.free(&self.inner, chunk_id);
+        }
+
+        free(&mut &self.inner, allocation)
+    }
+
+    /// Renames an allocation. Allocations made through a [`ThreadCache`] only change their own
+    /// name, reports keep listing the slice of the cache they live in.
+    pub fn rename_allocation(&self, allocation: &mut Allocation, name: &str) -> Result<()> {
+        if allocation.cache_slice.is_some() {
+            allocation.name = Some(name.into());
+            return Ok(());
+        }
+
+        rename_allocation(&mut &self.inner, allocation, name)
+    }
+
+    /// Creates a cache that serves small allocations for a single thread from slices of the
+    /// shared memory blocks, without taking the lock of their memory type.
+    ///
+    /// ```
+    /// use gpu_allocator::mock::*;
+    /// use gpu_allocator::{AllocationError, MemoryLocation, ThreadCacheCreateDesc};
+    ///
+    /// const MB: u64 = 1024 * 1024;
+    ///
+    /// let allocator = SyncAllocator::new(&AllocatorCreateDesc {
+    ///     memory_heaps: vec![MemoryHeapDesc { size: 1024 * MB }],
+    ///     memory_types: vec![
+    ///         MemoryTypeDesc { heap_index: 0, device_local: true, host_visible: false, host_cached: false },
+    ///     ],
+    ///     buffer_image_granularity: 1,
+    ///     heap_size_limits: vec![],
+    ///     debug_settings: Default::default(),
+    ///     allocation_sizes: Default::default(),
+    ///     sub_allocator: Default::default(),
+    ///     allocation_strategy: Default::default(),
+    ///     empty_block_retention: Default::default(),
+    /// })?;
+    ///
+    /// let desc = AllocationCreateDesc {
+    ///     name: "constants",
+    ///     size: 256,
+    ///     alignment: 256,
+    ///     location: MemoryLocation::GpuOnly,
+    ///     linear: true,
+    ///     dedicated: false,
+    ///     allocation_strategy: None,
+    ///     pool: None,
+    /// };
+    ///
+    /// let mut cache = allocator.create_thread_cache(&ThreadCacheCreateDesc {
+    ///     slice_size: MB,
+    ///     max_allocation_size: 4096,
+    /// })?;
+    /// let first = cache.allocate(&desc)?;
+    /// let second = cache.allocate(&desc)?;
+    /// // Both allocations live in the single slice the cache reserved.
+    /// assert_eq!(allocator.generate_report().allocations.len(), 1);
+    ///
+    /// // Allocations may be freed on other threads than the one owning the cache.
+    /// std::thread::scope(|scope| scope.spawn(|| allocator.free(second)).join().unwrap())?;
+    /// cache.free(first)?;
+    ///
+    /// // Flushing hands the now unused slice back to the memory block.
+    /// cache.flush()?;
+    /// assert!(allocator.generate_report().allocations.is_empty());
+    /// # Ok::<(), AllocationError>(())
+    /// ```
+    pub fn create_thread_cache(&self, desc: &ThreadCacheCreateDesc) -> Result<ThreadCache<'_>> {
+        Ok(ThreadCache {
+            allocator: self,
+            cache: GenericThreadCache::new(desc)?,
+        })
+    }
+
+    pub fn report_memory_leaks(&self, log_level: Level) {
+        self.inner.report_memory_leaks(log_level);
+    }
+
+    /// See [`Allocator::fail_memory_creation()`].
+    pub fn fail_memory_creation(&self, nth: u64) {
+        self.inner.backend().fail_memory_creation(nth);
+    }
+
+    /// Number of memory creations that were attempted so far, including failed ones.
+    pub fn memory_creations(&self) -> u64 {
+        self.inner
+            .backend()
+            .memory_creations
+            .load(Ordering::Relaxed)
+    }
+
+    /// Number of bytes of the heap at `heap_index` that are used by live memory objects.
+    pub fn heap_usage(&self, heap_index: usize) -> u64 {
+        self.inner.backend().heap_usage(heap_index)
+    }
+
+    /// Generates a report while other threads may keep allocating and freeing, so it is not
+    /// necessarily a consistent snapshot of the whole allocator.
+    pub fn generate_report(&self) -> AllocatorReport {
+        self.inner.generate_report()
+    }
+
+    /// Current total capacity of memory blocks allocated on the device, in bytes
+    pub fn capacity(&self) -> u64 {
+        self.inner.capacity()
+    }
+
+    /// See [`Allocator::trim()`].
+    pub fn trim(&self) {
+        self.inner.trim();
+    }
+
+    /// See [`Allocator::set_current_frame()`].
+    pub fn set_current_frame(&self, frame: u64) {
+        self.inner.set_current_frame(frame);
+    }
+}
+
+/// Allocates through the core of either an [`Allocator`] or a [`SyncAllocator`].
+fn allocate(
+    core: &mut impl AllocatorCore<MockBackend>,
+    buffer_image_granularity: u64,
+    desc: &AllocationCreateDesc<'_>,
+) -> Result<Allocation> {
+    let size = desc.size;
+    let alignment = desc.alignment;
+
+    #[cfg(feature = "std")]
+    let backtrace = core.debug_settings().capture_backtrace();
+
+    core.debug_settings()
+        .log_allocation(desc.name, size, alignment);
+
+    if size == 0 || !alignment.is_power_of_two() {
+        return Err(AllocationError::InvalidAllocationCreateDesc);
+    }
+
+    let request = AllocationRequest {
+        name: desc.name,
+        size,
+        alignment,
+        allocation_type: if desc.linear {
+            AllocationType::Linear
+        } else {
+            AllocationType::NonLinear
+        },
+        granularity: buffer_image_granularity,
+        strategy: desc.allocation_strategy,
+        location: desc.location,
+        priority: None,
+        #[cfg(feature = "std")]
+        backtrace,
+    };
+
+    allocate_request(core, desc, &request)
+}
+
+/// Allocates `request` for `desc`, once it has been validated.
+fn allocate_request(
+    core: &mut impl AllocatorCore<MockBackend>,
+    desc: &AllocationCreateDesc<'_>,
+    request: &AllocationRequest<'_>,
+) -> Result<Allocation> {
+    if let Some(pool) = desc.pool {
+        if desc.dedicated {
+            return Err(AllocationError::InvalidAllocationCreateDesc);
+        }
+
+        let allocation =
+            core.allocate_from_pool(pool.memory_type_index, pool.pool_index, request)?;
+        return new_allocation(core, allocation, desc.name, false);
+    }
+
+    let memory_type_index = find_memory_type_index(core, desc.location, u32::MAX)
+        .ok_or(AllocationError::NoCompatibleMemoryTypeFound)?;
+    let dedicated_resource = desc.dedicated.then_some(());
+
+    let allocation = match core.allocate(memory_type_index, request, dedicated_resource) {
+        // Uploads fall back to the next best memory type when the best one is full, like when
+        // device local host visible memory is limited to a small BAR.
+        Err(AllocationError::OutOfMemory | AllocationError::OutOfBudget)
+            if desc.location == MemoryLocation::CpuToGpu =>
+        {
+            let memory_type_index =
+                find_memory_type_index(core, desc.location, !(1 << memory_type_index))
+                    .ok_or(AllocationError::NoCompatibleMemoryTypeFound)?;
+
+            core.allocate(memory_type_index, request, dedicated_resource)
+        }
+        allocation => allocation,
+    }?;
+
+    new_allocation(core, allocation, desc.name, desc.dedicated)
+}
+
+/// Creates the [`Allocation`] handle for memory allocated through the [`GenericAllocator`].
+fn new_allocation(
+    core: &impl AllocatorCore<MockBackend>,
+    allocation: SubAllocation,
+    name: &str,
+    dedicated_allocation: bool,
+) -> Result<Allocation> {
+    let memory_id = core.with_memory_block(&allocation, |mem_block| mem_block.memory.id)?;
+
+    Ok(Allocation {
+        chunk_id: Some(allocation.chunk_id),
+        offset: allocation.offset,
+        size: allocation.size,
+        memory_block_index: allocation.memory_block_index,
+        memory_type_index: allocation.memory_type_index,
+        memory_id,
+        dedicated_allocation,
+        name: Some(name.into()),
+        #[cfg(feature = "std")]
+        cache_slice: None,
+    })
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn free(core: &mut impl AllocatorCore<MockBackend>, allocation: Allocation) -> Result<()> {
+    core.debug_settings().log_free(allocation.name.as_deref());
+
+    if allocation.is_null() {
+        return Ok(());
+    }
+
+    core.free(
+        allocation.memory_type_index,
+        allocation.memory_block_index,
+        allocation.chunk_id,
+    )
+}
+
+fn rename_allocation(
+    core: &mut impl AllocatorCore<MockBackend>,
+    allocation: &mut Allocation,
+    name: &str,
+) -> Result<()> {
+    allocation.name = Some(name.into());
+
+    if allocation.is_null() {
+        return Ok(());
+    }
+
+    core.rename_allocation(
+        allocation.memory_type_index,
+        allocation.memory_block_index,
+        allocation.chunk_id,
+        name,
+    )
+}
+
+/// Scores every memory type in `memory_type_bits` for `location`, see
+/// [`memory_property_preferences()`], and returns the index of the best one.
+fn find_memory_type_index(
+    core: &impl AllocatorCore<MockBackend>,
+    location: MemoryLocation,
+    memory_type_bits: u32,
+) -> Option<usize> {
+    memory_property_preferences(location).find_memory_type_index(
+        (0..core.memory_type_count()).map(|memory_type_index| {
+            core.memory_type_properties(memory_type_index)
+                .property_flags()
+        }),
+        memory_type_bits,
+    )
+}
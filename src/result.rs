@@ -8,10 +8,14 @@ pub enum AllocationError {
     OutOfMemory,
     #[error("Failed to map memory: {0}")]
     FailedToMap(String),
+    #[error("Out of memory budget")]
+    OutOfBudget,
     #[error("No compatible memory type available")]
     NoCompatibleMemoryTypeFound,
     #[error("Invalid AllocationCreateDesc")]
     InvalidAllocationCreateDesc,
+    #[error("Range is out of the bounds of the allocation")]
+    InvalidRange,
     #[error("Invalid AllocatorCreateDesc {0}")]
     InvalidAllocatorCreateDesc(String),
     #[error("Internal error: {0}")]
@@ -0,0 +1,244 @@
+//! Sub-allocation of arbitrary ranges that are not backed by any device memory.
+//!
+//! A [`VirtualBlock`] runs the same placement logic as the memory blocks of the real allocators,
+//! but only does the bookkeeping of offsets within a range of a given size. What that range
+//! represents is up to the caller: descriptor heaps, bindless index ranges, or a large buffer
+//! that is sub-allocated manually. No device or backend is involved at all.
+//!
+//! ```
+//! use gpu_allocator::virtual_block::*;
+//! use gpu_allocator::AllocationError;
+//!
+//! let mut block = VirtualBlock::new(&VirtualBlockCreateDesc {
+//!     size: 1024,
+//!     debug_settings: Default::default(),
+//!     allocation_strategy: Default::default(),
+//! })?;
+//!
+//! let desc = VirtualAllocationCreateDesc {
+//!     name: "textures",
+//!     size: 100,
+//!     alignment: 64,
+//!     allocation_strategy: None,
+//! };
+//!
+//! let textures = block.allocate(&desc)?;
+//! let mut samplers = block.allocate(&VirtualAllocationCreateDesc { name: "samplers", ..desc })?;
+//! assert_eq!(textures.offset(), 0);
+//! assert_eq!(samplers.offset(), 128);
+//!
+//! block.rename_allocation(&mut samplers, "static samplers")?;
+//! let report = block.generate_report();
+//! assert_eq!(report.allocations.len(), 2);
+//!
+//! let err = block.allocate(&VirtualAllocationCreateDesc { size: 1024, ..desc });
+//! assert!(matches!(err, Err(AllocationError::OutOfMemory)));
+//!
+//! block.free(textures)?;
+//! block.free(samplers)?;
+//! assert!(block.is_empty());
+//! # Ok::<(), AllocationError>(())
+//! ```
+
+use alloc::boxed::Box;
+use core::{fmt, num::NonZeroU64};
+
+use log::Level;
+
+#[cfg(feature = "visualizer")]
+mod visualizer;
+#[cfg(feature = "visualizer")]
+pub use visualizer::VirtualBlockVisualizer;
+
+use crate::{
+    allocator::{FreeListAllocator, SubAllocator},
+    AllocationError, AllocationStrategy, AllocationType, AllocatorDebugSettings, AllocatorReport,
+    MemoryBlockReport, Result,
+};
+
+#[derive(Clone, Debug)]
+pub struct VirtualBlockCreateDesc {
+    /// Size in bytes of the range to sub-allocate from.
+    pub size: u64,
+    pub debug_settings: AllocatorDebugSettings,
+    /// How to pick between multiple free regions that can hold an allocation, unless overridden
+    /// through [`VirtualAllocationCreateDesc::allocation_strategy`].
+    pub allocation_strategy: AllocationStrategy,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct VirtualAllocationCreateDesc<'a> {
+    /// Name of the allocation, for tracking and debugging purposes
+    pub name: &'a str,
+    /// Size in bytes of the allocation.
+    pub size: u64,
+    /// Alignment in bytes of the offset of the allocation, must be a power of two.
+    pub alignment: u64,
+    /// Overrides [`VirtualBlockCreateDesc::allocation_strategy`] for this allocation.
+    pub allocation_strategy: Option<AllocationStrategy>,
+}
+
+/// A range sub-allocated from a [`VirtualBlock`].
+#[derive(Debug, Default)]
+pub struct VirtualAllocation {
+    chunk_id: Option<NonZeroU64>,
+    offset: u64,
+    size: u64,
+    name: Option<Box<str>>,
+}
+
+impl VirtualAllocation {
+    pub fn chunk_id(&self) -> Option<NonZeroU64> {
+        self.chunk_id
+    }
+
+    /// Returns the offset of the allocation in the [`VirtualBlock`].
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the size of the allocation
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.chunk_id.is_none()
+    }
+}
+
+/// Sub-allocates ranges of a fixed size, without any memory behind them.
+pub struct VirtualBlock {
+    sub_allocator: FreeListAllocator,
+    size: u64,
+    debug_settings: AllocatorDebugSettings,
+    allocation_strategy: AllocationStrategy,
+}
+
+impl fmt::Debug for VirtualBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.generate_report().fmt(f)
+    }
+}
+
+impl VirtualBlock {
+    pub fn new(desc: &VirtualBlockCreateDesc) -> Result<Self> {
+        if desc.size == 0 {
+            return Err(AllocationError::InvalidAllocatorCreateDesc(
+                "VirtualBlockCreateDesc::size must not be 0".into(),
+            ));
+        }
+
+        Ok(Self {
+            sub_allocator: FreeListAllocator::new(desc.size),
+            size: desc.size,
+            debug_settings: desc.debug_settings,
+            allocation_strategy: desc.allocation_strategy,
+        })
+    }
+
+    pub fn allocate(
+        &mut self,
+        desc: &VirtualAllocationCreateDesc<'_>,
+    ) -> Result<VirtualAllocation> {
+        self.debug_settings
+            .log_allocation(desc.name, desc.size, desc.alignment);
+
+        if desc.size == 0 || !desc.alignment.is_power_of_two() {
+            return Err(AllocationError::InvalidAllocationCreateDesc);
+        }
+
+        // There are no resources with tiling requirements in a virtual block, so every
+        // allocation is linear and no granularity has to be respected.
+        let (offset, chunk_id) = self.sub_allocator.allocate(
+            desc.size,
+            desc.alignment,
+            AllocationType::Linear,
+            1,
+            desc.allocation_strategy.unwrap_or(self.allocation_strategy),
+            desc.name,
+            self.debug_settings.capture_context(),
+        )?;
+
+        Ok(VirtualAllocation {
+            chunk_id: Some(chunk_id),
+            offset,
+            size: desc.size,
+            name: Some(desc.name.into()),
+        })
+    }
+
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn free(&mut self, allocation: VirtualAllocation) -> Result<()> {
+        self.debug_settings.log_free(allocation.name.as_deref());
+
+        if allocation.is_null() {
+            return Ok(());
+        }
+
+        self.sub_allocator.free(allocation.chunk_id)
+    }
+
+    pub fn rename_allocation(
+        &mut self,
+        allocation: &mut VirtualAllocation,
+        name: &str,
+    ) -> Result<()> {
+        allocation.name = Some(name.into());
+
+        if allocation.is_null() {
+            return Ok(());
+        }
+
+        self.sub_allocator
+            .rename_allocation(allocation.chunk_id, name)
+    }
+
+    /// Logs every live allocation at `log_level`.
+    pub fn report_memory_leaks(&self, log_level: Level) {
+        self.sub_allocator.report_memory_leaks(log_level, 0, 0);
+    }
+
+    /// Generates a report with a single memory block, covering the whole range of this block.
+    pub fn generate_report(&self) -> AllocatorReport {
+        let allocations = self.sub_allocator.report_allocations();
+        let total_allocated_bytes = allocations.iter().map(|report| report.size).sum();
+
+        AllocatorReport {
+            blocks: vec![MemoryBlockReport {
+                size: self.size,
+                allocations: 0..allocations.len(),
+            }],
+            allocations,
+            total_allocated_bytes,
+            total_capacity_bytes: self.size,
+        }
+    }
+
+    /// Size in bytes of the range that is sub-allocated from.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Number of bytes that are currently allocated, including padding for alignment.
+    pub fn allocated(&self) -> u64 {
+        self.sub_allocator.allocated()
+    }
+
+    /// Returns [`true`] if there are no live allocations.
+    pub fn is_empty(&self) -> bool {
+        self.sub_allocator.is_empty()
+    }
+}
+
+impl Drop for VirtualBlock {
+    fn drop(&mut self) {
+        if self.debug_settings.log_leaks_on_shutdown {
+            self.report_memory_leaks(Level::Warn);
+        }
+    }
+}
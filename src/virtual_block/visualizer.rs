@@ -0,0 +1,79 @@
+#![allow(clippy::new_without_default)]
+
+use super::VirtualBlock;
+use crate::{
+    allocator::SubAllocator,
+    visualizer::{
+        render_allocation_reports_ui, AllocationReportVisualizeSettings, ColorScheme,
+        MemoryChunksVisualizationSettings, SubAllocatorVisualizer,
+    },
+};
+
+pub struct VirtualBlockVisualizer {
+    color_scheme: ColorScheme,
+    settings: MemoryChunksVisualizationSettings,
+    breakdown_settings: AllocationReportVisualizeSettings,
+}
+
+impl VirtualBlockVisualizer {
+    pub fn new() -> Self {
+        Self {
+            color_scheme: ColorScheme::default(),
+            settings: Default::default(),
+            breakdown_settings: Default::default(),
+        }
+    }
+
+    pub fn set_color_scheme(&mut self, color_scheme: ColorScheme) {
+        self.color_scheme = color_scheme;
+    }
+
+    pub fn render_visualization_ui(&mut self, ui: &mut egui::Ui, block: &VirtualBlock) {
+        ui.label(format!(
+            "Block size: {} KiB, allocated: {} KiB",
+            block.size / 1024,
+            block.allocated() / 1024
+        ));
+        block.sub_allocator.draw_base_info(ui);
+
+        self.settings
+            .ui(ui, block.debug_settings.store_stack_traces);
+
+        ui.separator();
+
+        block
+            .sub_allocator
+            .draw_visualization(&self.color_scheme, ui, &self.settings);
+    }
+
+    pub fn render_visualization_window(
+        &mut self,
+        ctx: &egui::Context,
+        block: &VirtualBlock,
+        open: &mut bool,
+    ) {
+        egui::Window::new("Virtual Block Visualizer")
+            .default_size([1920.0 * 0.5, 1080.0 * 0.5])
+            .open(open)
+            .show(ctx, |ui| self.render_visualization_ui(ui, block));
+    }
+
+    pub fn render_breakdown_ui(&mut self, ui: &mut egui::Ui, block: &VirtualBlock) {
+        render_allocation_reports_ui(
+            ui,
+            &mut self.breakdown_settings,
+            block.sub_allocator.report_allocations(),
+        );
+    }
+
+    pub fn render_breakdown_window(
+        &mut self,
+        ctx: &egui::Context,
+        block: &VirtualBlock,
+        open: &mut bool,
+    ) {
+        egui::Window::new("Virtual Block Breakdown")
+            .open(open)
+            .show(ctx, |ui| self.render_breakdown_ui(ui, block));
+    }
+}
@@ -3,9 +3,11 @@ use std::backtrace::BacktraceStatus;
 use egui::{Color32, DragValue, Rect, ScrollArea, Sense, Ui, Vec2};
 
 use super::ColorScheme;
-use crate::allocator::free_list_allocator::MemoryChunk;
+use crate::allocator::{free_list_allocator::MemoryChunk, AllocationContext, AllocationType};
 
-pub(crate) struct MemoryChunksVisualizationSettings {
+/// Settings of the memory chunk view, passed to
+/// [`SubAllocatorVisualizer::draw_visualization()`][super::SubAllocatorVisualizer::draw_visualization()].
+pub struct MemoryChunksVisualizationSettings {
     pub width_in_bytes: u64,
     pub show_backtraces: bool,
 }
@@ -114,9 +116,9 @@ pub(crate) fn render_memory_chunks_ui<'a>(
                                 ui.label(format!("name: {name}"));
                             }
                             if settings.show_backtraces
-                                && chunk.backtrace.status() == BacktraceStatus::Captured
+                                && chunk.context.backtrace.status() == BacktraceStatus::Captured
                             {
-                                ui.label(chunk.backtrace.to_string());
+                                ui.label(chunk.context.backtrace.to_string());
                             }
                         });
                     }
@@ -130,3 +132,49 @@ pub(crate) fn render_memory_chunks_ui<'a>(
         }
     });
 }
+
+/// Like [`render_memory_chunks_ui()`], for sub-allocators that don't keep track of every free
+/// region of their memory block: the gaps between the given chunks are drawn as free memory.
+pub(crate) fn render_sparse_memory_chunks_ui<'a>(
+    ui: &mut Ui,
+    color_scheme: &ColorScheme,
+    settings: &MemoryChunksVisualizationSettings,
+    total_size_in_bytes: u64,
+    data: impl IntoIterator<Item = &'a MemoryChunk>,
+) {
+    let mut data = data.into_iter().collect::<Vec<_>>();
+    data.sort_by_key(|chunk| chunk.offset);
+
+    let free_chunk = |offset: u64, size: u64| MemoryChunk {
+        chunk_id: core::num::NonZeroU64::MAX,
+        size,
+        offset,
+        allocation_type: AllocationType::Free,
+        alignment: 1,
+        padding: 0,
+        name: None,
+        context: AllocationContext::default(),
+        prev: None,
+        next: None,
+    };
+
+    let mut free_chunks = vec![];
+    let mut end = 0;
+    for chunk in &data {
+        if chunk.offset > end {
+            free_chunks.push(free_chunk(end, chunk.offset - end));
+        }
+        end = chunk.offset + chunk.size;
+    }
+    if total_size_in_bytes > end {
+        free_chunks.push(free_chunk(end, total_size_in_bytes - end));
+    }
+
+    render_memory_chunks_ui(
+        ui,
+        color_scheme,
+        settings,
+        total_size_in_bytes,
+        data.into_iter().chain(&free_chunks),
+    );
+}
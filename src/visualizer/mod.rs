@@ -4,9 +4,10 @@ mod allocation_reports;
 mod memory_chunks;
 
 pub(crate) use allocation_reports::*;
+pub use memory_chunks::MemoryChunksVisualizationSettings;
 pub(crate) use memory_chunks::*;
 
-use crate::allocator::AllocationType;
+use crate::allocator::{AllocationType, SubAllocator};
 
 pub const DEFAULT_COLOR_ALLOCATION_TYPE_FREE: Color32 = Color32::from_rgb(159, 159, 159); // gray
 pub const DEFAULT_COLOR_ALLOCATION_TYPE_LINEAR: Color32 = Color32::from_rgb(91, 206, 250); // blue
@@ -30,7 +31,7 @@ impl Default for ColorScheme {
 }
 
 impl ColorScheme {
-    pub(crate) fn get_allocation_type_color(&self, allocation_type: AllocationType) -> Color32 {
+    pub fn get_allocation_type_color(&self, allocation_type: AllocationType) -> Color32 {
         match allocation_type {
             AllocationType::Free => self.free_color,
             AllocationType::Linear => self.linear_color,
@@ -39,13 +40,19 @@ impl ColorScheme {
     }
 }
 
-pub(crate) trait SubAllocatorVisualizer {
+/// Visualizer hooks of a [`SubAllocator`], used by the memory block
+/// windows of the backend visualizers.
+pub trait SubAllocatorVisualizer {
+    /// Returns [`true`] if [`Self::draw_visualization()`] draws anything.
     fn supports_visualization(&self) -> bool {
         false
     }
+    /// Draws general information about the sub-allocator state, such as the number of
+    /// allocations.
     fn draw_base_info(&self, ui: &mut Ui) {
         ui.label("No sub allocator information available");
     }
+    /// Draws the layout of the allocations in the memory block.
     fn draw_visualization(
         &self,
         _color_scheme: &ColorScheme,
@@ -54,3 +61,32 @@ pub(crate) trait SubAllocatorVisualizer {
     ) {
     }
 }
+
+/// Lets the backend visualizers draw any memory block, through the hooks returned from
+/// [`SubAllocator::visualizer()`].
+impl SubAllocatorVisualizer for dyn SubAllocator {
+    fn supports_visualization(&self) -> bool {
+        self.visualizer()
+            .is_some_and(SubAllocatorVisualizer::supports_visualization)
+    }
+
+    fn draw_base_info(&self, ui: &mut Ui) {
+        match self.visualizer() {
+            Some(visualizer) => visualizer.draw_base_info(ui),
+            None => {
+                ui.label("No sub allocator information available");
+            }
+        }
+    }
+
+    fn draw_visualization(
+        &self,
+        color_scheme: &ColorScheme,
+        ui: &mut Ui,
+        settings: &MemoryChunksVisualizationSettings,
+    ) {
+        if let Some(visualizer) = self.visualizer() {
+            visualizer.draw_visualization(color_scheme, ui, settings);
+        }
+    }
+}
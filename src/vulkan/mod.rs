@@ -2,8 +2,6 @@
 use alloc::sync::Arc;
 use alloc::{borrow::ToOwned, boxed::Box, string::ToString, vec::Vec};
 use core::{fmt, marker::PhantomData};
-#[cfg(feature = "std")]
-use std::backtrace::Backtrace;
 
 use ash::vk;
 use log::{debug, Level};
@@ -15,10 +13,18 @@ pub use visualizer::AllocatorVisualizer;
 
 use crate::{
     allocator::{
-        AllocationType, AllocatorReport, DedicatedBlockAllocator, FreeListAllocator,
-        MemoryBlockReport, SubAllocator,
+        align_down, align_up, generic_allocator::is_valid_priority, AllocationKey,
+        AllocationRequest, AllocationType, AllocatorCore, AllocatorReport, CustomPool,
+        GenericAllocator, HeapProperties, MemoryBackend, MemoryPropertyPreferences, SubAllocation,
     },
-    AllocationError, AllocationSizes, AllocatorDebugSettings, MemoryLocation, Result,
+    AllocationError, AllocationSizes, AllocationStrategy, AllocatorDebugSettings,
+    DefragmentationMove, DefragmentationPlan, EmptyBlockRetention, MemoryLocation, Result,
+    SubAllocatorKind,
+};
+#[cfg(feature = "std")]
+use crate::{
+    allocator::{CacheSlice, GenericThreadCache, SyncGenericAllocator},
+    ThreadCacheCreateDesc,
 };
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -43,8 +49,300 @@ pub struct AllocationCreateDesc<'a> {
     pub location: MemoryLocation,
     /// If the resource is linear (buffer / linear texture) or a regular (tiled) texture.
     pub linear: bool,
-    /// Determines how this allocation should be managed.
+    /// Determines how this allocation should be managed. [`Self::from_buffer2()`] and
+    /// [`Self::from_image2()`] pick a dedicated allocation when the driver asks for one.
     pub allocation_scheme: AllocationScheme,
+    /// Overrides [`AllocatorCreateDesc::allocation_strategy`] for this allocation.
+    pub allocation_strategy: Option<AllocationStrategy>,
+    /// Allocates from the memory blocks of this [`Pool`] instead of the shared memory blocks, in
+    /// which case [`Self::location`] is ignored and [`Self::allocation_scheme`] must be
+    /// [`AllocationScheme::GpuAllocatorManaged`].
+    pub pool: Option<&'a Pool>,
+    /// Priority between `0.0` and `1.0` of the memory of this allocation relative to other
+    /// memory when the device is oversubscribed, if [`AllocatorCreateDesc::memory_priority`] is
+    /// set. Only applies when the allocation gets a memory block of its own, shared memory blocks
+    /// keep the default priority of `0.5`.
+    pub priority: Option<f32>,
+    /// Narrows down the memory types that this allocation may be placed in, on top of what
+    /// [`Self::location`] asks for. Ignored when allocating from a [`Pool`].
+    pub memory_type_preferences: MemoryTypePreferences,
+}
+
+/// Memory property flags and memory types to pick the memory type of an allocation by, on top
+/// of the ones that follow from its [`MemoryLocation`].
+///
+/// Every memory type that is in [`Self::memory_type_bits`] and the memory requirements, and has
+/// all required flags, is a candidate. The candidate missing the fewest preferred flags and
+/// having the fewest flags that are not preferred is picked, the one with the lowest index when
+/// several candidates are equally good.
+#[derive(Clone, Copy, Debug)]
+pub struct MemoryTypePreferences {
+    /// Flags that the memory type must have.
+    pub required_flags: vk::MemoryPropertyFlags,
+    /// Flags that the memory type should have.
+    pub preferred_flags: vk::MemoryPropertyFlags,
+    /// Flags that the memory type should not have, like
+    /// [`vk::MemoryPropertyFlags::DEVICE_UNCACHED_AMD`].
+    pub not_preferred_flags: vk::MemoryPropertyFlags,
+    /// Bitmask of the memory types that may be used, combined with
+    /// [`vk::MemoryRequirements::memory_type_bits`].
+    pub memory_type_bits: u32,
+    /// Allows host visible memory types that are not [`vk::MemoryPropertyFlags::HOST_COHERENT`]
+    /// for [`MemoryLocation::CpuToGpu`] and [`MemoryLocation::GpuToCpu`], and makes
+    /// [`MemoryLocation::GpuToCpu`] prefer host cached memory over host coherent memory, as it
+    /// is much faster to read from. Such memory has to be flushed and invalidated explicitly
+    /// through [`Allocator::flush_allocation()`] and [`Allocator::invalidate_allocation()`].
+    pub allow_non_coherent: bool,
+}
+
+impl Default for MemoryTypePreferences {
+    fn default() -> Self {
+        Self {
+            required_flags: vk::MemoryPropertyFlags::empty(),
+            preferred_flags: vk::MemoryPropertyFlags::empty(),
+            not_preferred_flags: vk::MemoryPropertyFlags::empty(),
+            memory_type_bits: u32::MAX,
+            allow_non_coherent: false,
+        }
+    }
+}
+
+impl MemoryTypePreferences {
+    /// Adds the flags that follow from `location` to these preferences.
+    fn with_location(&self, location: MemoryLocation) -> Self {
+        Self {
+            required_flags: self.required_flags
+                | required_memory_property_flags(location, self.allow_non_coherent),
+            preferred_flags: self.preferred_flags
+                | preferred_memory_property_flags(location, self.allow_non_coherent),
+            not_preferred_flags: self.not_preferred_flags
+                | not_preferred_memory_property_flags(location),
+            memory_type_bits: self.memory_type_bits,
+            allow_non_coherent: self.allow_non_coherent,
+        }
+    }
+
+    /// Returns the index of the best memory type in `memory_type_bits` for these preferences,
+    /// given the property flags of all memory types in order.
+    fn find_memory_type_index(
+        &self,
+        memory_type_flags: impl IntoIterator<Item = vk::MemoryPropertyFlags>,
+        memory_type_bits: u32,
+    ) -> Option<usize> {
+        MemoryPropertyPreferences {
+            required: self.required_flags.as_raw(),
+            preferred: self.preferred_flags.as_raw(),
+            not_preferred: self.not_preferred_flags.as_raw(),
+        }
+        .find_memory_type_index(
+            memory_type_flags
+                .into_iter()
+                .map(vk::MemoryPropertyFlags::as_raw),
+            memory_type_bits & self.memory_type_bits,
+        )
+    }
+}
+
+impl<'a> AllocationCreateDesc<'a> {
+    /// Helper function to construct an [`AllocationCreateDesc`] for an existing [`vk::Buffer`]
+    /// utilizing [`vkGetBufferMemoryRequirements()`]. The memory is sub-allocated, see
+    /// [`Self::from_buffer2()`] to also respect the dedicated allocation requirements of the
+    /// driver.
+    ///
+    /// [`vkGetBufferMemoryRequirements()`]: https://registry.khronos.org/vulkan/specs/1.3-extensions/man/html/vkGetBufferMemoryRequirements.html
+    pub fn from_buffer(
+        device: &ash::Device,
+        buffer: vk::Buffer,
+        name: &'a str,
+        location: MemoryLocation,
+    ) -> Self {
+        Self {
+            name,
+            // SAFETY: `device` is a valid device handle and `buffer` was created from it.
+            requirements: unsafe { device.get_buffer_memory_requirements(buffer) },
+            location,
+            linear: true,
+            allocation_scheme: AllocationScheme::GpuAllocatorManaged,
+            allocation_strategy: None,
+            pool: None,
+            priority: None,
+            memory_type_preferences: MemoryTypePreferences::default(),
+        }
+    }
+
+    /// Helper function to construct an [`AllocationCreateDesc`] for an existing [`vk::Buffer`]
+    /// utilizing [`vkGetBufferMemoryRequirements2()`]. A dedicated allocation is picked when the
+    /// driver prefers or requires one for this buffer, as reported by
+    /// [`vk::MemoryDedicatedRequirements`]. Requires Vulkan 1.1.
+    ///
+    /// [`vkGetBufferMemoryRequirements2()`]: https://registry.khronos.org/vulkan/specs/1.3-extensions/man/html/vkGetBufferMemoryRequirements2.html
+    pub fn from_buffer2(
+        device: &ash::Device,
+        buffer: vk::Buffer,
+        name: &'a str,
+        location: MemoryLocation,
+    ) -> Self {
+        let info = vk::BufferMemoryRequirementsInfo2::default().buffer(buffer);
+        let mut dedicated_requirements = vk::MemoryDedicatedRequirements::default();
+        let mut requirements =
+            vk::MemoryRequirements2::default().push_next(&mut dedicated_requirements);
+        // SAFETY: `device` is a valid device handle and `buffer` was created from it.
+        unsafe { device.get_buffer_memory_requirements2(&info, &mut requirements) };
+        let requirements = requirements.memory_requirements;
+
+        let dedicated = dedicated_requirements.prefers_dedicated_allocation == vk::TRUE
+            || dedicated_requirements.requires_dedicated_allocation == vk::TRUE;
+
+        Self {
+            name,
+            requirements,
+            location,
+            linear: true,
+            allocation_scheme: if dedicated {
+                AllocationScheme::DedicatedBuffer(buffer)
+            } else {
+                AllocationScheme::GpuAllocatorManaged
+            },
+            allocation_strategy: None,
+            pool: None,
+            priority: None,
+            memory_type_preferences: MemoryTypePreferences::default(),
+        }
+    }
+
+    /// Helper function to construct an [`AllocationCreateDesc`] for an existing [`vk::Image`]
+    /// utilizing [`vkGetImageMemoryRequirements()`]. `linear` should be set for images with
+    /// [`vk::ImageTiling::LINEAR`]. The memory is sub-allocated, see [`Self::from_image2()`] to
+    /// also respect the dedicated allocation requirements of the driver.
+    ///
+    /// [`vkGetImageMemoryRequirements()`]: https://registry.khronos.org/vulkan/specs/1.3-extensions/man/html/vkGetImageMemoryRequirements.html
+    pub fn from_image(
+        device: &ash::Device,
+        image: vk::Image,
+        linear: bool,
+        name: &'a str,
+        location: MemoryLocation,
+    ) -> Self {
+        Self {
+            name,
+            // SAFETY: `device` is a valid device handle and `image` was created from it.
+            requirements: unsafe { device.get_image_memory_requirements(image) },
+            location,
+            linear,
+            allocation_scheme: AllocationScheme::GpuAllocatorManaged,
+            allocation_strategy: None,
+            pool: None,
+            priority: None,
+            memory_type_preferences: MemoryTypePreferences::default(),
+        }
+    }
+
+    /// Helper function to construct an [`AllocationCreateDesc`] for an existing [`vk::Image`]
+    /// utilizing [`vkGetImageMemoryRequirements2()`]. A dedicated allocation is picked when the
+    /// driver prefers or requires one for this image, as reported by
+    /// [`vk::MemoryDedicatedRequirements`]. `linear` should be set for images with
+    /// [`vk::ImageTiling::LINEAR`]. Requires Vulkan 1.1.
+    ///
+    /// [`vkGetImageMemoryRequirements2()`]: https://registry.khronos.org/vulkan/specs/1.3-extensions/man/html/vkGetImageMemoryRequirements2.html
+    pub fn from_image2(
+        device: &ash::Device,
+        image: vk::Image,
+        linear: bool,
+        name: &'a str,
+        location: MemoryLocation,
+    ) -> Self {
+        let info = vk::ImageMemoryRequirementsInfo2::default().image(image);
+        let mut dedicated_requirements = vk::MemoryDedicatedRequirements::default();
+        let mut requirements =
+            vk::MemoryRequirements2::default().push_next(&mut dedicated_requirements);
+        // SAFETY: `device` is a valid device handle and `image` was created from it.
+        unsafe { device.get_image_memory_requirements2(&info, &mut requirements) };
+        let requirements = requirements.memory_requirements;
+
+        let dedicated = dedicated_requirements.prefers_dedicated_allocation == vk::TRUE
+            || dedicated_requirements.requires_dedicated_allocation == vk::TRUE;
+
+        Self {
+            name,
+            requirements,
+            location,
+            linear,
+            allocation_scheme: if dedicated {
+                AllocationScheme::DedicatedImage(image)
+            } else {
+                AllocationScheme::GpuAllocatorManaged
+            },
+            allocation_strategy: None,
+            pool: None,
+            priority: None,
+            memory_type_preferences: MemoryTypePreferences::default(),
+        }
+    }
+}
+
+/// Describes a [`Pool`] to create through [`Allocator::create_pool()`].
+#[derive(Clone, Debug)]
+pub struct PoolCreateDesc {
+    /// Location where the memory blocks of the pool should be stored
+    pub location: MemoryLocation,
+    /// The memory types the pool may be placed in, as reported in
+    /// [`vk::MemoryRequirements::memory_type_bits`] for the resources that will be allocated from
+    /// it.
+    pub memory_type_bits: u32,
+    /// Size in bytes of every memory block of the pool. Larger allocations can't be made from the
+    /// pool.
+    pub block_size: u64,
+    /// Number of memory blocks that are created together with the pool, and kept around when they
+    /// become empty.
+    pub min_block_count: usize,
+    /// Maximum number of memory blocks of the pool, after which allocating from a full pool fails
+    /// with [`AllocationError::OutOfMemory`]. [`None`] lets the pool grow as needed.
+    pub max_block_count: Option<usize>,
+    /// The algorithm used to place allocations inside the memory blocks of the pool.
+    pub sub_allocator: SubAllocatorKind,
+    /// How allocations pick between the free regions of the memory blocks of the pool, unless
+    /// overridden through [`AllocationCreateDesc::allocation_strategy`].
+    pub allocation_strategy: AllocationStrategy,
+    /// Priority between `0.0` and `1.0` of the memory blocks of the pool, if
+    /// [`AllocatorCreateDesc::memory_priority`] is set.
+    pub priority: Option<f32>,
+}
+
+/// A set of memory blocks of a single memory type, kept apart from the memory blocks that are
+/// shared by all other allocations.
+///
+/// Allocations are made from a pool by setting [`AllocationCreateDesc::pool`], which allows
+/// isolating the allocations of a subsystem and putting a hard limit on the memory it uses.
+#[derive(Debug)]
+pub struct Pool {
+    memory_type_index: usize,
+    pool_index: usize,
+}
+
+/// Describes a [`LinearPool`] to create through [`Allocator::create_linear_pool()`].
+#[derive(Clone, Debug)]
+pub struct LinearPoolCreateDesc {
+    /// Size in bytes of the single memory block backing the pool.
+    pub size: u64,
+    /// Location where the memory block of the pool should be stored
+    pub location: MemoryLocation,
+    /// The memory types the pool may be placed in, as reported in
+    /// [`vk::MemoryRequirements::memory_type_bits`] for the resources that will be allocated from
+    /// it.
+    pub memory_type_bits: u32,
+}
+
+/// A dedicated memory block for short-lived allocations, such as per-frame upload and constant
+/// buffers.
+///
+/// Allocations are bumped through the block as a ring buffer, see [`SubAllocatorKind::Linear`].
+/// Each allocation is tagged with the frame set through [`Allocator::begin_linear_pool_frame()`],
+/// so that all allocations of completed frames can be released at once with
+/// [`Allocator::free_linear_pool_frames_before()`] instead of freeing them one by one.
+#[derive(Debug)]
+pub struct LinearPool {
+    memory_type_index: usize,
+    memory_block_index: usize,
 }
 
 /// Wrapper type to only mark a raw pointer [`Send`] + [`Sync`] without having to
@@ -66,7 +364,46 @@ pub struct AllocatorCreateDesc {
     pub physical_device: vk::PhysicalDevice,
     pub debug_settings: AllocatorDebugSettings,
     pub buffer_device_address: bool,
+    /// Set if `VK_EXT_memory_budget` is enabled on the device, which also requires Vulkan 1.1 or
+    /// `VK_KHR_get_physical_device_properties2` on the instance. Memory blocks are then only
+    /// created as long as they fit in the budget of their heap, and [`Allocator::heap_budgets()`]
+    /// reports the budgets of the heaps.
+    pub memory_budget: bool,
+    /// Set if the device supports Vulkan 1.1, so that [`Allocator::create_buffer()`] and
+    /// [`Allocator::create_image()`] query the memory requirements through
+    /// [`AllocationCreateDesc::from_buffer2()`] and [`AllocationCreateDesc::from_image2()`], and
+    /// dedicate memory to resources that the driver prefers or requires dedicated memory for.
+    pub get_memory_requirements2: bool,
+    /// Set if `VK_EXT_memory_priority` is enabled on the device, to pass
+    /// [`AllocationCreateDesc::priority`] and [`PoolCreateDesc::priority`] on to the driver.
+    pub memory_priority: bool,
+    /// Set if `VK_EXT_pageable_device_local_memory` is enabled on the device, which allows
+    /// changing priorities through [`Allocator::set_allocation_priority()`] and
+    /// [`Allocator::set_pool_priority()`].
+    pub pageable_device_local_memory: bool,
+    /// Limits the total size of the memory blocks in each heap, indexed like the heaps of the
+    /// physical device. Memory block creation fails with [`AllocationError::OutOfBudget`] once a
+    /// heap would go past its limit. Heaps that are [`None`] or past the end of the list are only
+    /// limited by the driver.
+    pub heap_size_limits: Vec<Option<u64>>,
     pub allocation_sizes: AllocationSizes,
+    /// The algorithm used to place allocations inside shared memory blocks.
+    pub sub_allocator: SubAllocatorKind,
+    /// How free regions are picked inside shared memory blocks, unless overridden by
+    /// [`AllocationCreateDesc::allocation_strategy`].
+    pub allocation_strategy: AllocationStrategy,
+    /// How long shared memory blocks are kept around once they no longer hold any allocation.
+    pub empty_block_retention: EmptyBlockRetention,
+}
+
+/// Usage and budget of a memory heap, as returned by [`Allocator::heap_budgets()`].
+#[derive(Clone, Copy, Debug)]
+pub struct HeapBudget {
+    /// Bytes of the heap that are in use by the whole process.
+    pub usage: u64,
+    /// Bytes of the heap that the process can use without failing allocations or degrading
+    /// performance.
+    pub budget: u64,
 }
 
 /// A piece of allocated memory.
@@ -165,6 +502,9 @@ pub struct Allocation {
     dedicated_allocation: bool,
     memory_properties: vk::MemoryPropertyFlags,
     name: Option<Box<str>>,
+    /// The slice of a [`ThreadCache`] this allocation was made in.
+    #[cfg(feature = "std")]
+    cache_slice: Option<Arc<CacheSlice>>,
 }
 
 impl Allocation {
@@ -209,6 +549,10 @@ impl Allocation {
     }
 
     ///Returns the [`vk::MemoryPropertyFlags`] of this allocation.
+    ///
+    /// Host visible memory without [`vk::MemoryPropertyFlags::HOST_COHERENT`] must be flushed
+    /// through [`Allocator::flush_allocation()`] after writing to it, and invalidated through
+    /// [`Allocator::invalidate_allocation()`] before reading from it.
     pub fn memory_properties(&self) -> vk::MemoryPropertyFlags {
         self.memory_properties
     }
@@ -270,6 +614,28 @@ impl Allocation {
     pub fn is_null(&self) -> bool {
         self.chunk_id.is_none()
     }
+
+    /// Where this allocation lives in the [`GenericAllocator`], or [`None`] for null allocations.
+    fn key(&self) -> Option<AllocationKey> {
+        // Allocations in a thread cache slice are not known to the memory block.
+        #[cfg(feature = "std")]
+        if self.cache_slice.is_some() {
+            return None;
+        }
+
+        Some((
+            self.memory_type_index,
+            self.memory_block_index,
+            self.chunk_id?,
+        ))
+    }
+}
+
+impl DefragmentationMove<Allocation> {
+    /// Returns [`true`] if this move relocates `allocation`.
+    pub fn is_for(&self, allocation: &Allocation) -> bool {
+        self.moves(allocation.key())
+    }
 }
 
 impl Default for Allocation {
@@ -285,6 +651,8 @@ impl Default for Allocation {
             memory_properties: vk::MemoryPropertyFlags::empty(),
             name: None,
             dedicated_allocation: false,
+            #[cfg(feature = "std")]
+            cache_slice: None,
         }
     }
 }
@@ -340,35 +708,128 @@ unsafe impl presser::Slab for Allocation {
     }
 }
 
+/// Properties of a Vulkan memory type.
+#[derive(Clone, Debug)]
+pub(crate) struct MemoryTypeProperties {
+    pub(crate) memory_properties: vk::MemoryPropertyFlags,
+    pub(crate) heap_index: usize,
+    /// `nonCoherentAtomSize` of the device for host visible memory types that are not host
+    /// coherent, `1` for all other memory types.
+    pub(crate) non_coherent_atom_size: u64,
+}
+
+/// A [`vk::DeviceMemory`] object backing a memory block, persistently mapped if it is host
+/// visible.
 #[derive(Debug)]
-pub(crate) struct MemoryBlock {
+pub(crate) struct DeviceMemory {
     pub(crate) device_memory: vk::DeviceMemory,
-    pub(crate) size: u64,
     pub(crate) mapped_ptr: Option<SendSyncPtr>,
-    pub(crate) sub_allocator: Box<dyn SubAllocator>,
-    #[cfg(feature = "visualizer")]
-    pub(crate) dedicated_allocation: bool,
 }
 
-impl MemoryBlock {
-    fn new(
-        device: &ash::Device,
+pub(crate) struct VulkanBackend {
+    device: ash::Device,
+    buffer_device_address: bool,
+    /// Set when `VK_EXT_memory_budget` is enabled, to query the budgets of the heaps.
+    memory_budget: Option<(ash::Instance, vk::PhysicalDevice)>,
+    get_memory_requirements2: bool,
+    memory_priority: bool,
+    pageable_device_local_memory: Option<ash::ext::pageable_device_local_memory::Device>,
+}
+
+impl VulkanBackend {
+    /// Queries the usage and budget of every heap through `VK_EXT_memory_budget`, or returns
+    /// [`None`] if it is not enabled.
+    fn query_heap_budgets(&self) -> Option<Vec<HeapBudget>> {
+        let (instance, physical_device) = self.memory_budget.as_ref()?;
+
+        let mut budget_properties = vk::PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+        let mut memory_properties =
+            vk::PhysicalDeviceMemoryProperties2::default().push_next(&mut budget_properties);
+        unsafe {
+            instance
+                .get_physical_device_memory_properties2(*physical_device, &mut memory_properties)
+        };
+        let heap_count = memory_properties.memory_properties.memory_heap_count as usize;
+
+        Some(
+            budget_properties.heap_usage[..heap_count]
+                .iter()
+                .zip(&budget_properties.heap_budget[..heap_count])
+                .map(|(&usage, &budget)| HeapBudget { usage, budget })
+                .collect(),
+        )
+    }
+
+    /// Changes the priority of a memory object through `VK_EXT_pageable_device_local_memory`.
+    fn set_memory_priority(&self, memory: vk::DeviceMemory, priority: f32) -> Result<()> {
+        let pageable_device_local_memory =
+            self.pageable_device_local_memory.as_ref().ok_or_else(|| {
+                AllocationError::InvalidAllocatorCreateDesc(
+                    "AllocatorCreateDesc field `pageable_device_local_memory` is not set.".into(),
+                )
+            })?;
+
+        unsafe {
+            (pageable_device_local_memory
+                .fp()
+                .set_device_memory_priority_ext)(
+                pageable_device_local_memory.device(),
+                memory,
+                priority,
+            )
+        };
+
+        Ok(())
+    }
+}
+
+impl MemoryBackend for VulkanBackend {
+    type MemoryTypeProperties = MemoryTypeProperties;
+    type Memory = DeviceMemory;
+    /// Never [`AllocationScheme::GpuAllocatorManaged`].
+    type DedicatedResource = AllocationScheme;
+
+    fn is_host_visible(properties: &MemoryTypeProperties) -> bool {
+        properties
+            .memory_properties
+            .contains(vk::MemoryPropertyFlags::HOST_VISIBLE)
+    }
+
+    fn heap_index(properties: &MemoryTypeProperties) -> usize {
+        properties.heap_index
+    }
+
+    fn atom_size(properties: &MemoryTypeProperties) -> u64 {
+        properties.non_coherent_atom_size
+    }
+
+    fn create_memory(
+        &self,
+        properties: &MemoryTypeProperties,
+        memory_type_index: usize,
         size: u64,
-        mem_type_index: usize,
-        mapped: bool,
-        buffer_device_address: bool,
-        allocation_scheme: AllocationScheme,
-        requires_personal_block: bool,
-    ) -> Result<Self> {
+        dedicated_resource: Option<AllocationScheme>,
+        priority: Option<f32>,
+    ) -> Result<DeviceMemory> {
+        let device = &self.device;
+
+        // Don't wait for the driver to fail or start paging when going over budget.
+        if let Some(budgets) = self.query_heap_budgets() {
+            let budget = &budgets[properties.heap_index];
+            if budget.usage.saturating_add(size) > budget.budget {
+                return Err(AllocationError::OutOfBudget);
+            }
+        }
+
         let device_memory = {
             let alloc_info = vk::MemoryAllocateInfo::default()
                 .allocation_size(size)
-                .memory_type_index(mem_type_index as u32);
+                .memory_type_index(memory_type_index as u32);
 
             let allocation_flags = vk::MemoryAllocateFlags::DEVICE_ADDRESS;
             let mut flags_info = vk::MemoryAllocateFlagsInfo::default().flags(allocation_flags);
             // TODO(manon): Test this based on if the device has this feature enabled or not
-            let alloc_info = if buffer_device_address {
+            let alloc_info = if self.buffer_device_address {
                 alloc_info.push_next(&mut flags_info)
             } else {
                 alloc_info
@@ -376,16 +837,25 @@ impl MemoryBlock {
 
             // Flag the memory as dedicated if required.
             let mut dedicated_memory_info = vk::MemoryDedicatedAllocateInfo::default();
-            let alloc_info = match allocation_scheme {
-                AllocationScheme::DedicatedBuffer(buffer) => {
+            let alloc_info = match dedicated_resource {
+                Some(AllocationScheme::DedicatedBuffer(buffer)) => {
                     dedicated_memory_info = dedicated_memory_info.buffer(buffer);
                     alloc_info.push_next(&mut dedicated_memory_info)
                 }
-                AllocationScheme::DedicatedImage(image) => {
+                Some(AllocationScheme::DedicatedImage(image)) => {
                     dedicated_memory_info = dedicated_memory_info.image(image);
                     alloc_info.push_next(&mut dedicated_memory_info)
                 }
-                AllocationScheme::GpuAllocatorManaged => alloc_info,
+                Some(AllocationScheme::GpuAllocatorManaged) | None => alloc_info,
+            };
+
+            let mut priority_info = vk::MemoryPriorityAllocateInfoEXT::default();
+            let alloc_info = match priority {
+                Some(priority) if self.memory_priority => {
+                    priority_info = priority_info.priority(priority);
+                    alloc_info.push_next(&mut priority_info)
+                }
+                _ => alloc_info,
             };
 
             unsafe { device.allocate_memory(&alloc_info, None) }.map_err(|e| match e {
@@ -396,7 +866,7 @@ impl MemoryBlock {
             })?
         };
 
-        let mapped_ptr = mapped
+        let mapped_ptr = Self::is_host_visible(properties)
             .then(|| {
                 unsafe {
                     device.map_memory(
@@ -418,281 +888,147 @@ impl MemoryBlock {
             })
             .transpose()?;
 
-        let sub_allocator: Box<dyn SubAllocator> = if allocation_scheme
-            != AllocationScheme::GpuAllocatorManaged
-            || requires_personal_block
-        {
-            Box::new(DedicatedBlockAllocator::new(size))
-        } else {
-            Box::new(FreeListAllocator::new(size))
-        };
-
-        Ok(Self {
+        Ok(DeviceMemory {
             device_memory,
-            size,
             mapped_ptr,
-            sub_allocator,
-            #[cfg(feature = "visualizer")]
-            dedicated_allocation: allocation_scheme != AllocationScheme::GpuAllocatorManaged,
         })
     }
 
-    fn destroy(self, device: &ash::Device) {
-        if self.mapped_ptr.is_some() {
-            unsafe { device.unmap_memory(self.device_memory) };
+    fn destroy_memory(&self, memory: DeviceMemory) {
+        if memory.mapped_ptr.is_some() {
+            unsafe { self.device.unmap_memory(memory.device_memory) };
         }
 
-        unsafe { device.free_memory(self.device_memory, None) };
+        unsafe { self.device.free_memory(memory.device_memory, None) };
     }
 }
 
-#[derive(Debug)]
-pub(crate) struct MemoryType {
-    pub(crate) memory_blocks: Vec<Option<MemoryBlock>>,
-    pub(crate) memory_properties: vk::MemoryPropertyFlags,
-    pub(crate) memory_type_index: usize,
-    pub(crate) heap_index: usize,
-    pub(crate) mappable: bool,
-    pub(crate) active_general_blocks: usize,
-    pub(crate) buffer_device_address: bool,
+/// Memory property flags that memory types for `location` should ideally have, see
+/// [`MemoryTypePreferences::allow_non_coherent`].
+fn preferred_memory_property_flags(
+    location: MemoryLocation,
+    allow_non_coherent: bool,
+) -> vk::MemoryPropertyFlags {
+    match location {
+        MemoryLocation::GpuOnly => vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        MemoryLocation::CpuToGpu => {
+            vk::MemoryPropertyFlags::HOST_VISIBLE
+                | vk::MemoryPropertyFlags::HOST_COHERENT
+                | vk::MemoryPropertyFlags::DEVICE_LOCAL
+        }
+        // Host cached memory is not always host coherent, but it is much faster to read from.
+        MemoryLocation::GpuToCpu if allow_non_coherent => {
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_CACHED
+        }
+        MemoryLocation::GpuToCpu => {
+            vk::MemoryPropertyFlags::HOST_VISIBLE
+                | vk::MemoryPropertyFlags::HOST_COHERENT
+                | vk::MemoryPropertyFlags::HOST_CACHED
+        }
+        MemoryLocation::Transient => {
+            vk::MemoryPropertyFlags::DEVICE_LOCAL | vk::MemoryPropertyFlags::LAZILY_ALLOCATED
+        }
+        MemoryLocation::Unknown => vk::MemoryPropertyFlags::empty(),
+    }
 }
 
-impl MemoryType {
-    fn allocate(
-        &mut self,
-        device: &ash::Device,
-        desc: &AllocationCreateDesc<'_>,
-        granularity: u64,
-        #[cfg(feature = "std")] backtrace: Arc<Backtrace>,
-        allocation_sizes: &AllocationSizes,
-    ) -> Result<Allocation> {
-        let allocation_type = if desc.linear {
-            AllocationType::Linear
-        } else {
-            AllocationType::NonLinear
-        };
-
-        let is_host = self
-            .memory_properties
-            .contains(vk::MemoryPropertyFlags::HOST_VISIBLE);
-
-        let memblock_size = allocation_sizes.get_memblock_size(is_host, self.active_general_blocks);
-
-        let size = desc.requirements.size;
-        let alignment = desc.requirements.alignment;
-
-        let dedicated_allocation = desc.allocation_scheme != AllocationScheme::GpuAllocatorManaged;
-        let requires_personal_block = size > memblock_size;
-
-        // Create a dedicated block for large memory allocations or allocations that require dedicated memory allocations.
-        if dedicated_allocation || requires_personal_block {
-            let mem_block = MemoryBlock::new(
-                device,
-                size,
-                self.memory_type_index,
-                self.mappable,
-                self.buffer_device_address,
-                desc.allocation_scheme,
-                requires_personal_block,
-            )?;
-
-            let mut block_index = None;
-            for (i, block) in self.memory_blocks.iter().enumerate() {
-                if block.is_none() {
-                    block_index = Some(i);
-                    break;
-                }
-            }
-
-            let block_index = match block_index {
-                Some(i) => {
-                    self.memory_blocks[i].replace(mem_block);
-                    i
-                }
-                None => {
-                    self.memory_blocks.push(Some(mem_block));
-                    self.memory_blocks.len() - 1
-                }
-            };
-
-            let mem_block = self.memory_blocks[block_index]
-                .as_mut()
-                .ok_or_else(|| AllocationError::Internal("Memory block must be Some".into()))?;
-
-            let (offset, chunk_id) = mem_block.sub_allocator.allocate(
-                size,
-                alignment,
-                allocation_type,
-                granularity,
-                desc.name,
-                #[cfg(feature = "std")]
-                backtrace,
-            )?;
-
-            return Ok(Allocation {
-                chunk_id: Some(chunk_id),
-                offset,
-                size,
-                memory_block_index: block_index,
-                memory_type_index: self.memory_type_index,
-                device_memory: mem_block.device_memory,
-                mapped_ptr: mem_block.mapped_ptr,
-                memory_properties: self.memory_properties,
-                name: Some(desc.name.into()),
-                dedicated_allocation,
-            });
-        }
-
-        let mut empty_block_index = None;
-        for (mem_block_i, mem_block) in self.memory_blocks.iter_mut().enumerate().rev() {
-            if let Some(mem_block) = mem_block {
-                let allocation = mem_block.sub_allocator.allocate(
-                    size,
-                    alignment,
-                    allocation_type,
-                    granularity,
-                    desc.name,
-                    #[cfg(feature = "std")]
-                    backtrace.clone(),
-                );
-
-                match allocation {
-                    Ok((offset, chunk_id)) => {
-                        let mapped_ptr = if let Some(SendSyncPtr(mapped_ptr)) = mem_block.mapped_ptr
-                        {
-                            let offset_ptr = unsafe { mapped_ptr.as_ptr().add(offset as usize) };
-                            core::ptr::NonNull::new(offset_ptr).map(SendSyncPtr)
-                        } else {
-                            None
-                        };
-                        return Ok(Allocation {
-                            chunk_id: Some(chunk_id),
-                            offset,
-                            size,
-                            memory_block_index: mem_block_i,
-                            memory_type_index: self.memory_type_index,
-                            device_memory: mem_block.device_memory,
-                            memory_properties: self.memory_properties,
-                            mapped_ptr,
-                            dedicated_allocation: false,
-                            name: Some(desc.name.into()),
-                        });
-                    }
-                    Err(err) => match err {
-                        AllocationError::OutOfMemory => {} // Block is full, continue search.
-                        _ => return Err(err),              // Unhandled error, return.
-                    },
-                }
-            } else if empty_block_index.is_none() {
-                empty_block_index = Some(mem_block_i);
-            }
+/// Memory property flags that memory types for `location` should preferably not have.
+fn not_preferred_memory_property_flags(location: MemoryLocation) -> vk::MemoryPropertyFlags {
+    // Memory types for special purposes, that regular allocations should stay out of.
+    let special = vk::MemoryPropertyFlags::PROTECTED
+        | vk::MemoryPropertyFlags::DEVICE_COHERENT_AMD
+        | vk::MemoryPropertyFlags::DEVICE_UNCACHED_AMD;
+    match location {
+        // Leave host visible device local memory, like Resizable BAR, to uploads.
+        MemoryLocation::GpuOnly => {
+            special
+                | vk::MemoryPropertyFlags::HOST_VISIBLE
+                | vk::MemoryPropertyFlags::LAZILY_ALLOCATED
         }
+        MemoryLocation::Transient => special | vk::MemoryPropertyFlags::HOST_VISIBLE,
+        MemoryLocation::CpuToGpu | MemoryLocation::GpuToCpu | MemoryLocation::Unknown => {
+            special | vk::MemoryPropertyFlags::LAZILY_ALLOCATED
+        }
+    }
+}
 
-        let new_memory_block = MemoryBlock::new(
-            device,
-            memblock_size,
-            self.memory_type_index,
-            self.mappable,
-            self.buffer_device_address,
-            desc.allocation_scheme,
-            false,
-        )?;
-
-        let new_block_index = if let Some(block_index) = empty_block_index {
-            self.memory_blocks[block_index] = Some(new_memory_block);
-            block_index
-        } else {
-            self.memory_blocks.push(Some(new_memory_block));
-            self.memory_blocks.len() - 1
-        };
+/// Memory property flags that memory types for `location` must have.
+///
+/// Host visible memory only has to be host coherent unless `allow_non_coherent` is set, see
+/// [`MemoryTypePreferences::allow_non_coherent`].
+fn required_memory_property_flags(
+    location: MemoryLocation,
+    allow_non_coherent: bool,
+) -> vk::MemoryPropertyFlags {
+    match location {
+        MemoryLocation::GpuOnly | MemoryLocation::Transient => {
+            vk::MemoryPropertyFlags::DEVICE_LOCAL
+        }
+        MemoryLocation::CpuToGpu | MemoryLocation::GpuToCpu if allow_non_coherent => {
+            vk::MemoryPropertyFlags::HOST_VISIBLE
+        }
+        MemoryLocation::CpuToGpu | MemoryLocation::GpuToCpu => {
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT
+        }
+        MemoryLocation::Unknown => vk::MemoryPropertyFlags::empty(),
+    }
+}
 
-        self.active_general_blocks += 1;
+/// Returns [`true`] if memory with these property flags is mapped, but host writes and device
+/// writes only become visible to the other side after flushing and invalidating it.
+fn is_non_coherent(flags: vk::MemoryPropertyFlags) -> bool {
+    flags.contains(vk::MemoryPropertyFlags::HOST_VISIBLE)
+        && !flags.contains(vk::MemoryPropertyFlags::HOST_COHERENT)
+}
 
-        let mem_block = self.memory_blocks[new_block_index]
-            .as_mut()
-            .ok_or_else(|| AllocationError::Internal("Memory block must be Some".into()))?;
-        let allocation = mem_block.sub_allocator.allocate(
-            size,
-            alignment,
-            allocation_type,
-            granularity,
-            desc.name,
-            #[cfg(feature = "std")]
-            backtrace,
-        );
-        let (offset, chunk_id) = match allocation {
-            Ok(value) => value,
-            Err(err) => match err {
-                AllocationError::OutOfMemory => {
-                    return Err(AllocationError::Internal(
-                        "Allocation that must succeed failed. This is a bug in the allocator."
-                            .into(),
-                    ))
-                }
-                _ => return Err(err),
-            },
-        };
+/// A [`vk::Buffer`] bound to the memory of its [`Allocation`], created through
+/// [`Allocator::create_buffer()`] and destroyed through [`Allocator::destroy_buffer()`].
+#[derive(Debug)]
+pub struct Buffer {
+    buffer: vk::Buffer,
+    allocation: Allocation,
+}
 
-        let mapped_ptr = if let Some(SendSyncPtr(mapped_ptr)) = mem_block.mapped_ptr {
-            let offset_ptr = unsafe { mapped_ptr.as_ptr().add(offset as usize) };
-            core::ptr::NonNull::new(offset_ptr).map(SendSyncPtr)
-        } else {
-            None
-        };
+impl Buffer {
+    pub fn buffer(&self) -> vk::Buffer {
+        self.buffer
+    }
 
-        Ok(Allocation {
-            chunk_id: Some(chunk_id),
-            offset,
-            size,
-            memory_block_index: new_block_index,
-            memory_type_index: self.memory_type_index,
-            device_memory: mem_block.device_memory,
-            mapped_ptr,
-            memory_properties: self.memory_properties,
-            name: Some(desc.name.into()),
-            dedicated_allocation: false,
-        })
+    pub fn allocation(&self) -> &Allocation {
+        &self.allocation
     }
 
-    #[allow(clippy::needless_pass_by_value)]
-    fn free(&mut self, allocation: Allocation, device: &ash::Device) -> Result<()> {
-        let block_idx = allocation.memory_block_index;
+    pub fn allocation_mut(&mut self) -> &mut Allocation {
+        &mut self.allocation
+    }
+}
 
-        let mem_block = self.memory_blocks[block_idx]
-            .as_mut()
-            .ok_or_else(|| AllocationError::Internal("Memory block must be Some.".into()))?;
+/// A [`vk::Image`] bound to the memory of its [`Allocation`], created through
+/// [`Allocator::create_image()`] and destroyed through [`Allocator::destroy_image()`].
+#[derive(Debug)]
+pub struct Image {
+    image: vk::Image,
+    allocation: Allocation,
+}
 
-        mem_block.sub_allocator.free(allocation.chunk_id)?;
-
-        // We only want to destroy this now-empty block if it is either a dedicated/personal
-        // allocation, or a block supporting sub-allocations that is not the last one (ensuring
-        // there's always at least one block/allocator readily available).
-        let is_dedicated_or_not_last_general_block =
-            !mem_block.sub_allocator.supports_general_allocations()
-                || self.active_general_blocks > 1;
-        if mem_block.sub_allocator.is_empty() && is_dedicated_or_not_last_general_block {
-            let block = self.memory_blocks[block_idx]
-                .take()
-                .ok_or_else(|| AllocationError::Internal("Memory block must be Some.".into()))?;
-
-            if block.sub_allocator.supports_general_allocations() {
-                self.active_general_blocks -= 1;
-            }
+impl Image {
+    pub fn image(&self) -> vk::Image {
+        self.image
+    }
 
-            block.destroy(device);
-        }
+    pub fn allocation(&self) -> &Allocation {
+        &self.allocation
+    }
 
-        Ok(())
+    pub fn allocation_mut(&mut self) -> &mut Allocation {
+        &mut self.allocation
     }
 }
 
 pub struct Allocator {
-    pub(crate) memory_types: Vec<MemoryType>,
+    pub(crate) inner: GenericAllocator<VulkanBackend>,
     pub(crate) memory_heaps: Vec<vk::MemoryHeap>,
-    device: ash::Device,
     pub(crate) buffer_image_granularity: u64,
-    pub(crate) debug_settings: AllocatorDebugSettings,
-    allocation_sizes: AllocationSizes,
 }
 
 impl fmt::Debug for Allocator {
@@ -717,6 +1053,12 @@ impl Allocator {
         let memory_types = &mem_props.memory_types_as_slice();
         let memory_heaps = mem_props.memory_heaps_as_slice().to_vec();
 
+        if desc.heap_size_limits.len() > memory_heaps.len() {
+            return Err(AllocationError::InvalidAllocatorCreateDesc(
+                "AllocatorCreateDesc field `heap_size_limits` has more entries than there are memory heaps.".into(),
+            ));
+        }
+
         if desc.debug_settings.log_memory_information {
             debug!("memory type count: {}", mem_props.memory_type_count);
             debug!("memory heap count: {}", mem_props.memory_heap_count);
@@ -740,265 +1082,1318 @@ impl Allocator {
             }
         }
 
-        let memory_types = memory_types
-            .iter()
-            .enumerate()
-            .map(|(i, mem_type)| MemoryType {
-                memory_blocks: Vec::default(),
-                memory_properties: mem_type.property_flags,
-                memory_type_index: i,
-                heap_index: mem_type.heap_index as usize,
-                mappable: mem_type
-                    .property_flags
-                    .contains(vk::MemoryPropertyFlags::HOST_VISIBLE),
-                active_general_blocks: 0,
-                buffer_device_address: desc.buffer_device_address,
-            })
-            .collect::<Vec<_>>();
-
         let physical_device_properties = unsafe {
             desc.instance
                 .get_physical_device_properties(desc.physical_device)
         };
 
+        let non_coherent_atom_size = physical_device_properties.limits.non_coherent_atom_size;
+        let memory_types = memory_types.iter().map(|mem_type| MemoryTypeProperties {
+            memory_properties: mem_type.property_flags,
+            heap_index: mem_type.heap_index as usize,
+            non_coherent_atom_size: if is_non_coherent(mem_type.property_flags) {
+                non_coherent_atom_size
+            } else {
+                1
+            },
+        });
+
         let granularity = physical_device_properties.limits.buffer_image_granularity;
 
+        let backend = VulkanBackend {
+            device: desc.device.clone(),
+            buffer_device_address: desc.buffer_device_address,
+            memory_budget: desc
+                .memory_budget
+                .then(|| (desc.instance.clone(), desc.physical_device)),
+            get_memory_requirements2: desc.get_memory_requirements2,
+            memory_priority: desc.memory_priority,
+            pageable_device_local_memory: desc.pageable_device_local_memory.then(|| {
+                ash::ext::pageable_device_local_memory::Device::new(&desc.instance, &desc.device)
+            }),
+        };
+
+        let allocation_sizes = desc.allocation_sizes.resolve(
+            memory_heaps
+                .iter()
+                .map(|heap| heap.size)
+                .max()
+                .unwrap_or(u64::MAX),
+        )?;
+        let heaps = memory_heaps
+            .iter()
+            .enumerate()
+            .map(|(heap_index, heap)| HeapProperties {
+                size: heap.size,
+                size_limit: desc.heap_size_limits.get(heap_index).copied().flatten(),
+            })
+            .collect::<Vec<_>>();
+
         Ok(Self {
-            memory_types,
+            inner: GenericAllocator::new(
+                backend,
+                memory_types,
+                &heaps,
+                desc.debug_settings,
+                &allocation_sizes,
+                desc.sub_allocator,
+                desc.allocation_strategy,
+                desc.empty_block_retention,
+            ),
             memory_heaps,
-            device: desc.device.clone(),
             buffer_image_granularity: granularity,
-            debug_settings: desc.debug_settings,
-            allocation_sizes: desc.allocation_sizes,
         })
     }
 
     pub fn allocate(&mut self, desc: &AllocationCreateDesc<'_>) -> Result<Allocation> {
-        let size = desc.requirements.size;
-        let alignment = desc.requirements.alignment;
+        allocate(
+            &mut self.inner,
+            &self.memory_heaps,
+            self.buffer_image_granularity,
+            desc,
+        )
+    }
 
-        #[cfg(feature = "std")]
-        let backtrace = Arc::new(if self.debug_settings.store_stack_traces {
-            Backtrace::force_capture()
-        } else {
-            Backtrace::disabled()
-        });
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn free(&mut self, allocation: Allocation) -> Result<()> {
+        free(&mut self.inner, allocation)
+    }
 
-        if self.debug_settings.log_allocations {
-            debug!(
-                "Allocating `{}` of {} bytes with an alignment of {}.",
-                &desc.name, size, alignment
-            );
-            #[cfg(feature = "std")]
-            if self.debug_settings.log_stack_traces {
-                let backtrace = Backtrace::force_capture();
-                debug!("Allocation stack trace: {backtrace}");
-            }
+    /// Sets the algorithm used to place allocations inside the shared memory blocks of the memory
+    /// type at `memory_type_index`, overriding [`AllocatorCreateDesc::sub_allocator`] for it.
+    ///
+    /// Only affects memory blocks that are created after this call.
+    pub fn set_memory_type_sub_allocator(
+        &mut self,
+        memory_type_index: usize,
+        sub_allocator: SubAllocatorKind,
+    ) -> Result<()> {
+        let memory_type = self
+            .inner
+            .memory_types
+            .get_mut(memory_type_index)
+            .ok_or(AllocationError::NoCompatibleMemoryTypeFound)?;
+        memory_type.sub_allocator = sub_allocator;
+
+        Ok(())
+    }
+
+    /// Finds the memory type to place memory blocks for `location` in, which must fit memory
+    /// blocks of `block_size` bytes.
+    fn find_block_memory_type_index(
+        &self,
+        location: MemoryLocation,
+        memory_type_bits: u32,
+        block_size: u64,
+    ) -> Result<usize> {
+        let memory_type_index = find_memorytype_index(
+            &self.inner,
+            memory_type_bits,
+            &MemoryTypePreferences::default().with_location(location),
+        )
+        .ok_or(AllocationError::NoCompatibleMemoryTypeFound)?;
+
+        let heap_index = self.inner.memory_types[memory_type_index]
+            .properties
+            .heap_index;
+        if block_size > self.memory_heaps[heap_index].size {
+            return Err(AllocationError::OutOfMemory);
         }
 
-        if size == 0 || !alignment.is_power_of_two() {
+        Ok(memory_type_index)
+    }
+
+    /// Creates a [`Pool`] in a memory type compatible with the given location and memory type
+    /// bits, along with its [`PoolCreateDesc::min_block_count`] memory blocks.
+    pub fn create_pool(&mut self, desc: &PoolCreateDesc) -> Result<Pool> {
+        let pool = CustomPool::new(
+            desc.block_size,
+            desc.min_block_count,
+            desc.max_block_count,
+            desc.sub_allocator,
+            desc.allocation_strategy,
+            desc.priority,
+        )?;
+        let memory_type_index = self.find_block_memory_type_index(
+            desc.location,
+            desc.memory_type_bits,
+            desc.block_size,
+        )?;
+        let pool_index = self.inner.create_pool(memory_type_index, pool)?;
+
+        Ok(Pool {
+            memory_type_index,
+            pool_index,
+        })
+    }
+
+    /// Destroys all memory blocks of a [`Pool`].
+    ///
+    /// All allocations made from the pool must have been freed. Otherwise this fails with
+    /// [`AllocationError::Internal`] and destroys nothing, and the memory blocks of the pool are only
+    /// destroyed together with the allocator.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn destroy_pool(&mut self, pool: Pool) -> Result<()> {
+        self.inner
+            .destroy_pool(pool.memory_type_index, pool.pool_index)
+    }
+
+    /// Changes the priority of the memory blocks of a [`Pool`], including the ones that are
+    /// created later on.
+    ///
+    /// Requires [`AllocatorCreateDesc::pageable_device_local_memory`].
+    pub fn set_pool_priority(&mut self, pool: &Pool, priority: f32) -> Result<()> {
+        if !is_valid_priority(Some(priority)) {
             return Err(AllocationError::InvalidAllocationCreateDesc);
         }
 
-        let mem_loc_preferred_bits = match desc.location {
-            MemoryLocation::GpuOnly => vk::MemoryPropertyFlags::DEVICE_LOCAL,
-            MemoryLocation::CpuToGpu => {
-                vk::MemoryPropertyFlags::HOST_VISIBLE
-                    | vk::MemoryPropertyFlags::HOST_COHERENT
-                    | vk::MemoryPropertyFlags::DEVICE_LOCAL
+        let memory_type = &mut self.inner.memory_types[pool.memory_type_index];
+        for block in memory_type.memory_blocks.iter().flatten() {
+            if block.pool == Some(pool.pool_index) {
+                self.inner
+                    .backend
+                    .set_memory_priority(block.memory.device_memory, priority)?;
             }
-            MemoryLocation::GpuToCpu => {
-                vk::MemoryPropertyFlags::HOST_VISIBLE
-                    | vk::MemoryPropertyFlags::HOST_COHERENT
-                    | vk::MemoryPropertyFlags::HOST_CACHED
+        }
+
+        let pool = memory_type.pools[pool.pool_index]
+            .as_mut()
+            .ok_or_else(|| AllocationError::Internal("Pool must be Some.".into()))?;
+        pool.priority = Some(priority);
+
+        Ok(())
+    }
+
+    /// Changes the priority of the memory of an allocation that has a memory block of its own,
+    /// like dedicated allocations and allocations that are too large for the shared memory
+    /// blocks. Fails with [`AllocationError::InvalidAllocationCreateDesc`] for other allocations.
+    ///
+    /// Requires [`AllocatorCreateDesc::pageable_device_local_memory`].
+    pub fn set_allocation_priority(
+        &mut self,
+        allocation: &Allocation,
+        priority: f32,
+    ) -> Result<()> {
+        if allocation.is_null() || !is_valid_priority(Some(priority)) {
+            return Err(AllocationError::InvalidAllocationCreateDesc);
+        }
+
+        let block = self.inner.memory_types[allocation.memory_type_index].memory_blocks
+            [allocation.memory_block_index]
+            .as_ref()
+            .ok_or_else(|| AllocationError::Internal("Memory block must be Some.".into()))?;
+        if block.sub_allocator.supports_general_allocations()
+            || block.linear_pool
+            || block.pool.is_some()
+        {
+            return Err(AllocationError::InvalidAllocationCreateDesc);
+        }
+
+        self.inner
+            .backend
+            .set_memory_priority(block.memory.device_memory, priority)
+    }
+
+    /// Creates a [`LinearPool`] holding a single memory block of [`LinearPoolCreateDesc::size`]
+    /// bytes, in a memory type compatible with the given location and memory type bits.
+    pub fn create_linear_pool(&mut self, desc: &LinearPoolCreateDesc) -> Result<LinearPool> {
+        if desc.size == 0 {
+            return Err(AllocationError::InvalidAllocationCreateDesc);
+        }
+
+        let memory_type_index =
+            self.find_block_memory_type_index(desc.location, desc.memory_type_bits, desc.size)?;
+        let memory_block_index = self
+            .inner
+            .create_linear_pool(memory_type_index, desc.size)?;
+
+        Ok(LinearPool {
+            memory_type_index,
+            memory_block_index,
+        })
+    }
+
+    /// Destroys the memory block of a [`LinearPool`].
+    ///
+    /// Any allocation that is still live in the pool becomes invalid, and must neither be used
+    /// nor freed afterwards.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn destroy_linear_pool(&mut self, pool: LinearPool) -> Result<()> {
+        self.inner
+            .destroy_linear_pool(pool.memory_type_index, pool.memory_block_index)
+    }
+
+    /// Allocates from a [`LinearPool`] instead of the shared memory blocks.
+    ///
+    /// [`AllocationCreateDesc::location`] is ignored in favor of the location of the pool, and
+    /// [`AllocationCreateDesc::allocation_scheme`] must be
+    /// [`AllocationScheme::GpuAllocatorManaged`]. Returns [`AllocationError::OutOfMemory`] when the
+    /// pool is full, no other memory blocks are created to make room.
+    ///
+    /// The allocation may be freed through [`Allocator::free()`] like any other, or be released
+    /// together with the rest of its frame through [`Allocator::free_linear_pool_frames_before()`].
+    pub fn allocate_from_linear_pool(
+        &mut self,
+        pool: &LinearPool,
+        desc: &AllocationCreateDesc<'_>,
+    ) -> Result<Allocation> {
+        let size = desc.requirements.size;
+        let alignment = desc.requirements.alignment;
+
+        #[cfg(feature = "std")]
+        let backtrace = self.inner.capture_backtrace();
+
+        self.inner.log_allocation(desc.name, size, alignment);
+
+        if size == 0
+            || !alignment.is_power_of_two()
+            || desc.allocation_scheme != AllocationScheme::GpuAllocatorManaged
+        {
+            return Err(AllocationError::InvalidAllocationCreateDesc);
+        }
+
+        if (1 << pool.memory_type_index) & desc.requirements.memory_type_bits == 0 {
+            return Err(AllocationError::NoCompatibleMemoryTypeFound);
+        }
+
+        let request = AllocationRequest {
+            name: desc.name,
+            size,
+            alignment,
+            allocation_type: if desc.linear {
+                AllocationType::Linear
+            } else {
+                AllocationType::NonLinear
+            },
+            granularity: self.buffer_image_granularity,
+            strategy: desc.allocation_strategy,
+            location: desc.location,
+            priority: None,
+            #[cfg(feature = "std")]
+            backtrace,
+        };
+        let allocation = self.inner.allocate_from_linear_pool(
+            pool.memory_type_index,
+            pool.memory_block_index,
+            &request,
+        )?;
+
+        new_allocation(&self.inner, allocation, desc.name, desc.location, false)
+    }
+
+    /// Tags all allocations made from `pool` from now on with `frame`.
+    ///
+    /// Frame numbers are expected to increase monotonically.
+    pub fn begin_linear_pool_frame(&mut self, pool: &LinearPool, frame: u64) -> Result<()> {
+        self.inner
+            .begin_linear_pool_frame(pool.memory_type_index, pool.memory_block_index, frame)
+    }
+
+    /// Frees all allocations of `pool` that were made before `frame` was begun, typically
+    /// because the GPU has finished processing those frames.
+    ///
+    /// The released [`Allocation`]s become invalid and must no longer be used. Passing them to
+    /// [`Allocator::free()`] afterwards is allowed and does nothing.
+    pub fn free_linear_pool_frames_before(&mut self, pool: &LinearPool, frame: u64) -> Result<()> {
+        self.inner.free_linear_pool_frames_before(
+            pool.memory_type_index,
+            pool.memory_block_index,
+            frame,
+        )
+    }
+
+    /// Plans moving allocations out of the least used shared memory blocks into other memory
+    /// blocks of the same memory type, so that those memory blocks become empty and are freed
+    /// once the plan is committed.
+    ///
+    /// Memory blocks are only emptied as a whole, and only as long as the total size of the
+    /// moved allocations stays within `max_bytes_to_move`. Room for every move is reserved right
+    /// away: copy the data of each moved allocation to [`DefragmentationMove::destination()`],
+    /// recreate or rebind the resources placed on it, and then pass the plan to
+    /// [`Allocator::commit_defragmentation()`], or give up with
+    /// [`Allocator::cancel_defragmentation()`]. Allocations that are part of the plan must not be
+    /// freed before then, and dropping the plan keeps the reserved room allocated until the
+    /// allocator is dropped.
+    pub fn plan_defragmentation(
+        &mut self,
+        max_bytes_to_move: u64,
+    ) -> Result<DefragmentationPlan<Allocation>> {
+        let moves = self
+            .inner
+            .plan_defragmentation(max_bytes_to_move, self.buffer_image_granularity)?
+            .into_iter()
+            .map(|planned| {
+                let destination = new_allocation(
+                    &self.inner,
+                    planned.destination,
+                    &planned.name,
+                    MemoryLocation::Unknown,
+                    false,
+                )?;
+                Ok(planned.with_destination(destination))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(DefragmentationPlan { moves })
+    }
+
+    /// Finishes `plan` once the data has been copied: every allocation in `allocations` that is
+    /// moved by the plan is freed and replaced with the destination of its move, which may empty
+    /// and free whole memory blocks.
+    ///
+    /// The moves of allocations that are not passed in are cancelled.
+    pub fn commit_defragmentation<'a>(
+        &mut self,
+        plan: DefragmentationPlan<Allocation>,
+        allocations: impl IntoIterator<Item = &'a mut Allocation>,
+    ) -> Result<()> {
+        self.inner
+            .commit_defragmentation(plan, allocations, Allocation::key)
+    }
+
+    /// Releases the room reserved by `plan`, leaving all allocations where they are.
+    pub fn cancel_defragmentation(&mut self, plan: DefragmentationPlan<Allocation>) -> Result<()> {
+        self.inner.cancel_defragmentation(plan)
+    }
+
+    pub fn rename_allocation(&mut self, allocation: &mut Allocation, name: &str) -> Result<()> {
+        rename_allocation(&mut self.inner, allocation, name)
+    }
+
+    pub fn report_memory_leaks(&self, log_level: Level) {
+        self.inner.report_memory_leaks(log_level);
+    }
+
+    pub fn generate_report(&self) -> AllocatorReport {
+        self.inner.generate_report()
+    }
+
+    /// Current total capacity of memory blocks allocated on the device, in bytes
+    pub fn capacity(&self) -> u64 {
+        self.inner.capacity()
+    }
+
+    /// Creates shared memory blocks up front in the memory type that allocations for `location`
+    /// are placed in, until that memory type has at least `size` bytes of reserved memory blocks.
+    /// This moves the cost of creating memory blocks to a convenient moment, like a loading
+    /// screen, instead of the first allocations that need them. The size is rounded up to whole
+    /// memory blocks.
+    ///
+    /// Reserved memory blocks are shared by all allocations, and are never destroyed when they
+    /// become empty, not even by [`Allocator::trim()`].
+    pub fn reserve(&mut self, location: MemoryLocation, size: u64) -> Result<()> {
+        let memory_type_index = self.find_block_memory_type_index(location, u32::MAX, size)?;
+        self.inner.reserve(memory_type_index, location, size)
+    }
+
+    /// Destroys all shared memory blocks that don't hold any allocation, including the last one of
+    /// every memory type, to give memory back during idle periods. Memory blocks of pools and
+    /// reserved memory blocks are unaffected.
+    pub fn trim(&mut self) {
+        self.inner.trim();
+    }
+
+    /// Advances the frame counter of [`EmptyBlockRetention::Frames`] to `frame`, destroying the
+    /// empty shared memory blocks that have been kept around for long enough. `frame` must never
+    /// go down.
+    pub fn set_current_frame(&mut self, frame: u64) {
+        self.inner.set_current_frame(frame);
+    }
+
+    /// Returns the usage and budget of every memory heap.
+    ///
+    /// These are queried from the driver if [`AllocatorCreateDesc::memory_budget`] is set.
+    /// Otherwise the usage only covers the memory blocks of this allocator, and the budget is the
+    /// size of the heap. Budgets are capped at [`AllocatorCreateDesc::heap_size_limits`].
+    pub fn heap_budgets(&self) -> Vec<HeapBudget> {
+        let mut budgets = self.inner.backend.query_heap_budgets().unwrap_or_else(|| {
+            let mut budgets = self
+                .memory_heaps
+                .iter()
+                .map(|heap| HeapBudget {
+                    usage: 0,
+                    budget: heap.size,
+                })
+                .collect::<Vec<_>>();
+            for memory_type in &self.inner.memory_types {
+                budgets[memory_type.properties.heap_index].usage += memory_type.capacity();
             }
-            MemoryLocation::Unknown => vk::MemoryPropertyFlags::empty(),
+            budgets
+        });
+
+        // Heaps can't be used beyond their size limit, whatever the driver reports.
+        for (budget, heap) in budgets.iter_mut().zip(&self.inner.memory_heaps) {
+            if let Some(size_limit) = heap.size_limit {
+                budget.budget = budget.budget.min(size_limit);
+            }
+        }
+
+        budgets
+    }
+
+    /// Makes host writes to `size` bytes at `offset` in `allocation` available to the device.
+    /// Must be called after writing to memory that is not host coherent, see
+    /// [`Allocation::memory_properties()`], and does nothing for host coherent memory. Such
+    /// memory is only picked when [`MemoryTypePreferences::allow_non_coherent`] is set.
+    ///
+    /// `offset` is relative to the start of the allocation, and `size` may be [`vk::WHOLE_SIZE`]
+    /// to cover the rest of the allocation. The range is widened to whole multiples of
+    /// `nonCoherentAtomSize`, which never reaches into other allocations.
+    pub fn flush_allocation(&self, allocation: &Allocation, offset: u64, size: u64) -> Result<()> {
+        flush_allocation(
+            &self.inner.backend.device,
+            &self.inner,
+            allocation,
+            offset,
+            size,
+        )
+    }
+
+    /// Makes device writes to `size` bytes at `offset` in `allocation` visible to the host. Must
+    /// be called before reading from memory that is not host coherent, see
+    /// [`Allocation::memory_properties()`], and does nothing for host coherent memory.
+    ///
+    /// The range is interpreted like in [`Self::flush_allocation()`].
+    pub fn invalidate_allocation(
+        &self,
+        allocation: &Allocation,
+        offset: u64,
+        size: u64,
+    ) -> Result<()> {
+        invalidate_allocation(
+            &self.inner.backend.device,
+            &self.inner,
+            allocation,
+            offset,
+            size,
+        )
+    }
+
+    /// Creates a buffer from `create_info`, allocates memory for it in `location` and binds the
+    /// two together. Nothing is left behind when any of these steps fails.
+    ///
+    /// The memory requirements are queried through [`AllocationCreateDesc::from_buffer()`], or
+    /// through [`AllocationCreateDesc::from_buffer2()`] if
+    /// [`AllocatorCreateDesc::get_memory_requirements2`] is set, which requires Vulkan 1.1 and
+    /// dedicates the memory to the buffer when the driver asks for it.
+    ///
+    /// Created buffers should be destroyed at the end of their lifetime by calling
+    /// [`Self::destroy_buffer()`].
+    pub fn create_buffer(
+        &mut self,
+        name: &str,
+        create_info: &vk::BufferCreateInfo<'_>,
+        location: MemoryLocation,
+    ) -> Result<Buffer> {
+        let device = self.inner.backend.device.clone();
+        let buffer = unsafe { device.create_buffer(create_info, None) }.map_err(|e| match e {
+            vk::Result::ERROR_OUT_OF_DEVICE_MEMORY => AllocationError::OutOfMemory,
+            e => AllocationError::Internal(format!("Unexpected error in vkCreateBuffer: {e:?}")),
+        })?;
+
+        let desc = if self.inner.backend.get_memory_requirements2 {
+            AllocationCreateDesc::from_buffer2(&device, buffer, name, location)
+        } else {
+            AllocationCreateDesc::from_buffer(&device, buffer, name, location)
         };
-        let mut memory_type_index_opt =
-            self.find_memorytype_index(&desc.requirements, mem_loc_preferred_bits);
-
-        if memory_type_index_opt.is_none() {
-            let mem_loc_required_bits = match desc.location {
-                MemoryLocation::GpuOnly => vk::MemoryPropertyFlags::DEVICE_LOCAL,
-                MemoryLocation::CpuToGpu | MemoryLocation::GpuToCpu => {
-                    vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT
-                }
-                MemoryLocation::Unknown => vk::MemoryPropertyFlags::empty(),
-            };
+        let allocation = self.allocate(&desc).map_err(|e| {
+            unsafe { device.destroy_buffer(buffer, None) };
+            e
+        })?;
 
-            memory_type_index_opt =
-                self.find_memorytype_index(&desc.requirements, mem_loc_required_bits);
+        if let Err(e) =
+            unsafe { device.bind_buffer_memory(buffer, allocation.memory(), allocation.offset()) }
+        {
+            unsafe { device.destroy_buffer(buffer, None) };
+            // The bind error is what the caller needs to see, not a failure to clean up after it.
+            if let Err(err) = self.free(allocation) {
+                log::error!("Failed to free the memory of an unbound buffer: {err}");
+            }
+            return Err(AllocationError::Internal(format!(
+                "Unexpected error in vkBindBufferMemory: {e:?}"
+            )));
         }
 
-        let memory_type_index = match memory_type_index_opt {
-            Some(x) => x as usize,
-            None => return Err(AllocationError::NoCompatibleMemoryTypeFound),
+        Ok(Buffer { buffer, allocation })
+    }
+
+    /// Destroys a buffer created through [`Self::create_buffer()`] and frees its memory.
+    pub fn destroy_buffer(&mut self, buffer: Buffer) -> Result<()> {
+        unsafe {
+            self.inner
+                .backend
+                .device
+                .destroy_buffer(buffer.buffer, None)
         };
+        self.free(buffer.allocation)
+    }
 
-        //Do not try to create a block if the heap is smaller than the required size (avoids validation warnings).
-        let memory_type = &mut self.memory_types[memory_type_index];
-        let allocation = if size > self.memory_heaps[memory_type.heap_index].size {
-            Err(AllocationError::OutOfMemory)
+    /// Creates an image from `create_info`, allocates memory for it in `location` and binds the
+    /// two together. Nothing is left behind when any of these steps fails.
+    ///
+    /// The memory requirements are queried through [`AllocationCreateDesc::from_image()`], or
+    /// through [`AllocationCreateDesc::from_image2()`] if
+    /// [`AllocatorCreateDesc::get_memory_requirements2`] is set, which requires Vulkan 1.1 and
+    /// dedicates the memory to the image when the driver asks for it.
+    ///
+    /// Created images should be destroyed at the end of their lifetime by calling
+    /// [`Self::destroy_image()`].
+    pub fn create_image(
+        &mut self,
+        name: &str,
+        create_info: &vk::ImageCreateInfo<'_>,
+        location: MemoryLocation,
+    ) -> Result<Image> {
+        let device = self.inner.backend.device.clone();
+        let image = unsafe { device.create_image(create_info, None) }.map_err(|e| match e {
+            vk::Result::ERROR_OUT_OF_DEVICE_MEMORY => AllocationError::OutOfMemory,
+            e => AllocationError::Internal(format!("Unexpected error in vkCreateImage: {e:?}")),
+        })?;
+
+        let linear = create_info.tiling == vk::ImageTiling::LINEAR;
+        let desc = if self.inner.backend.get_memory_requirements2 {
+            AllocationCreateDesc::from_image2(&device, image, linear, name, location)
         } else {
-            memory_type.allocate(
-                &self.device,
-                desc,
-                self.buffer_image_granularity,
-                #[cfg(feature = "std")]
-                backtrace.clone(),
-                &self.allocation_sizes,
-            )
+            AllocationCreateDesc::from_image(&device, image, linear, name, location)
         };
+        let allocation = self.allocate(&desc).map_err(|e| {
+            unsafe { device.destroy_image(image, None) };
+            e
+        })?;
 
-        if desc.location == MemoryLocation::CpuToGpu {
-            if allocation.is_err() {
-                let mem_loc_preferred_bits =
-                    vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT;
-
-                let memory_type_index_opt =
-                    self.find_memorytype_index(&desc.requirements, mem_loc_preferred_bits);
-
-                let memory_type_index = match memory_type_index_opt {
-                    Some(x) => x as usize,
-                    None => return Err(AllocationError::NoCompatibleMemoryTypeFound),
-                };
-
-                self.memory_types[memory_type_index].allocate(
-                    &self.device,
-                    desc,
-                    self.buffer_image_granularity,
-                    #[cfg(feature = "std")]
-                    backtrace,
-                    &self.allocation_sizes,
-                )
-            } else {
-                allocation
+        if let Err(e) =
+            unsafe { device.bind_image_memory(image, allocation.memory(), allocation.offset()) }
+        {
+            unsafe { device.destroy_image(image, None) };
+            // The bind error is what the caller needs to see, not a failure to clean up after it.
+            if let Err(err) = self.free(allocation) {
+                log::error!("Failed to free the memory of an unbound image: {err}");
             }
-        } else {
-            allocation
+            return Err(AllocationError::Internal(format!(
+                "Unexpected error in vkBindImageMemory: {e:?}"
+            )));
         }
+
+        Ok(Image { image, allocation })
     }
 
-    pub fn free(&mut self, allocation: Allocation) -> Result<()> {
-        if self.debug_settings.log_frees {
-            let name = allocation.name.as_deref().unwrap_or("<null>");
-            debug!("Freeing `{name}`.");
-            #[cfg(feature = "std")]
-            if self.debug_settings.log_stack_traces {
-                let backtrace = Backtrace::force_capture();
-                debug!("Free stack trace: {backtrace}");
-            }
+    /// Destroys an image created through [`Self::create_image()`] and frees its memory.
+    pub fn destroy_image(&mut self, image: Image) -> Result<()> {
+        unsafe { self.inner.backend.device.destroy_image(image.image, None) };
+        self.free(image.allocation)
+    }
+}
+
+/// Serves small allocations for a single thread from slices of the memory blocks of a
+/// [`SyncAllocator`], created through [`SyncAllocator::create_thread_cache()`].
+///
+/// Allocations that are larger than [`ThreadCacheCreateDesc::max_allocation_size`], dedicated or
+/// made from a pool are passed on to the [`SyncAllocator`]. Cached allocations may be freed
+/// through any [`ThreadCache`] of the same allocator or through the [`SyncAllocator`] itself, also
+/// from other threads. Dropping the cache hands unused slices back to the shared memory blocks.
+#[cfg(feature = "std")]
+pub struct ThreadCache<'a> {
+    allocator: &'a SyncAllocator,
+    cache: GenericThreadCache<Allocation>,
+}
+
+#[cfg(feature = "std")]
+impl fmt::Debug for ThreadCache<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ThreadCache").finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "std")]
+impl ThreadCache<'_> {
+    pub fn allocate(&mut self, desc: &AllocationCreateDesc<'_>) -> Result<Allocation> {
+        let allocator = self.allocator;
+        if desc.pool.is_some()
+            || desc.allocation_scheme != AllocationScheme::GpuAllocatorManaged
+            || desc.location == MemoryLocation::Transient
+        {
+            return allocator.allocate(desc);
         }
 
-        if allocation.is_null() {
-            return Ok(());
+        let mut core = &allocator.inner;
+        let size = desc.requirements.size;
+        let alignment = desc.requirements.alignment;
+
+        let backtrace = core.debug_settings().capture_backtrace();
+
+        core.debug_settings()
+            .log_allocation(desc.name, size, alignment);
+
+        if size == 0 || !alignment.is_power_of_two() {
+            return Err(AllocationError::InvalidAllocationCreateDesc);
         }
 
-        self.memory_types[allocation.memory_type_index].free(allocation, &self.device)?;
+        let request = AllocationRequest {
+            name: desc.name,
+            size,
+            alignment,
+            allocation_type: if desc.linear {
+                AllocationType::Linear
+            } else {
+                AllocationType::NonLinear
+            },
+            granularity: allocator.buffer_image_granularity,
+            strategy: desc.allocation_strategy,
+            location: desc.location,
+            priority: None,
+            backtrace,
+        };
 
-        Ok(())
+        let memory_type_index = find_memorytype_index(
+            &core,
+            desc.requirements.memory_type_bits,
+            &desc.memory_type_preferences.with_location(desc.location),
+        )
+        .ok_or(AllocationError::NoCompatibleMemoryTypeFound)?;
+
+        let cached = self
+            .cache
+            .allocate(core, memory_type_index, &request, |reservation| {
+                new_allocation(
+                    &core,
+                    reservation,
+                    "thread cache slice",
+                    request.location,
+                    false,
+                )
+            })?;
+        let Some(cached) = cached else {
+            return allocate_request(&mut core, &allocator.memory_heaps, desc, &request);
+        };
+
+        let allocation = cached.allocation;
+        let template = cached.template;
+        let mapped_ptr = template.mapped_ptr.and_then(|SendSyncPtr(mapped_ptr)| {
+            let offset = allocation.offset - template.offset;
+            let offset_ptr = unsafe { mapped_ptr.as_ptr().add(offset as usize) };
+            core::ptr::NonNull::new(offset_ptr).map(SendSyncPtr)
+        });
+
+        Ok(Allocation {
+            chunk_id: Some(allocation.chunk_id),
+            offset: allocation.offset,
+            size: allocation.size,
+            memory_block_index: allocation.memory_block_index,
+            memory_type_index: allocation.memory_type_index,
+            device_memory: template.device_memory,
+            mapped_ptr,
+            memory_properties: template.memory_properties,
+            name: Some(desc.name.into()),
+            dedicated_allocation: false,
+            cache_slice: Some(cached.slice),
+        })
     }
 
-    pub fn rename_allocation(&mut self, allocation: &mut Allocation, name: &str) -> Result<()> {
-        allocation.name = Some(name.into());
+    /// Frees an allocation, which may also have been made through another [`ThreadCache`] or
+    /// through the [`SyncAllocator`] directly.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn free(&mut self, allocation: Allocation) -> Result<()> {
+        let (Some(slice), Some(chunk_id)) = (&allocation.cache_slice, allocation.chunk_id) else {
+            return self.allocator.free(allocation);
+        };
 
-        if allocation.is_null() {
-            return Ok(());
+        let core = &self.allocator.inner;
+        core.debug_settings().log_free(allocation.name.as_deref());
+        self.cache.free(core, slice, chunk_id)
+    }
+
+    /// Hands the slices without live allocations back to the shared memory blocks. Slices that
+    /// are still in use are no longer allocated from by this cache, and are handed back once
+    /// all their allocations are freed.
+    pub fn flush(&mut self) -> Result<()> {
+        self.cache.flush(&self.allocator.inner)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for ThreadCache<'_> {
+    fn drop(&mut self) {
+        if let Err(err) = self.flush() {
+            log::error!("Failed to flush thread cache: {err}");
         }
+    }
+}
 
-        let mem_type = &mut self.memory_types[allocation.memory_type_index];
-        let mem_block = mem_type.memory_blocks[allocation.memory_block_index]
-            .as_mut()
-            .ok_or_else(|| AllocationError::Internal("Memory block must be Some.".into()))?;
+/// An [`Allocator`] that can be shared between threads, with methods that take `&self`.
+///
+/// Every memory type is locked separately, so threads only wait on each other while they
+/// allocate or free in the same memory type. Pools have to be created before the [`Allocator`]
+/// is converted, after which they can still be allocated from; linear pools, defragmentation and
+/// [`Allocator::set_memory_type_sub_allocator()`] are only available on [`Allocator`].
+#[cfg(feature = "std")]
+pub struct SyncAllocator {
+    inner: SyncGenericAllocator<VulkanBackend>,
+    memory_heaps: Vec<vk::MemoryHeap>,
+    buffer_image_granularity: u64,
+}
 
-        mem_block
-            .sub_allocator
-            .rename_allocation(allocation.chunk_id, name)?;
+#[cfg(feature = "std")]
+impl fmt::Debug for SyncAllocator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.generate_report().fmt(f)
+    }
+}
 
-        Ok(())
+#[cfg(feature = "std")]
+impl From<Allocator> for SyncAllocator {
+    fn from(allocator: Allocator) -> Self {
+        Self {
+            inner: allocator.inner.into(),
+            memory_heaps: allocator.memory_heaps,
+            buffer_image_granularity: allocator.buffer_image_granularity,
+        }
     }
+}
 
-    pub fn report_memory_leaks(&self, log_level: Level) {
-        for (mem_type_i, mem_type) in self.memory_types.iter().enumerate() {
-            for (block_i, mem_block) in mem_type.memory_blocks.iter().enumerate() {
-                if let Some(mem_block) = mem_block {
-                    mem_block
-                        .sub_allocator
-                        .report_memory_leaks(log_level, mem_type_i, block_i);
-                }
-            }
+#[cfg(feature = "std")]
+impl SyncAllocator {
+    pub fn new(desc: &AllocatorCreateDesc) -> Result<Self> {
+        Allocator::new(desc).map(Self::from)
+    }
+
+    pub fn allocate(&self, desc: &AllocationCreateDesc<'_>) -> Result<Allocation> {
+        allocate(
+            &mut &self.inner,
+            &self.memory_heaps,
+            self.buffer_image_granularity,
+            desc,
+        )
+    }
+
+    /// Frees an allocation, which may also have been made through a [`ThreadCache`] of this
+    /// allocator.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn free(&self, allocation: Allocation) -> Result<()> {
+        if let (Some(slice), Some(chunk_id)) = (&allocation.cache_slice, allocation.chunk_id) {
+            self.inner
+                .debug_settings()
+                .log_free(allocation.name.as_deref());
+            return slice.free(&self.inner, chunk_id);
         }
+
+        free(&mut &self.inner, allocation)
+    }
+
+    /// See [`Allocator::flush_allocation()`].
+    pub fn flush_allocation(&self, allocation: &Allocation, offset: u64, size: u64) -> Result<()> {
+        flush_allocation(
+            &self.inner.backend().device,
+            &&self.inner,
+            allocation,
+            offset,
+            size,
+        )
     }
 
-    fn find_memorytype_index(
+    /// See [`Allocator::invalidate_allocation()`].
+    pub fn invalidate_allocation(
         &self,
-        memory_req: &vk::MemoryRequirements,
-        flags: vk::MemoryPropertyFlags,
-    ) -> Option<u32> {
-        self.memory_types
-            .iter()
-            .find(|memory_type| {
-                (1 << memory_type.memory_type_index) & memory_req.memory_type_bits != 0
-                    && memory_type.memory_properties.contains(flags)
-            })
-            .map(|memory_type| memory_type.memory_type_index as _)
+        allocation: &Allocation,
+        offset: u64,
+        size: u64,
+    ) -> Result<()> {
+        invalidate_allocation(
+            &self.inner.backend().device,
+            &&self.inner,
+            allocation,
+            offset,
+            size,
+        )
     }
 
-    pub fn generate_report(&self) -> AllocatorReport {
-        let mut allocations = vec![];
-        let mut blocks = vec![];
-        let mut total_capacity_bytes = 0;
-
-        for memory_type in &self.memory_types {
-            for block in memory_type.memory_blocks.iter().flatten() {
-                total_capacity_bytes += block.size;
-                let first_allocation = allocations.len();
-                allocations.extend(block.sub_allocator.report_allocations());
-                blocks.push(MemoryBlockReport {
-                    size: block.size,
-                    allocations: first_allocation..allocations.len(),
-                });
-            }
+    /// Renames an allocation. Allocations made through a [`ThreadCache`] only change their own
+    /// name, reports keep listing the slice of the cache they live in.
+    pub fn rename_allocation(&self, allocation: &mut Allocation, name: &str) -> Result<()> {
+        if allocation.cache_slice.is_some() {
+            allocation.name = Some(name.into());
+            return Ok(());
         }
 
-        let total_allocated_bytes = allocations.iter().map(|report| report.size).sum();
+        rename_allocation(&mut &self.inner, allocation, name)
+    }
 
-        AllocatorReport {
-            allocations,
-            blocks,
-            total_allocated_bytes,
-            total_capacity_bytes,
-        }
+    /// Creates a cache that serves small allocations for a single thread from slices of the
+    /// shared memory blocks, without taking the lock of their memory type.
+    pub fn create_thread_cache(&self, desc: &ThreadCacheCreateDesc) -> Result<ThreadCache<'_>> {
+        Ok(ThreadCache {
+            allocator: self,
+            cache: GenericThreadCache::new(desc)?,
+        })
+    }
+
+    pub fn report_memory_leaks(&self, log_level: Level) {
+        self.inner.report_memory_leaks(log_level);
+    }
+
+    /// Generates a report while other threads may keep allocating and freeing, so it is not
+    /// necessarily a consistent snapshot of the whole allocator.
+    pub fn generate_report(&self) -> AllocatorReport {
+        self.inner.generate_report()
     }
 
     /// Current total capacity of memory blocks allocated on the device, in bytes
     pub fn capacity(&self) -> u64 {
-        let mut total_capacity_bytes = 0;
+        self.inner.capacity()
+    }
 
-        for memory_type in &self.memory_types {
-            for block in memory_type.memory_blocks.iter().flatten() {
-                total_capacity_bytes += block.size;
-            }
+    /// See [`Allocator::trim()`].
+    pub fn trim(&self) {
+        self.inner.trim();
+    }
+
+    /// See [`Allocator::set_current_frame()`].
+    pub fn set_current_frame(&self, frame: u64) {
+        self.inner.set_current_frame(frame);
+    }
+}
+
+/// Allocates through the core of either an [`Allocator`] or a [`SyncAllocator`].
+fn allocate(
+    core: &mut impl AllocatorCore<VulkanBackend>,
+    memory_heaps: &[vk::MemoryHeap],
+    buffer_image_granularity: u64,
+    desc: &AllocationCreateDesc<'_>,
+) -> Result<Allocation> {
+    let size = desc.requirements.size;
+    let alignment = desc.requirements.alignment;
+
+    #[cfg(feature = "std")]
+    let backtrace = core.debug_settings().capture_backtrace();
+
+    core.debug_settings()
+        .log_allocation(desc.name, size, alignment);
+
+    if size == 0 || !alignment.is_power_of_two() || !is_valid_priority(desc.priority) {
+        return Err(AllocationError::InvalidAllocationCreateDesc);
+    }
+
+    let request = AllocationRequest {
+        name: desc.name,
+        size,
+        alignment,
+        allocation_type: if desc.linear {
+            AllocationType::Linear
+        } else {
+            AllocationType::NonLinear
+        },
+        granularity: buffer_image_granularity,
+        strategy: desc.allocation_strategy,
+        location: desc.location,
+        priority: desc.priority,
+        #[cfg(feature = "std")]
+        backtrace,
+    };
+
+    allocate_request(core, memory_heaps, desc, &request)
+}
+
+/// Allocates `request` for `desc`, once it has been validated.
+fn allocate_request(
+    core: &mut impl AllocatorCore<VulkanBackend>,
+    memory_heaps: &[vk::MemoryHeap],
+    desc: &AllocationCreateDesc<'_>,
+    request: &AllocationRequest<'_>,
+) -> Result<Allocation> {
+    if let Some(pool) = desc.pool {
+        if desc.allocation_scheme != AllocationScheme::GpuAllocatorManaged {
+            return Err(AllocationError::InvalidAllocationCreateDesc);
+        }
+        if (1 << pool.memory_type_index) & desc.requirements.memory_type_bits == 0 {
+            return Err(AllocationError::NoCompatibleMemoryTypeFound);
         }
 
-        total_capacity_bytes
+        let allocation =
+            core.allocate_from_pool(pool.memory_type_index, pool.pool_index, request)?;
+        return new_allocation(core, allocation, desc.name, desc.location, false);
     }
+
+    let preferences = desc.memory_type_preferences.with_location(desc.location);
+    let memory_type_index =
+        find_memorytype_index(core, desc.requirements.memory_type_bits, &preferences)
+            .ok_or(AllocationError::NoCompatibleMemoryTypeFound)?;
+
+    let dedicated_resource = match desc.allocation_scheme {
+        AllocationScheme::GpuAllocatorManaged => None,
+        allocation_scheme => Some(allocation_scheme),
+    };
+
+    //Do not try to create a block if the heap is smaller than the required size (avoids validation warnings).
+    let heap_index = core.memory_type_properties(memory_type_index).heap_index;
+    let allocation = if request.size > memory_heaps[heap_index].size {
+        Err(AllocationError::OutOfMemory)
+    } else {
+        core.allocate(memory_type_index, request, dedicated_resource)
+    };
+
+    let allocation = match allocation {
+        // Uploads fall back to the next best memory type when the best one is full, like when
+        // device local host visible memory is limited to a small BAR.
+        Err(AllocationError::OutOfMemory | AllocationError::OutOfBudget)
+            if desc.location == MemoryLocation::CpuToGpu =>
+        {
+            let memory_type_bits = desc.requirements.memory_type_bits & !(1 << memory_type_index);
+            let memory_type_index = find_memorytype_index(core, memory_type_bits, &preferences)
+                .ok_or(AllocationError::NoCompatibleMemoryTypeFound)?;
+
+            core.allocate(memory_type_index, request, dedicated_resource)
+        }
+        allocation => allocation,
+    }?;
+
+    new_allocation(
+        core,
+        allocation,
+        desc.name,
+        desc.location,
+        dedicated_resource.is_some(),
+    )
 }
 
-impl Drop for Allocator {
-    fn drop(&mut self) {
-        if self.debug_settings.log_leaks_on_shutdown {
-            self.report_memory_leaks(Level::Warn);
+/// Creates the [`Allocation`] handle for memory allocated through the [`GenericAllocator`] for
+/// `location`.
+fn new_allocation(
+    core: &impl AllocatorCore<VulkanBackend>,
+    allocation: SubAllocation,
+    name: &str,
+    location: MemoryLocation,
+    dedicated_allocation: bool,
+) -> Result<Allocation> {
+    let (device_memory, mapped_ptr) = core.with_memory_block(&allocation, |mem_block| {
+        // Transient attachments are never mapped, even when they end up in host visible memory.
+        let transient = location == MemoryLocation::Transient || mem_block.transient;
+        let mapped_ptr = if transient {
+            None
+        } else if let Some(SendSyncPtr(mapped_ptr)) = mem_block.memory.mapped_ptr {
+            let offset_ptr = unsafe { mapped_ptr.as_ptr().add(allocation.offset as usize) };
+            core::ptr::NonNull::new(offset_ptr).map(SendSyncPtr)
+        } else {
+            None
+        };
+        (mem_block.memory.device_memory, mapped_ptr)
+    })?;
+
+    Ok(Allocation {
+        chunk_id: Some(allocation.chunk_id),
+        offset: allocation.offset,
+        size: allocation.size,
+        memory_block_index: allocation.memory_block_index,
+        memory_type_index: allocation.memory_type_index,
+        device_memory,
+        mapped_ptr,
+        memory_properties: core
+            .memory_type_properties(allocation.memory_type_index)
+            .memory_properties,
+        name: Some(name.into()),
+        dedicated_allocation,
+        #[cfg(feature = "std")]
+        cache_slice: None,
+    })
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn free(core: &mut impl AllocatorCore<VulkanBackend>, allocation: Allocation) -> Result<()> {
+    core.debug_settings().log_free(allocation.name.as_deref());
+
+    if allocation.is_null() {
+        return Ok(());
+    }
+
+    core.free(
+        allocation.memory_type_index,
+        allocation.memory_block_index,
+        allocation.chunk_id,
+    )?;
+
+    Ok(())
+}
+
+fn rename_allocation(
+    core: &mut impl AllocatorCore<VulkanBackend>,
+    allocation: &mut Allocation,
+    name: &str,
+) -> Result<()> {
+    allocation.name = Some(name.into());
+
+    if allocation.is_null() {
+        return Ok(());
+    }
+
+    core.rename_allocation(
+        allocation.memory_type_index,
+        allocation.memory_block_index,
+        allocation.chunk_id,
+        name,
+    )
+}
+
+/// Returns the range of device memory covering `size` bytes at `offset` in `allocation`, widened
+/// to whole multiples of `nonCoherentAtomSize` or to the end of the memory of a dedicated
+/// allocation. Returns [`None`] if the allocation is not in memory that has to be flushed and
+/// invalidated, or if the range is empty.
+fn non_coherent_range(
+    core: &impl AllocatorCore<VulkanBackend>,
+    allocation: &Allocation,
+    offset: u64,
+    size: u64,
+) -> Result<Option<vk::MappedMemoryRange<'static>>> {
+    if allocation.is_null() || !is_non_coherent(allocation.memory_properties) {
+        return Ok(None);
+    }
+
+    let size = if size == vk::WHOLE_SIZE {
+        allocation.size.saturating_sub(offset)
+    } else {
+        size
+    };
+    if offset
+        .checked_add(size)
+        .map_or(true, |end| end > allocation.size)
+    {
+        return Err(AllocationError::InvalidRange);
+    }
+    if size == 0 {
+        return Ok(None);
+    }
+
+    // Allocations in non-coherent memory are aligned to the atom size, and so is their size, so
+    // the widened range stays within the allocation. Dedicated memory is exactly as large as its
+    // allocation though, so the range may end at the end of the memory instead.
+    let atom_size = core
+        .memory_type_properties(allocation.memory_type_index)
+        .non_coherent_atom_size;
+    let start = align_down(allocation.offset + offset, atom_size);
+    let end = align_up(allocation.offset + offset + size, atom_size);
+    let end = if allocation.dedicated_allocation {
+        end.min(allocation.offset + allocation.size)
+    } else {
+        end
+    };
+
+    Ok(Some(
+        vk::MappedMemoryRange::default()
+            .memory(allocation.device_memory)
+            .offset(start)
+            .size(end - start),
+    ))
+}
+
+fn flush_allocation(
+    device: &ash::Device,
+    core: &impl AllocatorCore<VulkanBackend>,
+    allocation: &Allocation,
+    offset: u64,
+    size: u64,
+) -> Result<()> {
+    let Some(range) = non_coherent_range(core, allocation, offset, size)? else {
+        return Ok(());
+    };
+
+    unsafe { device.flush_mapped_memory_ranges(&[range]) }.map_err(|e| match e {
+        vk::Result::ERROR_OUT_OF_DEVICE_MEMORY => AllocationError::OutOfMemory,
+        e => AllocationError::Internal(format!(
+            "Unexpected error in vkFlushMappedMemoryRanges: {e:?}"
+        )),
+    })
+}
+
+fn invalidate_allocation(
+    device: &ash::Device,
+    core: &impl AllocatorCore<VulkanBackend>,
+    allocation: &Allocation,
+    offset: u64,
+    size: u64,
+) -> Result<()> {
+    let Some(range) = non_coherent_range(core, allocation, offset, size)? else {
+        return Ok(());
+    };
+
+    unsafe { device.invalidate_mapped_memory_ranges(&[range]) }.map_err(|e| match e {
+        vk::Result::ERROR_OUT_OF_DEVICE_MEMORY => AllocationError::OutOfMemory,
+        e => AllocationError::Internal(format!(
+            "Unexpected error in vkInvalidateMappedMemoryRanges: {e:?}"
+        )),
+    })
+}
+
+/// Scores every memory type in `memory_type_bits` against `preferences`, see
+/// [`MemoryTypePreferences`], and returns the index of the best one.
+fn find_memorytype_index(
+    core: &impl AllocatorCore<VulkanBackend>,
+    memory_type_bits: u32,
+    preferences: &MemoryTypePreferences,
+) -> Option<usize> {
+    preferences.find_memory_type_index(
+        (0..core.memory_type_count()).map(|memory_type_index| {
+            core.memory_type_properties(memory_type_index)
+                .memory_properties
+        }),
+        memory_type_bits,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEVICE_LOCAL: vk::MemoryPropertyFlags = vk::MemoryPropertyFlags::DEVICE_LOCAL;
+    const HOST_VISIBLE: vk::MemoryPropertyFlags = vk::MemoryPropertyFlags::HOST_VISIBLE;
+    const HOST_COHERENT: vk::MemoryPropertyFlags = vk::MemoryPropertyFlags::HOST_COHERENT;
+    const HOST_CACHED: vk::MemoryPropertyFlags = vk::MemoryPropertyFlags::HOST_CACHED;
+
+    /// The memory types of a typical discrete GPU with Resizable BAR.
+    fn memory_types() -> [vk::MemoryPropertyFlags; 4] {
+        [
+            DEVICE_LOCAL,
+            HOST_VISIBLE | HOST_COHERENT,
+            HOST_VISIBLE | HOST_CACHED,
+            DEVICE_LOCAL | HOST_VISIBLE | HOST_COHERENT,
+        ]
+    }
+
+    fn find(location: MemoryLocation, preferences: MemoryTypePreferences) -> Option<usize> {
+        preferences
+            .with_location(location)
+            .find_memory_type_index(memory_types(), u32::MAX)
+    }
+
+    #[test]
+    fn picks_memory_types_by_location() {
+        let preferences = MemoryTypePreferences::default();
+        // Host visible device local memory is left to uploads.
+        assert_eq!(find(MemoryLocation::GpuOnly, preferences), Some(0));
+        assert_eq!(find(MemoryLocation::CpuToGpu, preferences), Some(3));
+        assert_eq!(find(MemoryLocation::GpuToCpu, preferences), Some(1));
+        assert_eq!(find(MemoryLocation::Unknown, preferences), Some(0));
+    }
+
+    #[test]
+    fn only_picks_memory_types_in_memory_type_bits() {
+        let preferences = MemoryTypePreferences::default().with_location(MemoryLocation::CpuToGpu);
+        assert_eq!(
+            preferences.find_memory_type_index(memory_types(), !(1 << 3)),
+            Some(1)
+        );
+        assert_eq!(
+            preferences.find_memory_type_index(memory_types(), 1 << 0),
+            None
+        );
+
+        // Both the memory requirements and the preferences narrow down the memory types.
+        let preferences = MemoryTypePreferences {
+            memory_type_bits: 1 << 1,
+            ..MemoryTypePreferences::default()
         }
+        .with_location(MemoryLocation::CpuToGpu);
+        assert_eq!(
+            preferences.find_memory_type_index(memory_types(), u32::MAX),
+            Some(1)
+        );
+        assert_eq!(
+            preferences.find_memory_type_index(memory_types(), 1 << 3),
+            None
+        );
+    }
 
-        // Free all remaining memory blocks
-        for mem_type in self.memory_types.iter_mut() {
-            for mem_block in mem_type.memory_blocks.iter_mut() {
-                let block = mem_block.take();
-                if let Some(block) = block {
-                    block.destroy(&self.device);
+    #[test]
+    fn never_picks_memory_types_missing_a_required_flag() {
+        let preferences = MemoryTypePreferences {
+            required_flags: HOST_CACHED,
+            ..MemoryTypePreferences::default()
+        };
+        assert_eq!(find(MemoryLocation::GpuOnly, preferences), None);
+        // Host cached memory is not host coherent here, which is only allowed on request.
+        assert_eq!(find(MemoryLocation::GpuToCpu, preferences), None);
+        assert_eq!(
+            find(
+                MemoryLocation::GpuToCpu,
+                MemoryTypePreferences {
+                    allow_non_coherent: true,
+                    ..preferences
                 }
-            }
-        }
+            ),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn weighs_preferred_and_not_preferred_flags() {
+        let flags = [
+            HOST_VISIBLE | HOST_COHERENT,
+            HOST_VISIBLE | HOST_CACHED,
+            HOST_VISIBLE | HOST_COHERENT | HOST_CACHED,
+        ];
+        let find = |preferences: MemoryTypePreferences| {
+            preferences.find_memory_type_index(flags, u32::MAX)
+        };
+
+        // Each missing preferred flag and each present flag that is not preferred costs one.
+        let preferences = MemoryTypePreferences {
+            preferred_flags: HOST_COHERENT | HOST_CACHED,
+            ..MemoryTypePreferences::default()
+        };
+        assert_eq!(find(preferences), Some(2));
+        assert_eq!(
+            find(MemoryTypePreferences {
+                not_preferred_flags: HOST_COHERENT,
+                ..preferences
+            }),
+            Some(1)
+        );
+        assert_eq!(
+            find(MemoryTypePreferences {
+                preferred_flags: HOST_COHERENT,
+                not_preferred_flags: HOST_CACHED,
+                ..MemoryTypePreferences::default()
+            }),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn picks_the_lowest_index_on_ties() {
+        let flags = [HOST_VISIBLE, DEVICE_LOCAL, DEVICE_LOCAL, HOST_VISIBLE];
+        let preferences = MemoryTypePreferences::default();
+        assert_eq!(preferences.find_memory_type_index(flags, u32::MAX), Some(0));
+        assert_eq!(preferences.find_memory_type_index(flags, !1), Some(1));
+        assert_eq!(
+            preferences
+                .with_location(MemoryLocation::GpuOnly)
+                .find_memory_type_index(flags, u32::MAX),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn allow_non_coherent_prefers_host_cached_memory() {
+        let allow_non_coherent = MemoryTypePreferences {
+            allow_non_coherent: true,
+            ..MemoryTypePreferences::default()
+        };
+        assert_eq!(
+            find(MemoryLocation::GpuToCpu, MemoryTypePreferences::default()),
+            Some(1)
+        );
+        assert_eq!(find(MemoryLocation::GpuToCpu, allow_non_coherent), Some(2));
+
+        // Uploads still prefer host coherent memory.
+        assert_eq!(find(MemoryLocation::CpuToGpu, allow_non_coherent), Some(3));
+        let flags = [HOST_VISIBLE | HOST_CACHED, DEVICE_LOCAL];
+        assert_eq!(
+            MemoryTypePreferences::default()
+                .with_location(MemoryLocation::CpuToGpu)
+                .find_memory_type_index(flags, u32::MAX),
+            None
+        );
+        assert_eq!(
+            allow_non_coherent
+                .with_location(MemoryLocation::CpuToGpu)
+                .find_memory_type_index(flags, u32::MAX),
+            Some(0)
+        );
     }
 }
@@ -3,7 +3,7 @@
 use super::Allocator;
 use crate::visualizer::{
     render_allocation_reports_ui, AllocationReportVisualizeSettings, ColorScheme,
-    MemoryChunksVisualizationSettings,
+    MemoryChunksVisualizationSettings, SubAllocatorVisualizer,
 };
 
 struct AllocatorVisualizerBlockWindow {
@@ -62,9 +62,9 @@ impl AllocatorVisualizer {
         );
 
         ui.collapsing(
-            format!("Memory Types: ({} types)", alloc.memory_types.len()),
+            format!("Memory Types: ({} types)", alloc.inner.memory_types.len()),
             |ui| {
-                for (mem_type_idx, mem_type) in alloc.memory_types.iter().enumerate() {
+                for (mem_type_idx, mem_type) in alloc.inner.memory_types.iter().enumerate() {
                     ui.collapsing(
                         format!(
                             "Type: {} ({} blocks)",
@@ -86,8 +86,11 @@ impl AllocatorVisualizer {
                                 .filter(|block| block.is_some())
                                 .count();
 
-                            ui.label(format!("properties: {:?}", mem_type.memory_properties));
-                            ui.label(format!("heap index: {}", mem_type.heap_index));
+                            ui.label(format!(
+                                "properties: {:?}",
+                                mem_type.properties.memory_properties
+                            ));
+                            ui.label(format!("heap index: {}", mem_type.properties.heap_index));
                             ui.label(format!("total block size: {} KiB", total_block_size / 1024));
                             ui.label(format!("total allocated:  {} KiB", total_allocated / 1024));
                             ui.label(format!("block count: {active_block_count}"));
@@ -105,9 +108,9 @@ impl AllocatorVisualizer {
                                     ));
                                     ui.label(format!(
                                         "vk device memory: 0x{:x}",
-                                        block.device_memory.as_raw()
+                                        block.memory.device_memory.as_raw()
                                     ));
-                                    if let Some(mapped_ptr) = block.mapped_ptr {
+                                    if let Some(mapped_ptr) = block.memory.mapped_ptr {
                                         ui.label(format!(
                                             "mapped pointer: {:#p}",
                                             mapped_ptr.0.as_ptr()
@@ -171,8 +174,8 @@ impl AllocatorVisualizer {
             .default_size([1920.0 * 0.5, 1080.0 * 0.5])
             .open(&mut open)
             .show(ctx, |ui| {
-                let memblock = &allocator.memory_types[window.memory_type_index].memory_blocks
-                    [window.block_index]
+                let memblock = &allocator.inner.memory_types[window.memory_type_index]
+                    .memory_blocks[window.block_index]
                     .as_ref();
                 if let Some(memblock) = memblock {
                     ui.label(format!(
@@ -184,7 +187,7 @@ impl AllocatorVisualizer {
 
                     window
                         .settings
-                        .ui(ui, allocator.debug_settings.store_stack_traces);
+                        .ui(ui, allocator.inner.debug_settings.store_stack_traces);
 
                     ui.separator();
 
@@ -205,6 +208,7 @@ impl AllocatorVisualizer {
             ui,
             &mut self.breakdown_settings,
             allocator
+                .inner
                 .memory_types
                 .iter()
                 .flat_map(|memory_type| memory_type.memory_blocks.iter())